@@ -0,0 +1,68 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+};
+
+use virtualfriend::{
+    config::{EmulatorConfig, PowerOnState},
+    gamepad::GamepadInputs,
+    lockstep::{run_lockstep, LockstepResult},
+    trace::MednafenTraceReader,
+    VirtualFriend,
+};
+
+const DEFAULT_HISTORY_LENGTH: usize = 32;
+
+fn main() {
+    let args = std::env::args().collect::<Vec<String>>();
+
+    if args.len() != 3 && args.len() != 4 {
+        println!(
+            "Usage: lockstep [path to ROM] [path to Mednafen trace] [optional instruction history length]"
+        );
+
+        std::process::exit(1)
+    }
+
+    let rom = fs::read(&args[1]).expect("Could not read ROM");
+    let trace = File::open(&args[2]).expect("Could not open trace");
+
+    let history_length = args
+        .get(3)
+        .map(|length| length.parse().expect("Invalid history length"))
+        .unwrap_or(DEFAULT_HISTORY_LENGTH);
+
+    let mut virtualfriend = VirtualFriend::new_with_config(
+        rom,
+        EmulatorConfig {
+            power_on: PowerOnState::Mednafen,
+            ..Default::default()
+        },
+    )
+    .expect("Invalid ROM");
+
+    let result = run_lockstep(
+        &mut virtualfriend,
+        MednafenTraceReader::new(BufReader::new(trace)),
+        GamepadInputs::default(),
+        history_length,
+    )
+    .expect("Could not read trace");
+
+    match result {
+        LockstepResult::Matched { count } => {
+            println!("Matched all {count} instructions");
+        }
+        LockstepResult::Diverged(divergence) => {
+            print!("{divergence}");
+
+            std::process::exit(2)
+        }
+        LockstepResult::Crashed { index, report } => {
+            println!("Crashed before trace record {index}");
+            print!("{report}");
+
+            std::process::exit(2)
+        }
+    }
+}
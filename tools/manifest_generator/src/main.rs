@@ -5,7 +5,7 @@ use std::{
 };
 
 use reedline::{DefaultPrompt, DefaultPromptSegment, Reedline, Signal};
-use virtualfriend::manifest::Metadata;
+use virtualfriend::{cartridge::RomInfo, manifest::Metadata};
 use virtualfriend_desktop::{build_client, ThreadFrame};
 use winit::event_loop::EventLoop;
 
@@ -69,7 +69,7 @@ fn main() {
 
         let rom_data = fs::read(&path.path()).expect("Could not load ROM");
 
-        let folder_hash = format!("{:x}", md5::compute(rom_data));
+        let folder_hash = RomInfo::new(&rom_data).md5_hex();
 
         let rom_path = path.path().with_extension("");
         let rom_name = rom_path.file_name().unwrap().to_str().unwrap();
@@ -0,0 +1,90 @@
+use std::time::Instant;
+
+use virtualfriend::{assembler::assemble_rom, gamepad::GamepadInputs, VirtualFriend};
+
+/// Frames run per measurement. The first frame only executes the reset vector
+const FRAME_COUNT: usize = 300;
+
+/// Busy loop of ALU instructions and loads, which never halts. The instruction cache is enabled, so the loop runs in the
+/// same number of cycles from ROM and from WRAM
+const LOOP: &str = "
+    .align  4
+body:
+    add     0x1, r10
+    mov     r10, r11
+    shl     0x3, r11
+    xor     r10, r11
+    andi    0xFF, r11, r12
+    or      r12, r13
+    ld.w    0x0[r20], r14
+    cmp     r10, r14
+    bnz     body
+    br      body
+body_end:
+";
+
+/// Runs the loop in place in ROM, where each instruction is decoded once and then served by the decode cache
+fn rom_source() -> String {
+    format!(
+        "
+    ldsr    r0, psw
+    mov     0x2, r6
+    ldsr    r6, chcw
+    movhi   0x0500, r0, r20
+    movea   0x4000, r20, r20
+    jr      body
+{LOOP}"
+    )
+}
+
+/// Copies the loop to WRAM and runs it there, where every instruction is decoded each time it executes
+fn wram_source() -> String {
+    format!(
+        "
+    ldsr    r0, psw
+    mov     0x2, r6
+    ldsr    r6, chcw
+    movhi   0x0500, r0, r20
+    movea   0x4000, r20, r20
+    movhi   hi(body), r0, r6
+    movea   lo(body), r6, r6
+    movhi   hi(body_end), r0, r7
+    movea   lo(body_end), r7, r7
+    movhi   0x0500, r0, r8
+    mov     r8, r9
+copy:
+    ld.w    0x0[r6], r10
+    st.w    r10, 0x0[r9]
+    add     0x4, r6
+    add     0x4, r9
+    cmp     r7, r6
+    bnz     copy
+    mov     r0, r10
+    jmp     [r8]
+{LOOP}"
+    )
+}
+
+fn measure(name: &str, source: &str) {
+    let mut virtualfriend = VirtualFriend::new(assemble_rom(source).unwrap()).unwrap();
+
+    let start = Instant::now();
+
+    for _ in 0..FRAME_COUNT {
+        virtualfriend.run_video_frame(GamepadInputs::default());
+    }
+
+    let elapsed = start.elapsed();
+
+    let iterations = virtualfriend.registers().general_purpose[10];
+
+    println!(
+        "{name}: {:.3}ms per frame, {iterations} iterations",
+        elapsed.as_secs_f64() * 1000.0 / FRAME_COUNT as f64
+    );
+}
+
+fn main() {
+    measure("ROM (decode cache)", &rom_source());
+    measure("WRAM (decoded every time)", &wram_source());
+}
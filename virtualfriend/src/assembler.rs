@@ -0,0 +1,840 @@
+// A small V810 assembler for building self-contained test ROMs.
+//
+// Accepts the syntax produced by `disasm`, along with labels, `hi()`/`lo()` for loading addresses with a
+// MOVHI/MOVEA pair, and the `.word`, `.hword`, and `.align` directives. Comments start with `;` or `//`.
+
+use std::{collections::HashMap, fmt};
+
+use crate::instruction::*;
+
+/// Address of the start of ROM, where `assemble_rom` places the program
+pub const ROM_BASE_ADDRESS: u32 = 0x0700_0000;
+
+const MIN_ROM_SIZE: usize = 1024;
+
+/// Bytes at the end of the ROM reserved for the game header and the interrupt and exception vectors
+const ROM_FOOTER_SIZE: usize = 0x220;
+
+const RESET_VECTOR: u32 = 0xFFFF_FFF0;
+
+/// Vectors that jump to the handler with the given label, if the program defines it. Otherwise the vector executes
+/// RETI
+const VECTORS: &[(u32, &str)] = &[
+    (0xFFFF_FE00, "gamepad_interrupt"),
+    (0xFFFF_FE10, "timer_interrupt"),
+    (0xFFFF_FE20, "game_pak_interrupt"),
+    (0xFFFF_FE30, "communication_interrupt"),
+    (0xFFFF_FE40, "vip_interrupt"),
+    (0xFFFF_FF60, "float_exception"),
+    (0xFFFF_FF80, "zero_divide_exception"),
+    (0xFFFF_FF90, "illegal_opcode_exception"),
+    // TRAP vectors 0-15
+    (0xFFFF_FFA0, "trap_0"),
+    // TRAP vectors 16-31
+    (0xFFFF_FFB0, "trap_16"),
+    (0xFFFF_FFC0, "address_trap"),
+    (0xFFFF_FFD0, "duplexed_exception"),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssemblerError {
+    /// 1-based line number of the offending statement
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssemblerError {}
+
+/// Assembled code, along with the address of every label
+#[derive(Debug)]
+pub struct Program {
+    pub base_address: u32,
+    pub bytes: Vec<u8>,
+    labels: HashMap<String, u32>,
+}
+
+impl Program {
+    pub fn label(&self, name: &str) -> Option<u32> {
+        self.labels.get(name).copied()
+    }
+}
+
+/// Assembles `source` into a ROM image. The program starts at 0x07000000, where the reset vector jumps to.
+///
+/// Interrupt and exception vectors jump to the handlers named in `VECTORS`, if they are defined, otherwise they
+/// return immediately with RETI. The ROM is padded to the smallest power of two that fits
+pub fn assemble_rom(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    let program = assemble(ROM_BASE_ADDRESS, source)?;
+
+    let size = (program.bytes.len() + ROM_FOOTER_SIZE)
+        .next_power_of_two()
+        .max(MIN_ROM_SIZE);
+
+    let mut rom = vec![0; size];
+
+    rom[..program.bytes.len()].copy_from_slice(&program.bytes);
+
+    let rom_offset = |address: u32| address as usize & (size - 1);
+
+    let mut put = |address: u32, halfwords: &[u16]| {
+        for (i, halfword) in halfwords.iter().enumerate() {
+            let offset = rom_offset(address) + i * 2;
+
+            rom[offset..offset + 2].copy_from_slice(&halfword.to_le_bytes());
+        }
+    };
+
+    for &(vector, label) in VECTORS {
+        match program.label(label) {
+            Some(handler) => {
+                // The vectors are mirrors of ROM, so jump relative to the handler's offset in the same mirror
+                let disp = rom_offset(handler) as i64 - rom_offset(vector) as i64;
+
+                put(vector, &format_iv(OPCODE_BITS_JR, disp as u32));
+            }
+            None => put(vector, &[format_ii(OPCODE_BITS_RETI, 0, 0)]),
+        }
+    }
+
+    put(
+        RESET_VECTOR,
+        &[
+            format_v(OPCODE_BITS_MOVHI, 0, 1)[0],
+            high_half(ROM_BASE_ADDRESS as i64),
+            format_v(OPCODE_BITS_MOVEA, 1, 1)[0],
+            ROM_BASE_ADDRESS as u16,
+            format_i(OPCODE_BITS_JMP, 1, 0),
+        ],
+    );
+
+    Ok(rom)
+}
+
+/// Assembles `source` as code starting at `base_address`
+pub fn assemble(base_address: u32, source: &str) -> Result<Program, AssemblerError> {
+    let mut statements = Vec::new();
+    let mut labels = HashMap::new();
+
+    let mut address = base_address;
+
+    // First pass: Determine the address of every statement and label
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |message: String| AssemblerError {
+            line: line_number,
+            message,
+        };
+
+        let mut line = strip_comment(line).trim();
+
+        while let Some((label, rest)) = split_label(line) {
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(error(format!("Duplicate label {label}")));
+            }
+
+            line = rest.trim();
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let statement = Statement::parse(line).map_err(error)?;
+
+        let size = statement.size(address);
+
+        statements.push((line_number, address, statement));
+
+        address = address.wrapping_add(size);
+    }
+
+    // Second pass: Encode each statement now that all labels are known
+    let assembler = Assembler { labels };
+    let mut bytes = Vec::new();
+
+    for (line_number, address, statement) in statements {
+        assembler
+            .encode(&statement, address, &mut bytes)
+            .map_err(|message| AssemblerError {
+                line: line_number,
+                message,
+            })?;
+    }
+
+    Ok(Program {
+        base_address,
+        bytes,
+        labels: assembler.labels,
+    })
+}
+
+enum Statement {
+    Instruction {
+        mnemonic: String,
+        operands: Vec<String>,
+    },
+    Word(Vec<String>),
+    Halfword(Vec<String>),
+    Align(u32),
+}
+
+impl Statement {
+    fn parse(line: &str) -> Result<Statement, String> {
+        let (mnemonic, operands) = match line.split_once(char::is_whitespace) {
+            Some((mnemonic, operands)) => (mnemonic, operands.trim()),
+            None => (line, ""),
+        };
+
+        let mnemonic = mnemonic.to_lowercase();
+
+        let operands = if operands.is_empty() {
+            Vec::new()
+        } else {
+            operands
+                .split(',')
+                .map(|operand| operand.trim().to_string())
+                .collect()
+        };
+
+        let statement = match mnemonic.as_str() {
+            ".word" => Statement::Word(operands),
+            ".hword" => Statement::Halfword(operands),
+            ".align" => {
+                let [alignment] = operands.as_slice() else {
+                    return Err(".align expects a single alignment".to_string());
+                };
+
+                let alignment = parse_number(alignment)
+                    .filter(|alignment| *alignment > 0 && (*alignment as u64).is_power_of_two())
+                    .ok_or_else(|| format!("Invalid alignment {alignment}"))?;
+
+                Statement::Align(alignment as u32)
+            }
+            _ => {
+                if Kind::from_mnemonic(&mnemonic).is_none() {
+                    return Err(format!("Unknown mnemonic {mnemonic}"));
+                }
+
+                Statement::Instruction { mnemonic, operands }
+            }
+        };
+
+        Ok(statement)
+    }
+
+    fn size(&self, address: u32) -> u32 {
+        match self {
+            Statement::Instruction { mnemonic, .. } => {
+                let kind = Kind::from_mnemonic(mnemonic).unwrap();
+
+                if kind.has_second_halfword() {
+                    4
+                } else {
+                    2
+                }
+            }
+            Statement::Word(values) => values.len() as u32 * 4,
+            Statement::Halfword(values) => values.len() as u32 * 2,
+            Statement::Align(alignment) => address.next_multiple_of(*alignment) - address,
+        }
+    }
+}
+
+/// How a mnemonic's operands are parsed and encoded
+#[derive(Clone, Copy)]
+enum Kind {
+    /// Format I with `reg1, reg2`, or Format II with `imm5, reg2`
+    RegisterOrImmediate {
+        register_opcode: u16,
+        immediate_opcode: u16,
+        signed: bool,
+    },
+    /// Format I with `reg1, reg2`
+    Registers(u16),
+    /// Format I with `[reg1]`
+    Jump,
+    /// Format II without operands
+    NoOperands(u16),
+    Setf,
+    Ldsr,
+    Stsr,
+    Trap,
+    BitString(u32),
+    /// Format III
+    Branch(u16),
+    Nop,
+    /// Format IV
+    Displacement(u16),
+    /// Format V with `imm16, reg1, reg2`
+    Immediate16(u16),
+    /// Format VI with `disp16[reg1], reg2`
+    Load(u16),
+    /// Format VI with `reg2, disp16[reg1]`
+    Store(u16),
+    /// Format VII with `reg1, reg2`
+    Extended(u16),
+    /// Format VII with `reg2`
+    ExtendedRegister(u16),
+}
+
+impl Kind {
+    fn from_mnemonic(mnemonic: &str) -> Option<Kind> {
+        let register_or_immediate =
+            |register_opcode, immediate_opcode, signed| Kind::RegisterOrImmediate {
+                register_opcode,
+                immediate_opcode,
+                signed,
+            };
+
+        let kind = match mnemonic {
+            "mov" => register_or_immediate(OPCODE_BITS_MOV_REG, OPCODE_BITS_MOV_IMM, true),
+            "add" => register_or_immediate(OPCODE_BITS_ADD_REG, OPCODE_BITS_ADD_IMM_5, true),
+            "cmp" => register_or_immediate(OPCODE_BITS_CMP_REG, OPCODE_BITS_CMP_IMM, true),
+            "shl" => register_or_immediate(OPCODE_BITS_SHL_REG, OPCODE_BITS_SHL_IMM, false),
+            "shr" => register_or_immediate(OPCODE_BITS_SHR_REG, OPCODE_BITS_SHR_IMM, false),
+            "sar" => register_or_immediate(OPCODE_BITS_SAR_REG, OPCODE_BITS_SAR_IMM, false),
+            "sub" => Kind::Registers(OPCODE_BITS_SUB),
+            "mul" => Kind::Registers(OPCODE_BITS_MUL),
+            "div" => Kind::Registers(OPCODE_BITS_DIV),
+            "mulu" => Kind::Registers(OPCODE_BITS_MUL_U),
+            "divu" => Kind::Registers(OPCODE_BITS_DIV_U),
+            "or" => Kind::Registers(OPCODE_BITS_OR),
+            "and" => Kind::Registers(OPCODE_BITS_AND),
+            "xor" => Kind::Registers(OPCODE_BITS_XOR),
+            "not" => Kind::Registers(OPCODE_BITS_NOT),
+            "jmp" => Kind::Jump,
+            "cli" => Kind::NoOperands(OPCODE_BITS_CLI),
+            "reti" => Kind::NoOperands(OPCODE_BITS_RETI),
+            "halt" => Kind::NoOperands(OPCODE_BITS_HALT),
+            "sei" => Kind::NoOperands(OPCODE_BITS_SEI),
+            "setf" => Kind::Setf,
+            "ldsr" => Kind::Ldsr,
+            "stsr" => Kind::Stsr,
+            "trap" => Kind::Trap,
+            "sch0bsu" => Kind::BitString(OPCODE_BITS_BIT_STRING_OP_SCH0BSU),
+            "sch0bsd" => Kind::BitString(OPCODE_BITS_BIT_STRING_OP_SCH0BSD),
+            "sch1bsu" => Kind::BitString(OPCODE_BITS_BIT_STRING_OP_SCH1BSU),
+            "sch1bsd" => Kind::BitString(OPCODE_BITS_BIT_STRING_OP_SCH1BSD),
+            "orbsu" => Kind::BitString(OPCODE_BITS_BIT_STRING_OP_ORBSU),
+            "andbsu" => Kind::BitString(OPCODE_BITS_BIT_STRING_OP_ANDBSU),
+            "xorbsu" => Kind::BitString(OPCODE_BITS_BIT_STRING_OP_XORBSU),
+            "movbsu" => Kind::BitString(OPCODE_BITS_BIT_STRING_OP_MOVBSU),
+            "ornbsu" => Kind::BitString(OPCODE_BITS_BIT_STRING_OP_ORNBSU),
+            "andnbsu" => Kind::BitString(OPCODE_BITS_BIT_STRING_OP_ANDNBSU),
+            "xornbsu" => Kind::BitString(OPCODE_BITS_BIT_STRING_OP_XORNBSU),
+            "notbsu" => Kind::BitString(OPCODE_BITS_BIT_STRING_OP_NOTBSU),
+            "bv" => Kind::Branch(OPCODE_BITS_BCOND_BV),
+            "bc" => Kind::Branch(OPCODE_BITS_BCOND_BC),
+            "bz" => Kind::Branch(OPCODE_BITS_BCOND_BZ),
+            "bnh" => Kind::Branch(OPCODE_BITS_BCOND_BNH),
+            "bn" => Kind::Branch(OPCODE_BITS_BCOND_BN),
+            "br" => Kind::Branch(OPCODE_BITS_BCOND_BR),
+            "blt" => Kind::Branch(OPCODE_BITS_BCOND_BLT),
+            "ble" => Kind::Branch(OPCODE_BITS_BCOND_BLE),
+            "bnv" => Kind::Branch(OPCODE_BITS_BCOND_BNV),
+            "bnc" => Kind::Branch(OPCODE_BITS_BCOND_BNC),
+            "bnz" => Kind::Branch(OPCODE_BITS_BCOND_BNZ),
+            "bh" => Kind::Branch(OPCODE_BITS_BCOND_BH),
+            "bp" => Kind::Branch(OPCODE_BITS_BCOND_BP),
+            "bge" => Kind::Branch(OPCODE_BITS_BCOND_BGE),
+            "bgt" => Kind::Branch(OPCODE_BITS_BCOND_BGT),
+            "nop" => Kind::Nop,
+            "jr" => Kind::Displacement(OPCODE_BITS_JR),
+            "jal" => Kind::Displacement(OPCODE_BITS_JAL),
+            "movea" => Kind::Immediate16(OPCODE_BITS_MOVEA),
+            "addi" => Kind::Immediate16(OPCODE_BITS_ADD_IMM_16),
+            "ori" => Kind::Immediate16(OPCODE_BITS_OR_I),
+            "andi" => Kind::Immediate16(OPCODE_BITS_AND_I),
+            "xori" => Kind::Immediate16(OPCODE_BITS_XOR_I),
+            "movhi" => Kind::Immediate16(OPCODE_BITS_MOVHI),
+            "ld.b" => Kind::Load(OPCODE_BITS_LDB),
+            "ld.h" => Kind::Load(OPCODE_BITS_LDH),
+            "ld.w" => Kind::Load(OPCODE_BITS_LDW),
+            "in.b" => Kind::Load(OPCODE_BITS_INB),
+            "in.h" => Kind::Load(OPCODE_BITS_INH),
+            "in.w" => Kind::Load(OPCODE_BITS_INW),
+            "caxi" => Kind::Load(OPCODE_BITS_CAXI),
+            "st.b" => Kind::Store(OPCODE_BITS_STB),
+            "st.h" => Kind::Store(OPCODE_BITS_STH),
+            "st.w" => Kind::Store(OPCODE_BITS_STW),
+            "out.b" => Kind::Store(OPCODE_BITS_OUTB),
+            "out.h" => Kind::Store(OPCODE_BITS_OUTH),
+            "out.w" => Kind::Store(OPCODE_BITS_OUTW),
+            "cmpf.s" => Kind::Extended(OPCODE_BITS_SUB_OP_CMPF_S),
+            "cvt.ws" => Kind::Extended(OPCODE_BITS_SUB_OP_CVT_WS),
+            "cvt.sw" => Kind::Extended(OPCODE_BITS_SUB_OP_CVT_SW),
+            "addf.s" => Kind::Extended(OPCODE_BITS_SUB_OP_ADDF_S),
+            "subf.s" => Kind::Extended(OPCODE_BITS_SUB_OP_SUBF_S),
+            "mulf.s" => Kind::Extended(OPCODE_BITS_SUB_OP_MULF_S),
+            "divf.s" => Kind::Extended(OPCODE_BITS_SUB_OP_DIVF_S),
+            "rev" => Kind::Extended(OPCODE_BITS_SUB_OP_REV),
+            "trnc.sw" => Kind::Extended(OPCODE_BITS_SUB_OP_TRNC_SW),
+            "mpyhw" => Kind::Extended(OPCODE_BITS_SUB_OP_MPYHW),
+            "xb" => Kind::ExtendedRegister(OPCODE_BITS_SUB_OP_XB),
+            "xh" => Kind::ExtendedRegister(OPCODE_BITS_SUB_OP_XH),
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+
+    fn has_second_halfword(&self) -> bool {
+        matches!(
+            self,
+            Kind::Displacement(_)
+                | Kind::Immediate16(_)
+                | Kind::Load(_)
+                | Kind::Store(_)
+                | Kind::Extended(_)
+                | Kind::ExtendedRegister(_)
+        )
+    }
+}
+
+struct Assembler {
+    labels: HashMap<String, u32>,
+}
+
+impl Assembler {
+    fn encode(
+        &self,
+        statement: &Statement,
+        address: u32,
+        bytes: &mut Vec<u8>,
+    ) -> Result<(), String> {
+        match statement {
+            Statement::Instruction { mnemonic, operands } => {
+                let kind = Kind::from_mnemonic(mnemonic).unwrap();
+
+                for halfword in self.encode_instruction(kind, operands, address)? {
+                    bytes.extend_from_slice(&halfword.to_le_bytes());
+                }
+            }
+            Statement::Word(values) => {
+                for value in values {
+                    let value = self.value(value)?;
+
+                    bytes.extend_from_slice(&(value as u32).to_le_bytes());
+                }
+            }
+            Statement::Halfword(values) => {
+                for value in values {
+                    let value = self.value(value)?;
+
+                    bytes.extend_from_slice(&(value as u16).to_le_bytes());
+                }
+            }
+            Statement::Align(alignment) => {
+                let padding = address.next_multiple_of(*alignment) - address;
+
+                bytes.resize(bytes.len() + padding as usize, 0);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn encode_instruction(
+        &self,
+        kind: Kind,
+        operands: &[String],
+        address: u32,
+    ) -> Result<Vec<u16>, String> {
+        let halfwords = match kind {
+            Kind::RegisterOrImmediate {
+                register_opcode,
+                immediate_opcode,
+                signed,
+            } => {
+                let [source, reg2] = expect_operands(operands)?;
+                let reg2 = parse_register(reg2)?;
+
+                if let Ok(reg1) = parse_register(source) {
+                    vec![format_i(register_opcode, reg1, reg2)]
+                } else {
+                    let range = if signed { -16..=15 } else { 0..=31 };
+
+                    let imm5 = self.value_in_range(source, range)?;
+
+                    vec![format_ii(immediate_opcode, imm5 as u16, reg2)]
+                }
+            }
+            Kind::Registers(opcode) => {
+                let [reg1, reg2] = expect_operands(operands)?;
+
+                vec![format_i(
+                    opcode,
+                    parse_register(reg1)?,
+                    parse_register(reg2)?,
+                )]
+            }
+            Kind::Jump => {
+                let [target] = expect_operands(operands)?;
+
+                let reg1 = target
+                    .strip_prefix('[')
+                    .and_then(|target| target.strip_suffix(']'))
+                    .ok_or_else(|| format!("Expected [reg1], found {target}"))?;
+
+                vec![format_i(OPCODE_BITS_JMP, parse_register(reg1)?, 0)]
+            }
+            Kind::NoOperands(opcode) => {
+                let [] = expect_operands(operands)?;
+
+                vec![format_ii(opcode, 0, 0)]
+            }
+            Kind::Setf => {
+                let [condition, reg2] = expect_operands(operands)?;
+
+                vec![format_ii(
+                    OPCODE_BITS_SETF,
+                    parse_condition(condition)? as u16,
+                    parse_register(reg2)?,
+                )]
+            }
+            Kind::Ldsr => {
+                let [reg2, system_register] = expect_operands(operands)?;
+
+                vec![format_ii(
+                    OPCODE_BITS_LDSR,
+                    parse_system_register(system_register)?,
+                    parse_register(reg2)?,
+                )]
+            }
+            Kind::Stsr => {
+                let [system_register, reg2] = expect_operands(operands)?;
+
+                vec![format_ii(
+                    OPCODE_BITS_STSR,
+                    parse_system_register(system_register)?,
+                    parse_register(reg2)?,
+                )]
+            }
+            Kind::Trap => {
+                let [vector] = expect_operands(operands)?;
+
+                let vector = self.value_in_range(vector, 0..=31)?;
+
+                vec![format_ii(OPCODE_BITS_TRAP, vector as u16, 0)]
+            }
+            Kind::BitString(op) => {
+                let [] = expect_operands(operands)?;
+
+                vec![format_ii(OPCODE_BITS_BIT_STRING, op as u16, 0)]
+            }
+            Kind::Branch(condition) => {
+                let [target] = expect_operands(operands)?;
+
+                let disp = self.displacement(target, address, 9)?;
+
+                vec![format_iii(condition, disp)]
+            }
+            Kind::Nop => {
+                let [] = expect_operands(operands)?;
+
+                vec![format_iii(OPCODE_BITS_BCOND_NOP, 0)]
+            }
+            Kind::Displacement(opcode) => {
+                let [target] = expect_operands(operands)?;
+
+                let disp = self.displacement(target, address, 26)?;
+
+                format_iv(opcode, disp).to_vec()
+            }
+            Kind::Immediate16(opcode) => {
+                let [imm, reg1, reg2] = expect_operands(operands)?;
+
+                let imm = self.value_in_range(imm, -0x8000..=0xFFFF)?;
+
+                let mut halfwords = format_v(opcode, parse_register(reg1)?, parse_register(reg2)?);
+                halfwords[1] = imm as u16;
+
+                halfwords.to_vec()
+            }
+            Kind::Load(opcode) => {
+                let [memory, reg2] = expect_operands(operands)?;
+
+                let (disp, reg1) = self.memory_operand(memory)?;
+
+                format_vi(opcode, disp, reg1, parse_register(reg2)?).to_vec()
+            }
+            Kind::Store(opcode) => {
+                let [reg2, memory] = expect_operands(operands)?;
+
+                let (disp, reg1) = self.memory_operand(memory)?;
+
+                format_vi(opcode, disp, reg1, parse_register(reg2)?).to_vec()
+            }
+            Kind::Extended(subop) => {
+                let [reg1, reg2] = expect_operands(operands)?;
+
+                format_vii(subop, parse_register(reg1)?, parse_register(reg2)?).to_vec()
+            }
+            Kind::ExtendedRegister(subop) => {
+                let [reg2] = expect_operands(operands)?;
+
+                format_vii(subop, 0, parse_register(reg2)?).to_vec()
+            }
+        };
+
+        Ok(halfwords)
+    }
+
+    /// Parses `disp16[reg1]`
+    fn memory_operand(&self, operand: &str) -> Result<(u16, u16), String> {
+        let (disp, reg1) = operand
+            .strip_suffix(']')
+            .and_then(|operand| operand.split_once('['))
+            .ok_or_else(|| format!("Expected disp16[reg1], found {operand}"))?;
+
+        let disp = if disp.trim().is_empty() {
+            0
+        } else {
+            self.value_in_range(disp, -0x8000..=0x7FFF)?
+        };
+
+        Ok((disp as u16, parse_register(reg1)?))
+    }
+
+    /// Computes the displacement from `address` to the branch target, checking it fits in `bits`
+    fn displacement(&self, target: &str, address: u32, bits: u32) -> Result<u32, String> {
+        let target = self.value(target)? as u32;
+        let disp = target.wrapping_sub(address) as i32 as i64;
+
+        let limit = 1_i64 << (bits - 1);
+
+        if disp & 1 != 0 || !(-limit..limit).contains(&disp) {
+            return Err(format!("Branch target 0x{target:08X} is out of range"));
+        }
+
+        Ok(disp as u32)
+    }
+
+    fn value_in_range(
+        &self,
+        operand: &str,
+        range: std::ops::RangeInclusive<i64>,
+    ) -> Result<i64, String> {
+        let value = self.value(operand)?;
+
+        if !range.contains(&value) {
+            return Err(format!("{operand} does not fit in {range:?}"));
+        }
+
+        Ok(value)
+    }
+
+    /// Evaluates a sum of numbers and labels, optionally wrapped in `hi()` or `lo()`
+    fn value(&self, operand: &str) -> Result<i64, String> {
+        let operand = operand.trim();
+
+        if let Some(inner) = strip_function(operand, "hi") {
+            return Ok(high_half(self.value(inner)?) as i64);
+        }
+
+        if let Some(inner) = strip_function(operand, "lo") {
+            return Ok(self.value(inner)? & 0xFFFF);
+        }
+
+        let mut total = 0_i64;
+        let mut sign = 1;
+        let mut term_start = 0;
+
+        // Split on + and -, skipping a leading sign
+        for (i, c) in operand.char_indices().chain([(operand.len(), '+')]) {
+            if (c == '+' || c == '-') && i > term_start {
+                let term = operand[term_start..i].trim();
+
+                total += sign * self.term(term)?;
+
+                sign = if c == '-' { -1 } else { 1 };
+                term_start = i + 1;
+            } else if c == '-' && operand[term_start..i].trim().is_empty() {
+                sign = -sign;
+                term_start = i + 1;
+            }
+        }
+
+        Ok(total)
+    }
+
+    fn term(&self, term: &str) -> Result<i64, String> {
+        if let Some(value) = parse_number(term) {
+            return Ok(value);
+        }
+
+        self.labels
+            .get(term)
+            .map(|address| *address as i64)
+            .ok_or_else(|| format!("Unknown label or invalid number {term}"))
+    }
+}
+
+fn expect_operands<const N: usize>(operands: &[String]) -> Result<[&str; N], String> {
+    if operands.len() != N {
+        return Err(format!("Expected {N} operands, found {}", operands.len()));
+    }
+
+    Ok(std::array::from_fn(|i| operands[i].as_str()))
+}
+
+fn parse_register(operand: &str) -> Result<u16, String> {
+    let operand = operand.trim().to_lowercase();
+
+    let register = match operand.as_str() {
+        "sp" => Some(3),
+        "gp" => Some(4),
+        "tp" => Some(5),
+        "lp" => Some(31),
+        _ => operand
+            .strip_prefix('r')
+            .and_then(|index| index.parse::<u16>().ok())
+            .filter(|index| *index < 32),
+    };
+
+    register.ok_or_else(|| format!("Invalid register {operand}"))
+}
+
+fn parse_system_register(operand: &str) -> Result<u16, String> {
+    let operand = operand.trim().to_lowercase();
+
+    let id = match operand.as_str() {
+        "eipc" => OPCODE_SYSTEM_REGISTER_ID_EIPC,
+        "eipsw" => OPCODE_SYSTEM_REGISTER_ID_EIPSW,
+        "fepc" => OPCODE_SYSTEM_REGISTER_ID_FEPC,
+        "fepsw" => OPCODE_SYSTEM_REGISTER_ID_FEPSW,
+        "ecr" => OPCODE_SYSTEM_REGISTER_ID_ECR,
+        "psw" => OPCODE_SYSTEM_REGISTER_ID_PSW,
+        "pir" => OPCODE_SYSTEM_REGISTER_ID_PIR,
+        "tkcw" => OPCODE_SYSTEM_REGISTER_ID_TKCW,
+        "chcw" => OPCODE_SYSTEM_REGISTER_ID_CHCW,
+        "adtre" => OPCODE_SYSTEM_REGISTER_ID_ADTRE,
+        _ => parse_number(&operand)
+            .filter(|id| (0..32).contains(id))
+            .ok_or_else(|| format!("Invalid system register {operand}"))? as u32,
+    };
+
+    Ok(id as u16)
+}
+
+fn parse_condition(operand: &str) -> Result<u32, String> {
+    let operand = operand.trim().to_lowercase();
+
+    let condition = match operand.as_str() {
+        "v" => OPCODE_CONDITION_BITS_V,
+        "c" | "l" => OPCODE_CONDITION_BITS_C,
+        "z" | "e" => OPCODE_CONDITION_BITS_Z,
+        "nh" => OPCODE_CONDITION_BITS_NH,
+        "n" => OPCODE_CONDITION_BITS_N,
+        "t" => OPCODE_CONDITION_BITS_T,
+        "lt" => OPCODE_CONDITION_BITS_LT,
+        "le" => OPCODE_CONDITION_BITS_LE,
+        "nv" => OPCODE_CONDITION_BITS_NV,
+        "nc" | "nl" => OPCODE_CONDITION_BITS_NC,
+        "nz" | "ne" => OPCODE_CONDITION_BITS_NZ,
+        "h" => OPCODE_CONDITION_BITS_H,
+        "p" => OPCODE_CONDITION_BITS_P,
+        "f" => OPCODE_CONDITION_BITS_F,
+        "ge" => OPCODE_CONDITION_BITS_GE,
+        "gt" => OPCODE_CONDITION_BITS_GT,
+        _ => return Err(format!("Invalid condition {operand}")),
+    };
+
+    Ok(condition)
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let text = text.trim();
+
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+
+    let value = if let Some(hex) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = text.strip_prefix("0b").or(text.strip_prefix("0B")) {
+        i64::from_str_radix(binary, 2).ok()?
+    } else {
+        text.parse::<i64>().ok()?
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+fn strip_function<'a>(operand: &'a str, name: &str) -> Option<&'a str> {
+    operand
+        .strip_prefix(name)?
+        .trim_start()
+        .strip_prefix('(')?
+        .strip_suffix(')')
+}
+
+fn strip_comment(line: &str) -> &str {
+    let end = [line.find(';'), line.find("//")]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(line.len());
+
+    &line[..end]
+}
+
+/// Splits a leading `label:` from the line
+fn split_label(line: &str) -> Option<(&str, &str)> {
+    let (label, rest) = line.split_once(':')?;
+
+    let is_identifier = label
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+
+    if is_identifier {
+        Some((label, rest))
+    } else {
+        None
+    }
+}
+
+/// The upper half of a value loaded with MOVHI followed by MOVEA, accounting for MOVEA sign extending the lower half
+fn high_half(value: i64) -> u16 {
+    ((value + 0x8000) >> 16) as u16
+}
+
+fn format_i(opcode: u16, reg1: u16, reg2: u16) -> u16 {
+    (opcode << 10) | (reg2 << 5) | reg1
+}
+
+fn format_ii(opcode: u16, imm5: u16, reg2: u16) -> u16 {
+    (opcode << 10) | (reg2 << 5) | (imm5 & 0x1F)
+}
+
+fn format_iii(condition: u16, disp: u32) -> u16 {
+    (OPCODE_BITS_BCOND_PREFIX << 13) | (condition << 9) | (disp as u16 & 0x1FF)
+}
+
+fn format_iv(opcode: u16, disp: u32) -> [u16; 2] {
+    [(opcode << 10) | ((disp >> 16) as u16 & 0x3FF), disp as u16]
+}
+
+/// The immediate is left as 0
+fn format_v(opcode: u16, reg1: u16, reg2: u16) -> [u16; 2] {
+    [format_i(opcode, reg1, reg2), 0]
+}
+
+fn format_vi(opcode: u16, disp: u16, reg1: u16, reg2: u16) -> [u16; 2] {
+    [format_i(opcode, reg1, reg2), disp]
+}
+
+fn format_vii(subop: u16, reg1: u16, reg2: u16) -> [u16; 2] {
+    [format_i(OPCODE_BITS_EXTENDED, reg1, reg2), subop << 10]
+}
@@ -1,75 +1,143 @@
-use rand::{thread_rng, Rng};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
 use crate::{
     cartridge::Cartridge,
+    debugger::{WatchAccess, Watchpoints},
     gamepad::GamepadInputs,
     hardware::Hardware,
-    interrupt::InterruptRequest,
+    interrupt::{InterruptController, InterruptRequest},
     vip::VIP,
     vsu::{
         traits::{AudioFrame, Sink},
         VSU,
     },
+    wait_control::{AccessWidth, BusRegion},
 };
 
 #[derive(Savefile)]
 pub struct Bus {
     wram: [u16; 0x1_0000 / 2],
+    #[savefile_introspect_ignore]
     pub cart: Cartridge,
+
+    /// Set when the mapper reports that the ROM region changed, such as by a write to flash or a bank switch
+    #[savefile_ignore]
+    #[savefile_introspect_ignore]
+    rom_changed: bool,
     pub vip: VIP,
     vsu: VSU,
     hardware: Hardware,
+    #[savefile_versions = "3.."]
+    #[savefile_default_fn = "new_interrupt_controller"]
+    interrupts: InterruptController,
+
+    #[savefile_ignore]
+    #[savefile_introspect_ignore]
+    #[savefile_default_fn = "new_watchpoints"]
+    pub(crate) watchpoints: Watchpoints,
 }
 
 impl Bus {
+    /// Creates the bus with cleared WRAM
     pub fn new(cart: Cartridge, vip: VIP, vsu: VSU, hardware: Hardware) -> Self {
-        let mut wram = [0; 0x1_0000 / 2];
-
-        // Randomize starting data
-        thread_rng().fill(&mut wram[..]);
-
         Bus {
-            wram,
+            wram: [0; 0x1_0000 / 2],
             cart,
+            rom_changed: false,
             vip,
             vsu,
             hardware,
+            interrupts: InterruptController::new(),
+            watchpoints: Watchpoints::new(),
         }
     }
 
-    /// TODO: This is debug init to match with Mednafen
-    // pub fn debug_init(&mut self) {
-    //     self.cart.debug_init();
-
-    //     // Don't randomize WRAM
-    //     self.wram = [0; 0x1_0000 / 2];
-    // }
+    /// Fills WRAM with random data generated from `seed`. The same seed always produces the same contents
+    pub fn randomize_wram(&mut self, seed: u64) {
+        ChaCha8Rng::seed_from_u64(seed).fill(&mut self.wram[..]);
+    }
 
     pub fn step(
         &mut self,
         cycles_to_run: usize,
         audio_sink: &mut dyn Sink<AudioFrame>,
         inputs: &GamepadInputs,
-    ) -> Option<InterruptRequest> {
-        let mut request = None;
-
+    ) {
         self.hardware.gamepad.step(cycles_to_run, inputs);
         self.vsu.step(cycles_to_run, audio_sink);
+        self.hardware.timer.step(cycles_to_run);
+        self.vip.step(cycles_to_run);
 
-        // Priority 1
-        if self.hardware.timer.step(cycles_to_run) {
-            request = Some(InterruptRequest::TimerZero);
-        }
+        self.interrupts.set_line(
+            InterruptRequest::GamePad,
+            self.hardware.gamepad.interrupt_line(),
+        );
+        self.interrupts.set_line(
+            InterruptRequest::TimerZero,
+            self.hardware.timer.interrupt_line(),
+        );
+        self.interrupts
+            .set_line(InterruptRequest::GamePak, self.cart.interrupt_line());
+        self.interrupts
+            .set_line(InterruptRequest::VIP, self.vip.interrupt_line());
+        // The link port is not emulated, so the Communication line is never asserted
+    }
 
-        // 4: Highest priority
-        if self.vip.step(cycles_to_run) {
-            request = Some(InterruptRequest::VIP);
+    /// The highest priority interrupt currently asserted, as of the last `step`
+    pub fn pending_interrupt(&self) -> Option<InterruptRequest> {
+        self.interrupts.highest_pending()
+    }
+
+    /// Number of cycles until the next change in hardware state that the CPU could observe, such as an interrupt, a
+    /// register read changing value, VRAM wait states starting or ending with drawing, or the end of a video frame.
+    /// The change happens while running the final cycle, so stepping any fewer cycles can be done in a single `step`
+    /// without changing the result
+    pub fn cycles_until_event(&self) -> usize {
+        self.vip
+            .cycles_until_event()
+            .min(self.hardware.timer.cycles_until_event())
+            .min(self.hardware.gamepad.cycles_until_event())
+    }
+
+    /// Cycles added to an access of `width` at `address` by wait states, and by contention with the VIP
+    pub fn wait_cycles(&self, address: u32, width: AccessWidth) -> u32 {
+        let region = BusRegion::from_address(address);
+
+        let mut cycles = self.hardware.wait_control.wait_cycles(region, width);
+
+        if region == BusRegion::Vram {
+            cycles += self.vip.vram_wait_cycles() * width.transfer_count();
         }
 
-        request
+        cycles
+    }
+
+    /// Cycles added to an instruction fetch of `width` at `address`, relative to the power on wait states that
+    /// instruction timings already include
+    pub fn fetch_wait_delta(&self, address: u32, width: AccessWidth) -> i32 {
+        self.hardware
+            .wait_control
+            .fetch_wait_delta(BusRegion::from_address(address), width)
+    }
+
+    /// Number of cycles until `count` more audio samples have been output
+    pub fn cycles_until_audio_samples(&self, count: usize) -> usize {
+        self.vsu.cycles_until_samples(count)
     }
 
     pub fn get_u16(&mut self, address: u32) -> u16 {
+        let value = self.read_u16(address);
+
+        if self.watchpoints.is_active() {
+            self.watchpoints
+                .check(address & 0x07FF_FFFF, value, WatchAccess::Read);
+        }
+
+        value
+    }
+
+    fn read_u16(&mut self, address: u32) -> u16 {
         // Mask top 5 bits to mirror bus
         let address = address as usize & 0x07FF_FFFF;
 
@@ -81,9 +149,7 @@ impl Bus {
             //     0
             // }
             0x0200_0000..=0x02FF_FFFF => self.hardware.get(address as u8),
-            // 0x0400_0000..=0x04FF_FFFF => {
-            //     todo!("Game Pak Expansion")
-            // }
+            0x0400_0000..=0x04FF_FFFF => self.cart.get_expansion((address >> 1) & 0x7F_FFFF),
             0x0500_0000..=0x05FF_FFFF => self.wram[(address >> 1) & 0x7FFF],
             0x0600_0000..=0x06FF_FFFF => self.cart.get_ram((address >> 1) & 0x7F_FFFF),
             0x0700_0000..=0x07FF_FFFF => self.cart.get_rom((address >> 1) & 0x7F_FFFF),
@@ -91,6 +157,31 @@ impl Bus {
         }
     }
 
+    /// Reads an instruction halfword. Instruction fetches do not trigger watchpoints
+    pub fn fetch_u16(&mut self, address: u32) -> u16 {
+        self.read_u16(address)
+    }
+
+    /// Reads a halfword without any side effects on the bus. Used for debugging and disassembly
+    pub fn peek_u16(&self, address: u32) -> u16 {
+        let address = address as usize & 0x07FF_FFFF;
+
+        match address {
+            0x0000_0000..=0x00FF_FFFF => self.vip.get_bus(address),
+            0x0200_0000..=0x02FF_FFFF => self.hardware.get(address as u8),
+            0x0400_0000..=0x04FF_FFFF => self.cart.peek_expansion((address >> 1) & 0x7F_FFFF),
+            0x0500_0000..=0x05FF_FFFF => self.wram[(address >> 1) & 0x7FFF],
+            0x0600_0000..=0x06FF_FFFF => self.cart.peek_ram((address >> 1) & 0x7F_FFFF),
+            0x0700_0000..=0x07FF_FFFF => self.cart.get_rom((address >> 1) & 0x7F_FFFF),
+            _ => 0,
+        }
+    }
+
+    /// Returns true if the ROM region changed since the last call, invalidating any decoded instructions
+    pub fn take_rom_changed(&mut self) -> bool {
+        std::mem::take(&mut self.rom_changed)
+    }
+
     /// Hack to optimize PC fetch
     pub fn get_rom(&self, address: u32) -> u16 {
         self.cart.get_rom(address as usize)
@@ -119,6 +210,10 @@ impl Bus {
         // Mask top 5 bits to mirror bus
         let address = address & 0x07FF_FFFF;
 
+        if self.watchpoints.is_active() {
+            self.watchpoints.check(address, value, WatchAccess::Write);
+        }
+
         // Address for bus block
         let local_address = (address as usize) & 0xFF_FFFF;
         // Remove bottom 1 (shifted out) to make halfword addresses
@@ -128,14 +223,15 @@ impl Bus {
             0x0000_0000..=0x00FF_FFFF => self.vip.set_bus(address, value),
             0x0100_0000..=0x01FF_FFFF => self.vsu.set_u8(local_address, value as u8),
             0x0200_0000..=0x02FF_FFFF => self.hardware.set(address as u8, value),
-            // 0x0400_0000..=0x04FF_FFFF => {
-            //     todo!("Game Pak Expansion")
-            // }
+            0x0400_0000..=0x04FF_FFFF => {
+                // Mapper registers may switch banks
+                self.rom_changed |= self.cart.set_expansion(local_address_u16, value);
+            }
             0x0500_0000..=0x05FF_FFFF => self.wram[local_address_u16 & 0x7FFF] = value,
             0x0600_0000..=0x06FF_FFFF => self.cart.set_ram(local_address_u16, value),
             0x0700_0000..=0x07FF_FFFF => {
-                // Game Pak ROM
-                // Do nothing
+                // Game Pak ROM. Ignored unless the mapper has writable flash
+                self.rom_changed |= self.cart.set_rom(local_address_u16, value);
             }
             _ => {}
         }
@@ -150,7 +246,8 @@ impl Bus {
     }
 
     pub fn set_u8(&mut self, address: u32, value: u8) {
-        let existing_word = self.get_u16(address);
+        // Read without triggering watchpoints, as this is only a byte write
+        let existing_word = self.read_u16(address);
 
         let output_word = match address & 0x1 {
             0 => (existing_word & 0xFF00) | (value as u16),
@@ -161,3 +258,11 @@ impl Bus {
         self.set_u16(address, output_word);
     }
 }
+
+fn new_interrupt_controller() -> InterruptController {
+    InterruptController::new()
+}
+
+fn new_watchpoints() -> Watchpoints {
+    Watchpoints::new()
+}
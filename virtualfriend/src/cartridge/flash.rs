@@ -0,0 +1,249 @@
+use std::borrow::Cow;
+
+use super::{save_ram::SaveRam, CartridgeMapper, RomError};
+
+/// Size of the ROM region, in halfwords. Flash is mapped into it one bank at a time
+const BANK_WORD_COUNT: usize = 0x80_0000;
+
+/// Size of an erasable sector, in halfwords
+const SECTOR_WORD_COUNT: usize = 0x8000;
+
+/// CONTROL bit enabling programming and erasing of flash
+const CONTROL_WRITE_ENABLE: u16 = 0x1;
+/// CONTROL bit raising the Game Pak interrupt when an operation completes
+const CONTROL_INTERRUPT_ENABLE: u16 = 0x2;
+
+/// STATUS bit set when a program or erase operation completes
+const STATUS_DONE: u16 = 0x1;
+
+/// A homebrew flash cartridge, with flash memory in place of the mask ROM, and battery backed RAM.
+///
+/// Flash is mapped into the ROM region one 16MB bank at a time, so images larger than the region can be banked, or a
+/// menu can select between several complete ROM images. Flash can only have bits cleared by programming, and is set
+/// back to `0xFFFF` a 64KB sector at a time by erasing. Operations complete immediately.
+///
+/// Registers are in the expansion area:
+///
+/// * `0x0400_0000` BANK: The bank of flash mapped into the ROM region
+/// * `0x0400_0002` CONTROL: Bit 0 enables programming by writes to the ROM region, and erasing. Bit 1 raises the
+///   Game Pak interrupt when an operation completes
+/// * `0x0400_0004` STATUS: Bit 0 is set when an operation completes. Writing 1 clears it
+/// * `0x0400_0006` ERASE: Writing erases the sector with that index in the current bank
+pub struct FlashMapper {
+    flash: Vec<u16>,
+    /// The flash image as loaded, restored by savestates that predate any modification
+    original_flash: Box<[u16]>,
+    /// Flash has been programmed or erased since it was loaded
+    flash_modified: bool,
+
+    /// Flash address mask for word addresses
+    flash_address_mask: usize,
+
+    ram: SaveRam,
+
+    bank: u16,
+    control: u16,
+    status: u16,
+}
+
+impl FlashMapper {
+    /// Loads `flash`, which must be a power of two in size. Images smaller than the ROM region are mirrored
+    pub fn new(flash: Vec<u8>) -> Result<Self, RomError> {
+        if flash.is_empty() {
+            return Err(RomError::Empty);
+        }
+
+        if !flash.len().is_power_of_two() || flash.len() < 2 {
+            // Writable flash cannot be padded with mirrors
+            return Err(RomError::FlashSizeNotPowerOfTwo { size: flash.len() });
+        }
+
+        let flash: Vec<u16> = flash
+            .chunks_exact(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+
+        Ok(FlashMapper {
+            flash_address_mask: flash.len() - 1,
+            original_flash: flash.clone().into(),
+            flash,
+            flash_modified: false,
+            ram: SaveRam::new(),
+            bank: 0,
+            control: 0,
+            status: 0,
+        })
+    }
+
+    /// The current contents of flash, to be persisted by the frontend
+    pub fn dump_flash(&self) -> Vec<u8> {
+        self.flash
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    fn flash_address(&self, address: usize) -> usize {
+        // Mask the bank before offsetting by it, as banks beyond the flash size would overflow on 32-bit hosts
+        let bank = self.bank as usize & (self.flash_address_mask / BANK_WORD_COUNT);
+
+        (bank * BANK_WORD_COUNT + address) & self.flash_address_mask
+    }
+
+    fn can_write(&self) -> bool {
+        self.control & CONTROL_WRITE_ENABLE != 0
+    }
+
+    fn complete_operation(&mut self) {
+        self.flash_modified = true;
+        self.status |= STATUS_DONE;
+    }
+}
+
+impl CartridgeMapper for FlashMapper {
+    fn read_rom(&self, address: usize) -> u16 {
+        self.flash[self.flash_address(address)]
+    }
+
+    fn rom_image(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(
+            self.original_flash
+                .iter()
+                .flat_map(|word| word.to_le_bytes())
+                .collect(),
+        )
+    }
+
+    fn write_rom(&mut self, address: usize, value: u16) -> bool {
+        if !self.can_write() {
+            return false;
+        }
+
+        // Programming can only clear bits
+        let address = self.flash_address(address);
+        let previous = self.flash[address];
+        self.flash[address] &= value;
+
+        self.complete_operation();
+
+        self.flash[address] != previous
+    }
+
+    fn read_ram(&mut self, address: usize) -> u16 {
+        self.ram.get(address)
+    }
+
+    fn peek_ram(&self, address: usize) -> u16 {
+        self.ram.peek(address)
+    }
+
+    fn write_ram(&mut self, address: usize, value: u16) {
+        self.ram.set(address, value);
+    }
+
+    fn peek_expansion(&self, address: usize) -> u16 {
+        match address {
+            0x0 => self.bank,
+            0x1 => self.control,
+            0x2 => self.status,
+            _ => 0,
+        }
+    }
+
+    fn write_expansion(&mut self, address: usize, value: u16) -> bool {
+        match address {
+            0x0 => {
+                let previous = self.bank;
+                self.bank = value;
+
+                self.bank != previous
+            }
+            0x1 => {
+                self.control = value & (CONTROL_WRITE_ENABLE | CONTROL_INTERRUPT_ENABLE);
+
+                false
+            }
+            0x2 => {
+                self.status &= !value;
+
+                false
+            }
+            0x3 => {
+                if !self.can_write() {
+                    return false;
+                }
+
+                let start = self.flash_address(value as usize * SECTOR_WORD_COUNT);
+                let end = (start + SECTOR_WORD_COUNT).min(self.flash.len());
+
+                self.flash[start..end].fill(0xFFFF);
+
+                self.complete_operation();
+
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn interrupt_line(&self) -> bool {
+        self.control & CONTROL_INTERRUPT_ENABLE != 0 && self.status & STATUS_DONE != 0
+    }
+
+    fn dump_ram(&self) -> Vec<u8> {
+        self.ram.dump()
+    }
+
+    fn load_ram(&mut self, ram: Vec<u8>) {
+        self.ram.load(ram);
+    }
+
+    /// Registers, followed by the length and contents of RAM, followed by flash if it has been modified
+    fn savestate(&self) -> Vec<u16> {
+        let ram = self.ram.used_words();
+
+        let mut state = vec![
+            self.bank,
+            self.control,
+            self.status,
+            (ram.len() >> 16) as u16,
+            ram.len() as u16,
+        ];
+
+        state.extend_from_slice(ram);
+
+        if self.flash_modified {
+            state.extend_from_slice(&self.flash);
+        }
+
+        state
+    }
+
+    fn load_savestate(&mut self, state: &[u16]) {
+        let [bank, control, status, ram_length_high, ram_length_low, rest @ ..] = state else {
+            // No state was saved
+            return;
+        };
+
+        self.bank = *bank;
+        self.control = *control;
+        self.status = *status;
+
+        let ram_length = ((*ram_length_high as usize) << 16) | *ram_length_low as usize;
+        let (ram, flash) = rest.split_at(ram_length.min(rest.len()));
+
+        self.ram.load_u16(ram);
+
+        self.flash_modified = flash.len() == self.flash.len();
+
+        if self.flash_modified {
+            self.flash.copy_from_slice(flash);
+        } else {
+            self.flash.copy_from_slice(&self.original_flash);
+        }
+    }
+
+    fn mednafen_power_on(&mut self) {
+        self.ram.mednafen_power_on();
+    }
+}
@@ -0,0 +1,276 @@
+mod flash;
+mod rom_info;
+mod save_ram;
+mod standard;
+
+use std::{borrow::Cow, fmt, sync::OnceLock};
+
+use savefile::{
+    Deserialize, Packed, Schema, SchemaPrimitive, Serialize, VecOrStringLayout, WithSchema,
+};
+
+pub use flash::FlashMapper;
+pub use rom_info::RomInfo;
+pub use standard::StandardMapper;
+
+/// A ROM or flash image that cannot be loaded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RomError {
+    Empty,
+    /// Larger than the 16MB ROM region
+    TooLarge {
+        size: usize,
+    },
+    FlashSizeNotPowerOfTwo {
+        size: usize,
+    },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::Empty => write!(f, "ROM is empty"),
+            RomError::TooLarge { size } => write!(f, "ROM is too large ({size} bytes)"),
+            RomError::FlashSizeNotPowerOfTwo { size } => {
+                write!(f, "Flash size must be a power of two ({size} bytes)")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+/// The hardware on a Game Pak, which is mapped into the expansion area at `0x0400_0000`, RAM at `0x0600_0000`, and
+/// ROM at `0x0700_0000`. Addresses are halfword offsets into each 16MB region.
+///
+/// Mappers are not stepped with the rest of the system, so their state, including the Game Pak interrupt line, can
+/// only change in response to accesses
+pub trait CartridgeMapper: Send {
+    fn read_rom(&self, address: usize) -> u16;
+
+    /// The ROM image as loaded, before mirroring or any writes
+    fn rom_image(&self) -> Cow<'_, [u8]>;
+
+    /// Writes to the ROM region. Ignored by mask ROMs.
+    ///
+    /// Returns true if the contents of the ROM region changed
+    fn write_rom(&mut self, _address: usize, _value: u16) -> bool {
+        false
+    }
+
+    fn read_ram(&mut self, address: usize) -> u16;
+
+    /// Reads RAM without side effects. Used for debugging
+    fn peek_ram(&self, address: usize) -> u16;
+
+    fn write_ram(&mut self, address: usize, value: u16);
+
+    fn read_expansion(&mut self, address: usize) -> u16 {
+        self.peek_expansion(address)
+    }
+
+    /// Reads the expansion area without side effects. Used for debugging
+    fn peek_expansion(&self, _address: usize) -> u16 {
+        0
+    }
+
+    /// Writes to the expansion area. Returns true if the contents of the ROM region changed, such as by a bank switch
+    fn write_expansion(&mut self, _address: usize, _value: u16) -> bool {
+        false
+    }
+
+    /// Whether the Game Pak interrupt is asserted
+    fn interrupt_line(&self) -> bool {
+        false
+    }
+
+    /// Battery backed save data
+    fn dump_ram(&self) -> Vec<u8>;
+
+    fn load_ram(&mut self, ram: Vec<u8>);
+
+    /// All mutable state, as stored in savestates. The ROM image is not included
+    fn savestate(&self) -> Vec<u16>;
+
+    fn load_savestate(&mut self, state: &[u16]);
+
+    /// Restores the state of this mapper, as read from a savestate, into `mapper`, the running mapper
+    fn restore_into(&self, mapper: &mut dyn CartridgeMapper) {
+        mapper.load_savestate(&self.savestate());
+    }
+
+    /// Sets RAM to the contents Mednafen uses at power on
+    fn mednafen_power_on(&mut self) {}
+}
+
+/// The Game Pak inserted into the bus
+pub struct Cartridge {
+    mapper: Box<dyn CartridgeMapper>,
+
+    /// Computed on first use, as hashing the image is slow
+    info: OnceLock<RomInfo>,
+}
+
+/// The state of a mapper read from a savestate, before it is restored into the running mapper
+struct SavedMapper {
+    state: Vec<u16>,
+    /// Savestates before version 5 hold only the words of RAM in use, whatever the mapper
+    ram_only: bool,
+}
+
+impl Cartridge {
+    pub fn new(mapper: Box<dyn CartridgeMapper>) -> Self {
+        Cartridge {
+            mapper,
+            info: OnceLock::new(),
+        }
+    }
+
+    pub fn rom_info(&self) -> &RomInfo {
+        self.info
+            .get_or_init(|| RomInfo::new(&self.mapper.rom_image()))
+    }
+
+    /// Restores the mapper state held by `saved`, a cartridge read from a savestate
+    pub fn restore_from(&mut self, saved: &Cartridge) {
+        saved.mapper.restore_into(&mut *self.mapper);
+    }
+
+    pub fn mednafen_power_on(&mut self) {
+        self.mapper.mednafen_power_on();
+    }
+
+    pub fn dump_ram(&self) -> Vec<u8> {
+        self.mapper.dump_ram()
+    }
+
+    pub fn load_ram(&mut self, ram: Vec<u8>) {
+        self.mapper.load_ram(ram);
+    }
+
+    pub fn get_rom(&self, address: usize) -> u16 {
+        self.mapper.read_rom(address)
+    }
+
+    /// Returns true if the contents of the ROM region changed
+    pub fn set_rom(&mut self, address: usize, value: u16) -> bool {
+        self.mapper.write_rom(address, value)
+    }
+
+    pub fn get_ram(&mut self, address: usize) -> u16 {
+        self.mapper.read_ram(address)
+    }
+
+    /// Reads RAM without growing the observed RAM size
+    pub fn peek_ram(&self, address: usize) -> u16 {
+        self.mapper.peek_ram(address)
+    }
+
+    pub fn set_ram(&mut self, address: usize, value: u16) {
+        self.mapper.write_ram(address, value);
+    }
+
+    pub fn get_expansion(&mut self, address: usize) -> u16 {
+        self.mapper.read_expansion(address)
+    }
+
+    pub fn peek_expansion(&self, address: usize) -> u16 {
+        self.mapper.peek_expansion(address)
+    }
+
+    /// Returns true if the contents of the ROM region changed
+    pub fn set_expansion(&mut self, address: usize, value: u16) -> bool {
+        self.mapper.write_expansion(address, value)
+    }
+
+    pub fn interrupt_line(&self) -> bool {
+        self.mapper.interrupt_line()
+    }
+}
+
+impl CartridgeMapper for SavedMapper {
+    fn read_rom(&self, _address: usize) -> u16 {
+        0
+    }
+
+    fn rom_image(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&[])
+    }
+
+    fn read_ram(&mut self, _address: usize) -> u16 {
+        0
+    }
+
+    fn peek_ram(&self, _address: usize) -> u16 {
+        0
+    }
+
+    fn write_ram(&mut self, _address: usize, _value: u16) {}
+
+    fn dump_ram(&self) -> Vec<u8> {
+        vec![]
+    }
+
+    fn load_ram(&mut self, _ram: Vec<u8>) {}
+
+    fn savestate(&self) -> Vec<u16> {
+        self.state.clone()
+    }
+
+    fn load_savestate(&mut self, state: &[u16]) {
+        self.state = state.to_vec();
+    }
+
+    fn restore_into(&self, mapper: &mut dyn CartridgeMapper) {
+        if self.ram_only {
+            // Restored as a save file would be, leaving the rest of the mapper as it is
+            mapper.load_ram(
+                self.state
+                    .iter()
+                    .flat_map(|word| word.to_le_bytes())
+                    .collect(),
+            );
+        } else {
+            mapper.load_savestate(&self.state);
+        }
+    }
+}
+
+impl WithSchema for Cartridge {
+    fn schema(_version: u32, _context: &mut savefile::WithSchemaContext) -> Schema {
+        Schema::Vector(
+            Box::new(Schema::Primitive(SchemaPrimitive::schema_u16)),
+            VecOrStringLayout::default(),
+        )
+    }
+}
+
+impl Serialize for Cartridge {
+    fn serialize(
+        &self,
+        serializer: &mut savefile::Serializer<impl std::io::Write>,
+    ) -> Result<(), savefile::SavefileError> {
+        self.mapper.savestate().serialize(serializer)?;
+
+        Ok(())
+    }
+}
+
+impl Deserialize for Cartridge {
+    fn deserialize(
+        deserializer: &mut savefile::Deserializer<impl std::io::Read>,
+    ) -> Result<Self, savefile::SavefileError> {
+        let state = Vec::<u16>::deserialize(deserializer)?;
+
+        Ok(Cartridge::new(Box::new(SavedMapper {
+            state,
+            ram_only: deserializer.file_version < 5,
+        })))
+    }
+}
+
+impl Packed for Cartridge {
+    unsafe fn repr_c_optimization_safe(_version: u32) -> savefile::IsPacked {
+        savefile::IsPacked::yes()
+    }
+}
@@ -0,0 +1,83 @@
+use encoding_rs::SHIFT_JIS;
+
+/// Offset of the header from the end of the image. The header ends where the interrupt vectors begin, at `0xFFFFFE00`
+const HEADER_END_OFFSET: usize = 0x220;
+
+const TITLE_LENGTH: usize = 20;
+/// Offset of the maker code, following the title and 5 reserved bytes
+const MAKER_OFFSET: usize = 0x19;
+const MAKER_LENGTH: usize = 2;
+const GAME_ID_OFFSET: usize = 0x1B;
+const GAME_ID_LENGTH: usize = 4;
+const REVISION_OFFSET: usize = 0x1F;
+
+/// Identifying information for a ROM image, from its header at `0xFFFFFDE0` and hashes of the image.
+///
+/// Header fields are empty for images too small to hold a header
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RomInfo {
+    /// Decoded from Shift-JIS, with padding removed
+    pub title: String,
+    /// Two character maker code, such as "01" for Nintendo
+    pub maker: String,
+    /// Four character game code, such as "VMBE"
+    pub game_id: String,
+    /// Minor version, printed as `1.{revision}`
+    pub revision: u8,
+
+    /// Size of the image as loaded, before mirroring
+    pub size: usize,
+    pub crc32: u32,
+    pub md5: [u8; 16],
+    pub sha1: [u8; 20],
+}
+
+impl RomInfo {
+    pub fn new(image: &[u8]) -> Self {
+        let header = image
+            .len()
+            .checked_sub(HEADER_END_OFFSET)
+            .map(|start| &image[start..]);
+
+        let field = |offset: usize, length: usize| {
+            header.map_or(&[][..], |header| &header[offset..offset + length])
+        };
+
+        let (title, _) = SHIFT_JIS.decode_without_bom_handling(field(0, TITLE_LENGTH));
+
+        RomInfo {
+            title: trim_padding(&title),
+            maker: trim_padding(&String::from_utf8_lossy(field(MAKER_OFFSET, MAKER_LENGTH))),
+            game_id: trim_padding(&String::from_utf8_lossy(field(
+                GAME_ID_OFFSET,
+                GAME_ID_LENGTH,
+            ))),
+            revision: header.map_or(0, |header| header[REVISION_OFFSET]),
+
+            size: image.len(),
+            crc32: crc32fast::hash(image),
+            md5: md5::compute(image).0,
+            sha1: sha1_smol::Sha1::from(image).digest().bytes(),
+        }
+    }
+
+    /// The MD5 as lowercase hex, which names the game's folder in the manifests
+    pub fn md5_hex(&self) -> String {
+        to_hex(&self.md5)
+    }
+
+    pub fn sha1_hex(&self) -> String {
+        to_hex(&self.sha1)
+    }
+}
+
+/// Titles are padded with spaces, which may be full width, or with NULs
+fn trim_padding(field: &str) -> String {
+    field
+        .trim_end_matches(|c: char| c == '\0' || c.is_whitespace())
+        .to_string()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -0,0 +1,115 @@
+use std::slice::from_raw_parts;
+
+use crate::constants::{MAX_ROM_RAM_SIZE, MIN_ROM_RAM_SIZE};
+
+/// Battery backed Game Pak RAM. The size of the RAM is not declared by the ROM, so it is tracked by the highest
+/// address used
+pub struct SaveRam {
+    ram: Vec<u16>,
+
+    /// The maximum observed size of the RAM. If `None`, RAM has not been used.
+    ram_size: Option<usize>,
+}
+
+impl SaveRam {
+    pub fn new() -> Self {
+        // Initialize RAM to 0
+        SaveRam {
+            ram: vec![0; MAX_ROM_RAM_SIZE / 2],
+            ram_size: None,
+        }
+    }
+
+    pub fn dump(&self) -> Vec<u8> {
+        let array = unsafe {
+            from_raw_parts(
+                self.ram.as_ptr() as *const u8,
+                self.ram_size.unwrap_or(self.ram.len() * 2),
+            )
+        };
+
+        Vec::from(array)
+    }
+
+    pub fn load(&mut self, ram: Vec<u8>) {
+        let array = unsafe { from_raw_parts(ram.as_ptr() as *const u16, ram.len() / 2) };
+
+        self.load_u16(array);
+    }
+
+    /// The RAM in use, as stored in savestates
+    pub fn used_words(&self) -> &[u16] {
+        &self.ram[0..self.ram_size.unwrap_or(0)]
+    }
+
+    pub fn load_u16(&mut self, ram_array: &[u16]) {
+        // Reinit RAM
+        self.ram = vec![0; MAX_ROM_RAM_SIZE / 2];
+
+        // Track the highest address in use
+        // This lets us shrink saves created in the first release of VirtualFriend
+        let mut max_value_address = 0;
+
+        // Copy save words
+        for i in 0..ram_array.len() {
+            let value = ram_array[i];
+
+            if value > 0 {
+                max_value_address = i;
+            }
+
+            self.ram[i] = value;
+        }
+
+        if ram_array.len() > 0 && max_value_address > 0 {
+            self.build_ram_size(max_value_address);
+        } else {
+            self.ram_size = None;
+        }
+    }
+
+    pub fn get(&mut self, address: usize) -> u16 {
+        self.build_ram_size(address);
+
+        self.ram[address]
+    }
+
+    /// Reads RAM without growing the observed RAM size
+    pub fn peek(&self, address: usize) -> u16 {
+        self.ram[address]
+    }
+
+    pub fn set(&mut self, address: usize, value: u16) {
+        self.build_ram_size(address);
+
+        self.ram[address] = value;
+    }
+
+    /// Fills RAM with the pattern Mednafen leaves at power on, without growing the observed RAM size
+    pub fn mednafen_power_on(&mut self) {
+        for block in 0..3 {
+            let start = block * 0x50 / 2;
+
+            self.ram[start..start + 0x24].fill(0xFFFF);
+        }
+    }
+
+    fn build_ram_size(&mut self, address: usize) {
+        let size = self.ram_size.unwrap_or(0);
+
+        if address >= size {
+            // Address is outside of expected RAM bounds. Increase our internal representation of its size
+            // Addresses are 2 byte, and our min size is in bytes
+            let mut size = self.ram_size.unwrap_or(MIN_ROM_RAM_SIZE / 2);
+
+            while size <= address {
+                // RAM access is out of range. Up the RAM size
+                // Size cannot go over 16MB because address will never be larger than that
+                // If `address` equals `size`, then the address is at the beginning of the next `size` bank
+                size = size * 2;
+            }
+
+            self.ram_size = Some(size);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::{borrow::Cow, slice::from_raw_parts};
+
+use crate::constants::MAX_ROM_SIZE;
+
+use super::{save_ram::SaveRam, CartridgeMapper, RomError};
+
+/// A retail Game Pak, with a mask ROM and optional battery backed RAM. The expansion area is unused
+pub struct StandardMapper {
+    rom: ROM,
+    ram: SaveRam,
+}
+
+struct ROM {
+    // Max 16MB
+    // Buffers must be heap allocated, as stack allocation of large buffers causes segfaults on non-x86 platforms
+    rom_buffer: Box<[u8]>,
+    /// Length of the image as loaded. Mirroring only appends to the image
+    rom_length: usize,
+
+    /// ROM address mask for word addresses
+    rom_address_mask: usize,
+}
+
+impl ROM {
+    fn new(rom_vec: Vec<u8>) -> Result<Self, RomError> {
+        if rom_vec.is_empty() {
+            return Err(RomError::Empty);
+        }
+
+        if rom_vec.len() > MAX_ROM_SIZE {
+            return Err(RomError::TooLarge {
+                size: rom_vec.len(),
+            });
+        }
+
+        let rom_length = rom_vec.len();
+        let rom_buffer = mirror_to_power_of_two(rom_vec).into_boxed_slice();
+
+        let rom_address_mask = (rom_buffer.len() / 2) - 1;
+
+        Ok(ROM {
+            rom_buffer,
+            rom_length,
+            rom_address_mask,
+        })
+    }
+}
+
+impl StandardMapper {
+    pub fn new(rom: Vec<u8>) -> Result<Self, RomError> {
+        Ok(StandardMapper {
+            rom: ROM::new(rom)?,
+            ram: SaveRam::new(),
+        })
+    }
+}
+
+/// Expands `rom` to a power of two in size, mirrored as a Game Pak built from several ROM chips decodes it.
+///
+/// The largest power of two that fits is on the first chip. The remainder is on smaller chips, which only partially
+/// decode the address, and so repeat to fill the rest of the address space. This keeps the vectors at the end of an
+/// image at the top of the ROM region
+fn mirror_to_power_of_two(rom: Vec<u8>) -> Vec<u8> {
+    if rom.len().is_power_of_two() {
+        if rom.len() == 1 {
+            // Reads are a halfword at a time
+            return vec![rom[0]; 2];
+        }
+
+        return rom;
+    }
+
+    let first_chip_size = 1 << (usize::BITS - 1 - rom.len().leading_zeros());
+
+    let remainder = mirror_to_power_of_two(rom[first_chip_size..].to_vec());
+
+    let mut mirrored = rom;
+    mirrored.truncate(first_chip_size);
+
+    while mirrored.len() < first_chip_size * 2 {
+        mirrored.extend_from_slice(&remainder);
+    }
+
+    mirrored
+}
+
+impl CartridgeMapper for StandardMapper {
+    fn read_rom(&self, address: usize) -> u16 {
+        let rom = unsafe {
+            from_raw_parts(
+                self.rom.rom_buffer.as_ptr() as *const u16,
+                self.rom.rom_buffer.len() / 2,
+            )
+        };
+
+        let address = address & self.rom.rom_address_mask;
+
+        rom[address]
+    }
+
+    fn rom_image(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.rom.rom_buffer[..self.rom.rom_length])
+    }
+
+    fn read_ram(&mut self, address: usize) -> u16 {
+        self.ram.get(address)
+    }
+
+    fn peek_ram(&self, address: usize) -> u16 {
+        self.ram.peek(address)
+    }
+
+    fn write_ram(&mut self, address: usize, value: u16) {
+        self.ram.set(address, value);
+    }
+
+    fn dump_ram(&self) -> Vec<u8> {
+        self.ram.dump()
+    }
+
+    fn load_ram(&mut self, ram: Vec<u8>) {
+        self.ram.load(ram);
+    }
+
+    fn savestate(&self) -> Vec<u16> {
+        self.ram.used_words().to_vec()
+    }
+
+    fn load_savestate(&mut self, state: &[u16]) {
+        self.ram.load_u16(state);
+    }
+
+    fn mednafen_power_on(&mut self) {
+        self.ram.mednafen_power_on();
+    }
+}
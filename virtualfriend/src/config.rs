@@ -0,0 +1,82 @@
+use std::{
+    fmt,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// What to do when the CPU encounters an opcode, float sub-opcode, or bit string sub-opcode that does not exist
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IllegalOpcodeBehavior {
+    /// Raise the illegal opcode exception (0xFF90), as hardware does
+    #[default]
+    Exception,
+    /// Halt the system and return a `CrashReport`
+    HaltAndReport,
+    /// Log the instruction and skip it
+    LogAndContinue,
+}
+
+/// The contents of WRAM, and for `Mednafen`, the CPU registers and Game Pak RAM, when the system powers on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PowerOnState {
+    /// Random WRAM, as on hardware, using a different seed every power on
+    #[default]
+    Random,
+    /// Random WRAM generated from the seed, so that runs can be reproduced
+    RandomSeeded(u64),
+    /// WRAM is cleared
+    Zeroed,
+    /// The register, WRAM, and Game Pak RAM contents Mednafen uses, so its trace logs can be compared in lockstep
+    Mednafen,
+}
+
+/// Source of the time stamped into savestates
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Seconds since the Unix epoch
+    fn now_s(&self) -> u64;
+}
+
+/// The host's wall clock
+#[derive(Debug)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_s(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+}
+
+/// Always reports the same time, so that savestates are reproducible
+#[derive(Debug)]
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now_s(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Emulator options. These are not stored in savestates, and are retained when a savestate is loaded
+#[derive(Clone, Debug)]
+pub struct EmulatorConfig {
+    pub illegal_opcode: IllegalOpcodeBehavior,
+    pub power_on: PowerOnState,
+    pub clock: Arc<dyn Clock>,
+    /// Step HALT and idle polling loops cycle by cycle, rather than skipping ahead to the next hardware event. Both
+    /// produce identical results, but skipping is much faster
+    pub disable_idle_skipping: bool,
+}
+
+impl Default for EmulatorConfig {
+    fn default() -> Self {
+        EmulatorConfig {
+            illegal_opcode: IllegalOpcodeBehavior::default(),
+            power_on: PowerOnState::default(),
+            clock: Arc::new(SystemClock),
+            disable_idle_skipping: false,
+        }
+    }
+}
@@ -48,6 +48,13 @@ pub const DRAWING_BLOCK_CYCLE_COUNT: usize =
 /// Total time SBOUT remains high
 pub const SBOUT_HIGH_CYCLE_COUNT: usize = CYCLES_PER_US * 56;
 
+/// Wait states added to each CPU transfer to VRAM while drawing. The drawing process reads characters and writes the
+/// framebuffers for most of each block, so the CPU is held until VRAM is free.
+///
+/// An approximation, not taken from documentation or measured on hardware. A flat cost is charged per transfer, rather
+/// than modelling when drawing actually accesses VRAM
+pub const VRAM_DRAWING_WAIT_CYCLE_COUNT: u32 = 2;
+
 //
 // Gamepad
 //
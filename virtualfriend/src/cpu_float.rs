@@ -0,0 +1,220 @@
+use std::cmp::Ordering;
+
+use crate::cpu_internals::RoundingMode;
+
+/// Exceptions raised by a single floating-point operation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FloatExceptions {
+    /// The result was rounded
+    pub precision: bool,
+    /// The result was too small to be represented, and was flushed to zero
+    pub underflow: bool,
+    /// The result was too large to be represented
+    pub overflow: bool,
+    pub zero_divide: bool,
+    pub invalid: bool,
+}
+
+pub struct FloatResult {
+    pub value: f32,
+    pub exceptions: FloatExceptions,
+}
+
+/// The V810 does not support NaN, infinity, or denormal values. Any of these as an operand raises the reserved
+/// operand exception
+pub fn is_reserved(bits: u32) -> bool {
+    let exponent = (bits >> 23) & 0xFF;
+
+    match exponent {
+        0xFF => true,
+        // Zero is valid, but denormals are not
+        0 => bits & 0x7F_FFFF != 0,
+        _ => false,
+    }
+}
+
+pub fn add(lhs: f32, rhs: f32, rounding: RoundingMode) -> FloatResult {
+    let lhs = lhs as f64;
+    let rhs = rhs as f64;
+
+    let sum = lhs + rhs;
+
+    // Two sum: recover the error of the f64 addition, so the exact sum is `sum + error`
+    let rhs_virtual = sum - lhs;
+    let lhs_virtual = sum - rhs_virtual;
+    let error = (lhs - lhs_virtual) + (rhs - rhs_virtual);
+
+    round(sum, compare_to_zero(error), rounding)
+}
+
+pub fn sub(lhs: f32, rhs: f32, rounding: RoundingMode) -> FloatResult {
+    add(lhs, -rhs, rounding)
+}
+
+pub fn mul(lhs: f32, rhs: f32, rounding: RoundingMode) -> FloatResult {
+    // The product of two f32 mantissas fits exactly in an f64
+    round(lhs as f64 * rhs as f64, Ordering::Equal, rounding)
+}
+
+pub fn div(dividend: f32, divisor: f32, rounding: RoundingMode) -> FloatResult {
+    if divisor == 0.0 {
+        let invalid = dividend == 0.0;
+
+        return FloatResult {
+            value: if invalid {
+                f32::NAN
+            } else {
+                f32::INFINITY.copysign(dividend * divisor.signum())
+            },
+            exceptions: FloatExceptions {
+                zero_divide: !invalid,
+                invalid,
+                ..Default::default()
+            },
+        };
+    }
+
+    let dividend = dividend as f64;
+    let divisor = divisor as f64;
+
+    let quotient = dividend / divisor;
+
+    // The exact remainder of the f64 division, which has the same sign as the remaining error times the divisor
+    let remainder = (-quotient).mul_add(divisor, dividend);
+
+    let residual = if divisor < 0.0 {
+        compare_to_zero(remainder).reverse()
+    } else {
+        compare_to_zero(remainder)
+    };
+
+    round(quotient, residual, rounding)
+}
+
+/// CVT.WS
+pub fn int_to_float(value: i32, rounding: RoundingMode) -> FloatResult {
+    round(value as f64, Ordering::Equal, rounding)
+}
+
+/// CVT.SW and TRNC.SW
+pub fn float_to_int(value: f32, rounding: RoundingMode) -> (i32, FloatExceptions) {
+    let rounded = match rounding {
+        RoundingMode::Nearest => value.round_ties_even(),
+        RoundingMode::TowardZero => value.trunc(),
+        RoundingMode::TowardPositive => value.ceil(),
+        RoundingMode::TowardNegative => value.floor(),
+    };
+
+    if !(-2_147_483_648.0..2_147_483_648.0).contains(&rounded) {
+        return (
+            i32::MIN,
+            FloatExceptions {
+                invalid: true,
+                ..Default::default()
+            },
+        );
+    }
+
+    (
+        rounded as i32,
+        FloatExceptions {
+            precision: rounded != value,
+            ..Default::default()
+        },
+    )
+}
+
+/// Rounds an exact value to single precision. The exact value is `approximate` plus a small error with the sign
+/// `residual`, where `approximate` is the f64 nearest to the exact value
+fn round(approximate: f64, residual: Ordering, rounding: RoundingMode) -> FloatResult {
+    let nearest = approximate as f32;
+
+    let mut exceptions = FloatExceptions::default();
+
+    let value = if nearest as f64 == approximate && residual == Ordering::Equal {
+        // Exact
+        nearest
+    } else {
+        exceptions.precision = true;
+
+        // Find the two representable values surrounding the exact value
+        let (lower, upper) = if (nearest as f64) > approximate
+            || (nearest as f64 == approximate && residual == Ordering::Less)
+        {
+            (next_down(nearest), nearest)
+        } else {
+            (nearest, next_up(nearest))
+        };
+
+        match rounding {
+            RoundingMode::Nearest => {
+                let lower_distance = approximate - unbounded(lower);
+                let upper_distance = unbounded(upper) - approximate;
+
+                match lower_distance.partial_cmp(&upper_distance) {
+                    Some(Ordering::Less) => lower,
+                    Some(Ordering::Greater) => upper,
+                    // Exactly between two values. Use the error to break the tie, otherwise round to even
+                    _ => match residual {
+                        Ordering::Less => lower,
+                        Ordering::Greater => upper,
+                        Ordering::Equal => nearest,
+                    },
+                }
+            }
+            RoundingMode::TowardZero => {
+                if approximate > 0.0 {
+                    lower
+                } else {
+                    upper
+                }
+            }
+            RoundingMode::TowardPositive => upper,
+            RoundingMode::TowardNegative => lower,
+        }
+    };
+
+    if value.is_infinite() || approximate.abs() >= 2.0_f64.powi(128) {
+        exceptions.overflow = true;
+    } else if value.abs() < f32::MIN_POSITIVE && (value != 0.0 || exceptions.precision) {
+        // Denormals are not supported. Flush to zero. A nonzero result may also have already rounded to zero
+        exceptions.underflow = true;
+        exceptions.precision = true;
+
+        return FloatResult {
+            value: 0.0_f32.copysign(value),
+            exceptions,
+        };
+    }
+
+    FloatResult { value, exceptions }
+}
+
+/// Treats infinity as the first power of two beyond the largest finite value, for the purposes of rounding to nearest
+fn unbounded(value: f32) -> f64 {
+    if value.is_infinite() {
+        2.0_f64.powi(128).copysign(value as f64)
+    } else {
+        value as f64
+    }
+}
+
+fn next_up(value: f32) -> f32 {
+    if value == f32::INFINITY {
+        value
+    } else if value == 0.0 {
+        f32::from_bits(1)
+    } else if value > 0.0 {
+        f32::from_bits(value.to_bits() + 1)
+    } else {
+        f32::from_bits(value.to_bits() - 1)
+    }
+}
+
+fn next_down(value: f32) -> f32 {
+    -next_up(-value)
+}
+
+fn compare_to_zero(value: f64) -> Ordering {
+    value.partial_cmp(&0.0).unwrap_or(Ordering::Equal)
+}
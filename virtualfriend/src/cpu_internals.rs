@@ -1,5 +1,7 @@
 use bitvec::prelude::*;
 
+use crate::cpu_float::FloatExceptions;
+
 #[derive(Savefile)]
 pub struct ProgramStatusWord {
     pub zero: bool,
@@ -108,35 +110,121 @@ impl ProgramStatusWord {
         }
     }
 
-    pub fn update_float_flags(
-        &mut self,
-        value: f32,
-        set_fro: bool,
-        _set_fiv: bool,
-        _set_fzd: bool,
-        set_fov: bool,
-        set_fud: bool,
-        _set_fpr: bool,
-    ) {
-        // https://doc.rust-lang.org/stable/reference/expressions/operator-expr.html#numeric-cast
-        if set_fro && value.is_nan() {
-            self.float_reserved = true;
-        }
+    /// Sets the sticky floating-point exception flags for any exceptions raised by an operation
+    pub fn update_float_exception_flags(&mut self, exceptions: &FloatExceptions) {
+        self.float_precision |= exceptions.precision;
+        self.float_underflow |= exceptions.underflow;
+        self.float_overflow |= exceptions.overflow;
+        self.float_zero_divide |= exceptions.zero_divide;
+        self.float_invalid |= exceptions.invalid;
+    }
 
-        if set_fov && value == f32::INFINITY {
-            self.float_overflow = true;
-        }
+    /// Sets the condition flags for a floating-point result
+    pub fn update_float_flags(&mut self, value: f32) {
+        self.zero = value == 0.0;
+        self.sign = value.is_sign_negative() && value != 0.0;
+        self.overflow = false;
+        self.carry = self.sign;
+    }
+}
 
-        if set_fud && value == f32::NEG_INFINITY {
-            self.float_underflow = true;
-        }
+#[derive(Savefile, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundingMode {
+    Nearest,
+    TowardZero,
+    TowardPositive,
+    TowardNegative,
+}
 
-        // TODO: I don't know how to detect this in Rust
-        // self.float_precision = false;
+/// TKCW - Task Control Word
+///
+/// Controls rounding and which floating-point exceptions trap. Powers on as 0xE0, trapping invalid operations, zero
+/// division, and overflow while rounding to nearest.
+#[derive(Savefile)]
+pub struct TaskControlWord {
+    /// RD - Rounding direction for floating-point operations
+    pub rounding: RoundingMode,
+    /// RDI - When set, CVT.SW truncates like TRNC.SW rather than rounding according to RD
+    pub truncate_conversion: bool,
+
+    /// FPT - Precision degradation trap enable. The VB's CPU has no precision exception, so this has no effect
+    pub precision_trap: bool,
+    /// FUT - Underflow trap enable. The VB's CPU has no underflow exception, so this has no effect
+    pub underflow_trap: bool,
+    /// FVT - Overflow trap enable
+    pub overflow_trap: bool,
+    /// FZT - Zero division trap enable
+    pub zero_divide_trap: bool,
+    /// FIT - Invalid operation trap enable
+    pub invalid_trap: bool,
+}
 
-        self.zero = value == 0.0;
-        self.sign = value.is_sign_negative();
-        self.overflow = false;
-        self.carry = value.is_sign_negative();
+impl TaskControlWord {
+    pub fn new() -> Self {
+        let mut tkcw = TaskControlWord {
+            rounding: RoundingMode::Nearest,
+            truncate_conversion: false,
+            precision_trap: false,
+            underflow_trap: false,
+            overflow_trap: false,
+            zero_divide_trap: false,
+            invalid_trap: false,
+        };
+
+        tkcw.set(0xE0);
+
+        tkcw
+    }
+
+    pub fn get(&self) -> u32 {
+        let rounding = match self.rounding {
+            RoundingMode::Nearest => 0,
+            RoundingMode::TowardZero => 1,
+            RoundingMode::TowardPositive => 2,
+            RoundingMode::TowardNegative => 3,
+        };
+
+        rounding
+            | (self.truncate_conversion as u32) << 2
+            | (self.precision_trap as u32) << 3
+            | (self.underflow_trap as u32) << 4
+            | (self.overflow_trap as u32) << 5
+            | (self.zero_divide_trap as u32) << 6
+            | (self.invalid_trap as u32) << 7
     }
+
+    pub fn set(&mut self, value: u32) {
+        self.rounding = match value & 0x3 {
+            0 => RoundingMode::Nearest,
+            1 => RoundingMode::TowardZero,
+            2 => RoundingMode::TowardPositive,
+            _ => RoundingMode::TowardNegative,
+        };
+
+        self.truncate_conversion = value & 0x4 != 0;
+        self.precision_trap = value & 0x8 != 0;
+        self.underflow_trap = value & 0x10 != 0;
+        self.overflow_trap = value & 0x20 != 0;
+        self.zero_divide_trap = value & 0x40 != 0;
+        self.invalid_trap = value & 0x80 != 0;
+    }
+}
+
+/// A snapshot of the architectural registers of the CPU
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Registers {
+    pub pc: u32,
+
+    /// r0 is always 0
+    pub general_purpose: [u32; 32],
+
+    pub eipc: u32,
+    pub eipsw: u32,
+    pub fepc: u32,
+    pub fepsw: u32,
+    pub ecr: u32,
+    pub psw: u32,
+    pub tkcw: u32,
+    pub chcw: u32,
+    pub adtre: u32,
 }
@@ -0,0 +1,165 @@
+// Cycle counts for instructions whose execution time depends on their operands.
+// The V810 manual only gives a minimum and maximum for the floating point instructions, and no per-word figures for
+// the bit string instructions. Neither has been measured on hardware, so these counts are approximations. Float
+// instructions take the manual's minimum when no shifting is needed and its maximum for the longest possible shifts,
+// interpolated linearly by the number of bits the mantissas are shifted. Bit string instructions are charged for the
+// words they read and write, at the cost of the equivalent load or store.
+
+/// Cycles spent starting a bit string instruction, before any words are processed. An estimate
+const BIT_STRING_START_CYCLES: u32 = 13;
+
+/// Cycles to read a single word of a bit string. That of a load not following another load, as charged by
+/// `load_inst_cycle_count`
+const BIT_STRING_READ_CYCLES: u32 = 3;
+
+/// Cycles to scan a source word during a search. An estimate
+const BIT_STRING_SEARCH_CYCLES: u32 = 2;
+
+/// Cycles to write back a modified destination word. That of a store not following another store, as charged by
+/// `store_inst_cycle_count`
+const BIT_STRING_WRITE_CYCLES: u32 = 1;
+
+/// Cycles to shift source bits into position when the source and destination offsets differ. An estimate
+const BIT_STRING_ALIGNMENT_CYCLES: u32 = 2;
+
+/// The most bits a mantissa, with its implicit bit, can be shifted before it is entirely shifted out
+const MANTISSA_BITS: u32 = 24;
+
+/// ADDF.S takes 9-28 cycles
+pub fn addf_cycles(lhs: f32, rhs: f32, result: f32) -> u32 {
+    float_add_cycles(lhs, rhs, result, 9, 28)
+}
+
+/// SUBF.S takes 12-28 cycles
+pub fn subf_cycles(lhs: f32, rhs: f32, result: f32) -> u32 {
+    float_add_cycles(lhs, rhs, result, 12, 28)
+}
+
+/// CMPF.S takes 7-10 cycles. It is a subtraction that discards its result
+pub fn cmpf_cycles(lhs: f32, rhs: f32) -> u32 {
+    float_add_cycles(lhs, rhs, lhs - rhs, 7, 10)
+}
+
+/// MULF.S takes 8-30 cycles
+pub fn mulf_cycles(multiplicand: f32, multiplier: f32, result: f32) -> u32 {
+    if multiplicand == 0.0 || multiplier == 0.0 {
+        return 8;
+    }
+
+    // The multiplier mantissa is consumed until only zero bits remain
+    let mantissa = (multiplier.to_bits() & 0x7F_FFFF) | 0x80_0000;
+    let significant_bits = MANTISSA_BITS - mantissa.trailing_zeros();
+
+    // The product of two normalized mantissas is in [1, 4), so needs at most one normalizing shift
+    let expected_exponent = exponent(multiplicand) + exponent(multiplier) - 127;
+    let normalization = if exponent(result) != expected_exponent {
+        1
+    } else {
+        0
+    };
+
+    interpolate(8, 30, significant_bits - 1 + normalization, MANTISSA_BITS)
+}
+
+/// CVT.WS takes 5-16 cycles
+pub fn cvt_ws_cycles(value: i32) -> u32 {
+    if value == 0 {
+        return 5;
+    }
+
+    let magnitude = value.unsigned_abs();
+
+    // Shifting the integer left until the leading one reaches the top bit
+    let normalization = magnitude.leading_zeros();
+
+    // Integers wider than the mantissa must be rounded
+    let rounding = if magnitude >= 1 << MANTISSA_BITS {
+        1
+    } else {
+        0
+    };
+
+    interpolate(5, 16, normalization + rounding, 32)
+}
+
+/// CVT.SW and TRNC.SW take 9-14 cycles
+pub fn cvt_sw_cycles(value: f32) -> u32 {
+    if value == 0.0 {
+        return 9;
+    }
+
+    // Shifting the mantissa until the binary point lines up with the integer. Any further shift overflows or leaves
+    // zero
+    let shift = (exponent(value) - 127 - 23).unsigned_abs();
+
+    interpolate(9, 14, shift, 32)
+}
+
+/// Cycles for a single step of a bit string search, which scans at most one source word
+pub fn bit_string_search_cycles(starting: bool) -> u32 {
+    bit_string_start_cycles(starting) + BIT_STRING_READ_CYCLES + BIT_STRING_SEARCH_CYCLES
+}
+
+/// Cycles for a single step of a bit string transfer, which writes at most one destination word.
+///
+/// `source_words` is the number of source words read to fill the destination word. `aligned` is whether the
+/// source and destination bit offsets match, so no shifting was required
+pub fn bit_string_transfer_cycles(starting: bool, source_words: u32, aligned: bool) -> u32 {
+    let alignment = if aligned {
+        0
+    } else {
+        BIT_STRING_ALIGNMENT_CYCLES
+    };
+
+    bit_string_start_cycles(starting)
+        + source_words * BIT_STRING_READ_CYCLES
+        // Read, modify, and write the destination word
+        + BIT_STRING_READ_CYCLES
+        + BIT_STRING_WRITE_CYCLES
+        + alignment
+}
+
+fn bit_string_start_cycles(starting: bool) -> u32 {
+    if starting {
+        BIT_STRING_START_CYCLES
+    } else {
+        0
+    }
+}
+
+fn float_add_cycles(lhs: f32, rhs: f32, result: f32, min: u32, max: u32) -> u32 {
+    if lhs == 0.0 || rhs == 0.0 {
+        // No alignment or normalization is necessary
+        return min;
+    }
+
+    let lhs_exponent = exponent(lhs);
+    let rhs_exponent = exponent(rhs);
+
+    // Shifting the smaller mantissa right to match the larger exponent
+    let alignment = (lhs_exponent - rhs_exponent)
+        .unsigned_abs()
+        .min(MANTISSA_BITS);
+
+    // Shifting the result mantissa back into normalized form. Cancellation of nearly equal values requires the
+    // largest shifts
+    let normalization = if result == 0.0 {
+        MANTISSA_BITS
+    } else {
+        (lhs_exponent.max(rhs_exponent) - exponent(result))
+            .unsigned_abs()
+            .min(MANTISSA_BITS)
+    };
+
+    interpolate(min, max, alignment + normalization, 2 * MANTISSA_BITS)
+}
+
+/// Scales `steps` of shifting, out of the most possible, `max_steps`, onto the range of cycles given by the manual
+fn interpolate(min: u32, max: u32, steps: u32, max_steps: u32) -> u32 {
+    min + (max - min) * steps.min(max_steps) / max_steps
+}
+
+/// The biased exponent of a float
+fn exponent(value: f32) -> i32 {
+    ((value.to_bits() >> 23) & 0xFF) as i32
+}
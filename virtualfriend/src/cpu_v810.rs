@@ -1,8 +1,20 @@
 use bitvec::array::BitArray;
 use bitvec::prelude::Lsb0;
+use savefile::Removed;
 
 use crate::{
-    bus::Bus, cpu_internals::ProgramStatusWord, interrupt::InterruptRequest, util::sign_extend,
+    bus::Bus,
+    config::IllegalOpcodeBehavior,
+    cpu_float::{self, FloatExceptions, FloatResult},
+    cpu_internals::{ProgramStatusWord, Registers, RoundingMode, TaskControlWord},
+    cpu_timing,
+    crash::{CrashCause, PcHistory},
+    decode_cache::{DecodeCache, DecodedInstruction, InstructionHandler},
+    instruction::{Opcode, SubOp},
+    instruction_cache::InstructionCache,
+    interrupt::InterruptRequest,
+    util::sign_extend,
+    wait_control::AccessWidth,
 };
 
 /// Tracks the most recent activity of the bus for the purposes of timing
@@ -86,18 +98,27 @@ pub struct CpuV810 {
     /// ID 7: Task Control Word
     ///
     /// Specifies the behavior of floating-point instructions.
-    // TODO: Add proper exception for float operations
-    tkcw: u32,
+    #[savefile_versions_as = "0..1:tkcw_from_u32:u32"]
+    #[savefile_versions = "2.."]
+    #[savefile_default_fn = "new_tkcw"]
+    tkcw: TaskControlWord,
+
+    /// Whether the instruction cache was enabled, before the cache itself was saved
+    #[savefile_versions = "0..0"]
+    cache_enabled: Removed<bool>,
 
     /// ID 24: Cache Control Word
     ///
-    /// Configures the instruction cache.
-    // chcw: u32,
-    cache_enabled: bool,
+    /// Configures the instruction cache. The cache itself holds the readable state of this register. Savestates
+    /// before version 1 load with the cache disabled and empty, until the game next writes CHCW
+    #[savefile_versions = "1.."]
+    #[savefile_default_fn = "new_instruction_cache"]
+    instruction_cache: InstructionCache,
 
     /// ID 25: Address Trap Register for Execution
     ///
-    /// Configures the execution address for the hardware breakpoint.
+    /// Configures the execution address for the hardware breakpoint. When PSW.AE is set, the address trap exception
+    /// (0xFFC0) is raised before executing the instruction at this address.
     adtre: u32,
 
     /// ID 29: Unknown register
@@ -112,6 +133,27 @@ pub struct CpuV810 {
     last_bus_activity: BusActivity,
 
     processing_bitstring: bool,
+
+    #[savefile_ignore]
+    #[savefile_introspect_ignore]
+    #[savefile_default_fn = "new_decode_cache"]
+    decode_cache: DecodeCache,
+
+    /// Set when the CPU has stopped due to a fatal error. Execution does not continue
+    #[savefile_ignore]
+    #[savefile_introspect_ignore]
+    #[savefile_default_fn = "no_crash"]
+    crash: Option<CrashCause>,
+
+    #[savefile_ignore]
+    #[savefile_introspect_ignore]
+    #[savefile_default_fn = "new_pc_history"]
+    pc_history: PcHistory,
+
+    #[savefile_ignore]
+    #[savefile_introspect_ignore]
+    #[savefile_default_fn = "default_illegal_opcode_behavior"]
+    illegal_opcode_behavior: IllegalOpcodeBehavior,
 }
 
 impl CpuV810 {
@@ -130,8 +172,9 @@ impl CpuV810 {
             fepsw: 0,
             ecr: 0xFFF0,
             psw,
-            tkcw: 0,
-            cache_enabled: false,
+            tkcw: TaskControlWord::new(),
+            cache_enabled: Removed::new(),
+            instruction_cache: InstructionCache::new(),
             adtre: 0,
             unknown_29: 0,
             unknown_30: 0,
@@ -140,110 +183,154 @@ impl CpuV810 {
             last_bus_activity: BusActivity::Standard,
 
             processing_bitstring: false,
+
+            decode_cache: DecodeCache::new(),
+
+            crash: None,
+            pc_history: PcHistory::new(),
+
+            illegal_opcode_behavior: IllegalOpcodeBehavior::default(),
+        }
+    }
+
+    /// Sets the registers that are undefined at power on to the values Mednafen uses
+    pub fn mednafen_power_on(&mut self) {
+        self.general_purpose_reg[1..].fill(0xDEAD_BEEF);
+        self.eipc = 0xDEAD_BEEE;
+        self.eipsw = 0xDB2EF;
+        self.fepc = 0xDEAD_BEEE;
+        self.fepsw = 0xDB2EF;
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            pc: self.pc,
+            general_purpose: self.general_purpose_reg,
+            eipc: self.eipc,
+            eipsw: self.eipsw,
+            fepc: self.fepc,
+            fepsw: self.fepsw,
+            ecr: self.ecr,
+            psw: self.psw.get(),
+            tkcw: self.tkcw.get(),
+            chcw: self.instruction_cache.chcw(),
+            adtre: self.adtre,
+        }
+    }
+
+    /// Overwrites the architectural registers. Used by debuggers
+    pub fn set_registers(&mut self, registers: &Registers) {
+        self.pc = registers.pc & 0xFFFF_FFFE;
+        self.general_purpose_reg = registers.general_purpose;
+        // r0 is hardwired to 0
+        self.general_purpose_reg[0] = 0;
+        self.eipc = registers.eipc;
+        self.eipsw = registers.eipsw;
+        self.fepc = registers.fepc;
+        self.fepsw = registers.fepsw;
+        self.ecr = registers.ecr;
+        self.psw.set(registers.psw);
+        self.tkcw.set(registers.tkcw);
+        self.instruction_cache
+            .set_enabled(registers.chcw & 0x2 != 0);
+        self.adtre = registers.adtre;
+    }
+
+    /// Whether the next step will execute a new instruction, rather than idling in HALT or continuing a
+    /// partially completed bit string operation
+    pub fn at_instruction_boundary(&self) -> bool {
+        !self.is_halted && !self.processing_bitstring && self.crash.is_none()
+    }
+
+    pub fn pc(&self) -> u32 {
+        self.pc
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.is_halted
+    }
+
+    pub fn is_processing_bitstring(&self) -> bool {
+        self.processing_bitstring
+    }
+
+    pub fn crash_cause(&self) -> Option<CrashCause> {
+        self.crash
+    }
+
+    /// Stops execution due to a fatal error. Only the first cause is retained
+    pub fn crash(&mut self, cause: CrashCause) {
+        if self.crash.is_none() {
+            self.crash = Some(cause);
         }
     }
 
-    /// TODO: This is debug init to match with Mednafen
-    // pub fn debug_init(&mut self) {
-    //     // self.tkcw = 0xE0;
-
-    //     for i in 1..32 {
-    //         self.general_purpose_reg[i] = 0xDEADBEEF;
-    //     }
-    //     self.eipc = 0xDEADBEEE;
-    //     self.eipsw = 0xDB2EF;
-    //     self.fepc = 0xDEADBEEE;
-    //     self.fepsw = 0xDB2EF;
-    // }
-
-    // pub fn log_instruction(
-    //     &self,
-    //     log_file: Option<&mut BufWriter<File>>,
-    //     _cycle_count: usize,
-    //     _extra_log_info: Option<String>,
-    // ) {
-    //     // let mut tuples = vec![
-    //     //     ("PC".to_string(), self.pc),
-    //     //     ("R1".to_string(), self.general_purpose_reg[1]),
-    //     //     ("FP".to_string(), self.general_purpose_reg[2]),
-    //     //     ("SP".to_string(), self.general_purpose_reg[3]),
-    //     //     ("GP".to_string(), self.general_purpose_reg[4]),
-    //     //     ("TP".to_string(), self.general_purpose_reg[5]),
-    //     // ];
-
-    //     // for i in 6..31 {
-    //     //     tuples.push((format!("R{i}"), self.general_purpose_reg[i]));
-    //     // }
-
-    //     // let mut after_tuples = vec![
-    //     //     ("LP".to_string(), self.general_purpose_reg[31]),
-    //     //     ("EIPC".to_string(), self.eipc),
-    //     //     ("EIPSW".to_string(), self.eipsw),
-    //     //     ("FEPC".to_string(), self.fepc),
-    //     //     ("FEPSW".to_string(), self.fepsw),
-    //     //     ("ECR".to_string(), self.ecr),
-    //     //     ("PSW".to_string(), self.psw.get()),
-    //     //     ("PIR".to_string(), self.pir),
-    //     //     ("TKCW".to_string(), self.tkcw),
-    //     //     ("CHCW".to_string(), self.chcw),
-    //     //     ("ADTRE".to_string(), self.adtre),
-    //     // ];
-
-    //     // tuples.append(&mut after_tuples);
-
-    //     // let mut string = String::new();
-    //     // let mut first = true;
-
-    //     // for (name, value) in tuples {
-    //     //     if !first {
-    //     //         string += " ";
-    //     //     }
-
-    //     //     string += &format!("{name}={value:08X}");
-
-    //     //     first = false;
-    //     // }
-
-    //     // if let Some(extra_log_info) = extra_log_info {
-    //     //     string += &format!(" {extra_log_info}");
-    //     // }
-
-    //     // TODO: Mednafen seems to wrap cycle count at the arbitrary? value 0x061200
-    //     // let cycle_count = cycle_count % 0x061200;
-    //     // string += &format!(" TStamp={cycle_count:06X}");
-
-    //     if self.processing_bitstring {
-    //         // Ignore step
-    //         return;
-    //     }
-
-    //     let chcw = if self.cache_enabled { 2 } else { 0 };
-
-    //     if let Some(log_file) = log_file {
-    //         // writeln!(log_file, "PC={:08X}", self.pc).unwrap();
-    //         writeln!(log_file, "PC={:08X} R1={:08X} FP={:08X} SP={:08X} GP={:08X} TP={:08X} R6={:08X} R7={:08X} R8={:08X} R9={:08X} R10={:08X} R11={:08X} R12={:08X} R13={:08X} R14={:08X} R15={:08X} R16={:08X} R17={:08X} R18={:08X} R19={:08X} R20={:08X} R21={:08X} R22={:08X} R23={:08X} R24={:08X} R25={:08X} R26={:08X} R27={:08X} R28={:08X} R29={:08X} R30={:08X} LP={:08X} EIPC={:08X} EIPSW={:08X} FEPC={:08X} FEPSW={:08X} ECR={:08X} PSW={:08X} PIR=00005347 TKCW={:08X} CHCW={:08X} ADTRE={:08X}",
-    //         self.pc, self.general_purpose_reg[1], self.general_purpose_reg[2], self.general_purpose_reg[3], self.general_purpose_reg[4], self.general_purpose_reg[5], self.general_purpose_reg[6], self.general_purpose_reg[7], self.general_purpose_reg[8], self.general_purpose_reg[9], self.general_purpose_reg[10], self.general_purpose_reg[11], self.general_purpose_reg[12], self.general_purpose_reg[13], self.general_purpose_reg[14], self.general_purpose_reg[15], self.general_purpose_reg[16], self.general_purpose_reg[17], self.general_purpose_reg[18], self.general_purpose_reg[19], self.general_purpose_reg[20], self.general_purpose_reg[21], self.general_purpose_reg[22], self.general_purpose_reg[23], self.general_purpose_reg[24], self.general_purpose_reg[25], self.general_purpose_reg[26], self.general_purpose_reg[27], self.general_purpose_reg[28], self.general_purpose_reg[29], self.general_purpose_reg[30], self.general_purpose_reg[31], self.eipc, self.eipsw, self.fepc, self.fepsw, self.ecr, self.psw.get(), self.tkcw, chcw, self.adtre).unwrap();
-    //     }
-    //     // std_lock.write_fmt(format_args!("PC={:08X} R1={:08X} FP={:08X} SP={:08X} GP={:08X} TP={:08X} R6={:08X} R7={:08X} R8={:08X} R9={:08X} R10={:08X} R11={:08X} R12={:08X} R13={:08X} R14={:08X} R15={:08X} R16={:08X} R17={:08X} R18={:08X} R19={:08X} R20={:08X} R21={:08X} R22={:08X} R23={:08X} R24={:08X} R25={:08X} R26={:08X} R27={:08X} R28={:08X} R29={:08X} R30={:08X} LP={:08X} EIPC={:08X} EIPSW={:08X} FEPC={:08X} FEPSW={:08X} ECR={:08X} PSW={:08X} PIR=00005347 TKCW={:08X} CHCW={:08X} ADTRE={:08X}\n",
-    //     // self.pc, self.general_purpose_reg[1], self.general_purpose_reg[2], self.general_purpose_reg[3], self.general_purpose_reg[4], self.general_purpose_reg[5], self.general_purpose_reg[6], self.general_purpose_reg[7], self.general_purpose_reg[8], self.general_purpose_reg[9], self.general_purpose_reg[10], self.general_purpose_reg[11], self.general_purpose_reg[12], self.general_purpose_reg[13], self.general_purpose_reg[14], self.general_purpose_reg[15], self.general_purpose_reg[16], self.general_purpose_reg[17], self.general_purpose_reg[18], self.general_purpose_reg[19], self.general_purpose_reg[20], self.general_purpose_reg[21], self.general_purpose_reg[22], self.general_purpose_reg[23], self.general_purpose_reg[24], self.general_purpose_reg[25], self.general_purpose_reg[26], self.general_purpose_reg[27], self.general_purpose_reg[28], self.general_purpose_reg[29], self.general_purpose_reg[30], self.general_purpose_reg[31], self.eipc, self.eipsw, self.fepc, self.fepsw, self.ecr, self.psw.get(), self.tkcw, chcw, self.adtre));
-    // }
+    pub fn pc_history(&self) -> &PcHistory {
+        &self.pc_history
+    }
+
+    /// Records the PCs of loop iterations that were skipped rather than executed, so the history matches
+    pub(crate) fn record_skipped_iterations(&mut self, pcs: &[u32], iterations: usize) {
+        self.pc_history.push_repeated(pcs, iterations);
+    }
+
+    pub fn set_illegal_opcode_behavior(&mut self, behavior: IllegalOpcodeBehavior) {
+        self.illegal_opcode_behavior = behavior;
+    }
 
     /// Step one CPU instruction
     ///
     /// Returns the number of cycles consumed
     pub fn step(&mut self, bus: &mut Bus) -> usize {
-        if self.is_halted {
+        if self.is_halted || self.crash.is_some() {
             // Do nothing. 1 cycle consumed
             return 1;
         }
 
-        let instruction = self.fetch_instruction_word(bus);
+        // A bit string instruction resuming after an interrupt is not fetched again
+        let resuming_bitstring = self.processing_bitstring;
+
+        if !resuming_bitstring {
+            self.pc_history.push(self.pc);
+        }
+
+        if self.address_trap_hit() {
+            // Address trap. The instruction at ADTRE is not executed, and EIPC points to it, so the handler must clear
+            // AE in EIPSW or move ADTRE before returning
+            self.perform_exception(0xFFC0);
+
+            return 15;
+        }
+
+        let pc = self.pc;
+
+        let (decoded, fetch_cycles) = if self.instruction_cache.holds_restored_data() {
+            let (decoded, miss_cycles) = self.decode_through_instruction_cache(bus);
+
+            (decoded, miss_cycles as i32)
+        } else {
+            let decoded = self.decode(bus);
+
+            let fetch_cycles = if resuming_bitstring {
+                0
+            } else if self.instruction_cache.is_enabled() {
+                self.instruction_cache_fetch_cycles(bus, pc, decoded.size) as i32
+            } else {
+                Self::fetch_wait_delta(bus, pc, decoded.size)
+            };
+
+            (decoded, fetch_cycles)
+        };
+
+        // Increment PC past the full instruction before executing it
+        self.pc = self.pc.wrapping_add(decoded.size);
 
-        let (cycles, bus_activity) = self.perform_instruction(bus, instruction);
+        let (cycles, bus_activity) = (decoded.handler)(self, bus, &decoded);
 
         self.last_bus_activity = bus_activity;
 
-        cycles as usize
+        // Fetches with fewer wait states than at power on can only shorten an instruction so far
+        (cycles as i32 + fetch_cycles).max(1) as usize
     }
 
     /// Performs the necessary operations to jump to an interrupt, if valid
@@ -254,24 +341,13 @@ impl CpuV810 {
             return;
         }
 
-        // Second nibble is the same as interrupt level
-        let interrupt_level = ((request.code() >> 4) & 0xF) as u8;
+        let interrupt_level = request.level();
 
         if interrupt_level < self.psw.interrupt_level {
             // Level not high enough to perform interrupt. Skip
             return;
         }
 
-        if self.psw.nmi_pending {
-            // Fatal exception. Terminating program and halting
-            panic!(
-                "Fatal exception. Code: {:04X}, PSW: {:08X}, PC: {:08X}",
-                request.code(),
-                self.psw.get(),
-                self.pc
-            )
-        }
-
         self.perform_exception(request.code());
 
         if self.psw.interrupt_level < 15 {
@@ -281,7 +357,21 @@ impl CpuV810 {
         }
     }
 
+    /// Whether the hardware breakpoint configured by ADTRE should fire before executing the instruction at PC
+    #[inline(always)]
+    fn address_trap_hit(&self) -> bool {
+        // A bit string instruction resuming after an interrupt has already passed the trap check
+        self.psw.address_trap_enable && self.pc == self.adtre && !self.processing_bitstring
+    }
+
     fn perform_exception(&mut self, code: usize) {
+        if self.psw.nmi_pending {
+            // Fatal exception. Terminating program and halting
+            self.crash(CrashCause::FatalException { code: code as u16 });
+
+            return;
+        }
+
         if self.psw.exception_pending {
             // Duplexed exception
             // Set duplex code
@@ -302,7 +392,7 @@ impl CpuV810 {
             // Backup PC
             self.eipc = self.pc;
 
-            self.pc = 0xFFFF_0000 | code as u32;
+            self.pc = exception_handler_address(code);
         }
 
         self.psw.exception_pending = true;
@@ -315,100 +405,268 @@ impl CpuV810 {
         self.is_halted = false;
     }
 
-    fn perform_instruction(&mut self, bus: &mut Bus, instruction: u16) -> (u32, BusActivity) {
-        let opcode = instruction >> 10;
+    /// Decodes the instruction at PC, using the decode cache for ROM addresses
+    #[inline(always)]
+    fn decode(&mut self, bus: &mut Bus) -> DecodedInstruction {
+        let pc = self.pc;
+
+        if pc & 0x0700_0000 != 0x0700_0000 {
+            // Not in ROM. Code in RAM can be modified, so it is never cached
+            return Self::decode_instruction(pc, |address| {
+                Self::fetch_instruction_word(bus, address)
+            });
+        }
+
+        if bus.take_rom_changed() {
+            self.decode_cache.clear();
+        }
+
+        if let Some(decoded) = self.decode_cache.get(pc) {
+            return decoded;
+        }
+
+        let decoded =
+            Self::decode_instruction(pc, |address| Self::fetch_instruction_word(bus, address));
+        self.decode_cache.insert(pc, decoded);
+
+        decoded
+    }
+
+    /// Decodes the instruction at PC from the contents of the instruction cache, filling it on a miss.
+    ///
+    /// Only used while the cache holds restored contents, which need not match memory, so the decode cache is bypassed.
+    ///
+    /// Returns the decoded instruction and the cycles spent on cache misses
+    fn decode_through_instruction_cache(&mut self, bus: &mut Bus) -> (DecodedInstruction, u32) {
+        let instruction_cache = &mut self.instruction_cache;
+        let mut miss_cycles = 0;
+
+        let decoded = Self::decode_instruction(self.pc, |address| {
+            let (halfword, cycles) = instruction_cache.fetch(address, |word_address| {
+                Self::fill_cache_word(bus, word_address)
+            });
+
+            miss_cycles += cycles;
+
+            halfword
+        });
+
+        (decoded, miss_cycles)
+    }
+
+    /// Runs the fetches of an instruction of `size` bytes at `pc` through the instruction cache, for their timing and
+    /// to fill the cache. The instruction itself is decoded from memory, which the cache matches.
+    ///
+    /// Returns the cycles spent on cache misses
+    fn instruction_cache_fetch_cycles(&mut self, bus: &mut Bus, pc: u32, size: u32) -> u32 {
+        let mut cycles = 0;
+
+        for address in (0..size).step_by(2).map(|offset| pc.wrapping_add(offset)) {
+            cycles += self
+                .instruction_cache
+                .fetch(address, |word_address| {
+                    Self::fill_cache_word(bus, word_address)
+                })
+                .1;
+        }
+
+        cycles
+    }
+
+    /// Reads a word of instructions into the cache. Returns the word and the difference in its wait cycles from the
+    /// power on setting
+    fn fill_cache_word(bus: &mut Bus, address: u32) -> (u32, i32) {
+        let lower = Self::fetch_instruction_word(bus, address) as u32;
+        let upper = Self::fetch_instruction_word(bus, address.wrapping_add(2)) as u32;
+
+        (
+            (upper << 16) | lower,
+            bus.fetch_wait_delta(address, AccessWidth::Word),
+        )
+    }
+
+    fn decode_instruction(pc: u32, mut fetch: impl FnMut(u32) -> u16) -> DecodedInstruction {
+        let instruction = fetch(pc);
+
+        // Decoded in the same way as the disassembler, so the two agree on every opcode and its size
+        let opcode = Opcode::from_halfword(instruction);
+
+        let has_second_halfword =
+            opcode.is_some_and(|opcode| opcode.instruction_format().has_second_halfword());
+
+        let (second_halfword, size) = if has_second_halfword {
+            (fetch(pc.wrapping_add(2)), 4)
+        } else {
+            (0, 2)
+        };
+
+        DecodedInstruction::new(
+            Self::instruction_handler(opcode),
+            instruction,
+            second_halfword,
+            size,
+        )
+    }
+
+    fn instruction_handler(opcode: Option<Opcode>) -> InstructionHandler {
+        let Some(opcode) = opcode else {
+            return |cpu, _, decoded| cpu.invalid_opcode(decoded.instruction, 2);
+        };
 
         match opcode {
             // Register transfer
-            0b01_0000 => self.mov(instruction, true),
-            0b00_0000 => self.mov(instruction, false),
-            0b10_1000 => self.movea(instruction, bus),
-            0b10_1111 => self.movhi(instruction, bus),
+            Opcode::MovImm => |cpu, _, decoded| cpu.mov(decoded.reg1(), decoded.reg2(), true),
+            Opcode::MovReg => |cpu, _, decoded| cpu.mov(decoded.reg1(), decoded.reg2(), false),
+            Opcode::Movea => {
+                |cpu, _, decoded| cpu.movea(decoded.reg1(), decoded.reg2(), decoded.second_halfword)
+            }
+            Opcode::Movhi => {
+                |cpu, _, decoded| cpu.movhi(decoded.reg1(), decoded.reg2(), decoded.second_halfword)
+            }
 
             // Load and Input
-            0b11_1000 => self.load_inst_16(bus, instruction, 0xFFFF_FFFF, 0xFF, 0), // IN.B Input single byte
-            0b11_1001 => self.load_inst_16(bus, instruction, 0xFFFF_FFFE, 0xFFFF, 0), // IN.H Input 16 bit word
+            // IN.B Input single byte
+            Opcode::Inb => |cpu, bus, decoded| cpu.load_inst_16(bus, decoded, 0xFFFF_FFFF, 0xFF, 0),
+            // IN.H Input 16 bit word
+            Opcode::Inh => {
+                |cpu, bus, decoded| cpu.load_inst_16(bus, decoded, 0xFFFF_FFFE, 0xFFFF, 0)
+            }
 
-            0b11_1011 | 0b11_0011 => self.ld_w(instruction, bus),
-            0b11_0000 => self.load_inst_16(bus, instruction, 0xFFFF_FFFF, 0xFF, 8), // LD.B Load single byte (sign extend)
-            0b11_0001 => self.load_inst_16(bus, instruction, 0xFFFF_FFFE, 0xFFFF, 16), // LD.H Load 16 bit word (sign extend)
+            Opcode::Inw | Opcode::Ldw => |cpu, bus, decoded| {
+                cpu.ld_w(decoded.reg1(), decoded.reg2(), decoded.second_halfword, bus)
+            },
+            // LD.B Load single byte (sign extend)
+            Opcode::Ldb => |cpu, bus, decoded| cpu.load_inst_16(bus, decoded, 0xFFFF_FFFF, 0xFF, 8),
+            // LD.H Load 16 bit word (sign extend)
+            Opcode::Ldh => {
+                |cpu, bus, decoded| cpu.load_inst_16(bus, decoded, 0xFFFF_FFFE, 0xFFFF, 16)
+            }
 
             // Store and Output
-            0b11_1100 | 0b11_0100 => self.st_b(instruction, bus),
-            0b11_1101 | 0b11_0101 => self.st_h(instruction, bus),
-            0b11_1111 | 0b11_0111 => self.st_w(instruction, bus),
+            Opcode::Outb | Opcode::Stb => |cpu, bus, decoded| {
+                cpu.st_b(decoded.reg1(), decoded.reg2(), decoded.second_halfword, bus)
+            },
+            Opcode::Outh | Opcode::Sth => |cpu, bus, decoded| {
+                cpu.st_h(decoded.reg1(), decoded.reg2(), decoded.second_halfword, bus)
+            },
+            Opcode::Outw | Opcode::Stw => |cpu, bus, decoded| {
+                cpu.st_w(decoded.reg1(), decoded.reg2(), decoded.second_halfword, bus)
+            },
 
             // Arithmetic
-            0b01_0001 => self.add(instruction, true), // ADD immediate
-            0b00_0001 => self.add(instruction, false), // ADD reg
-            0b10_1001 => self.add_16_bit(instruction, bus),
-            0b01_0011 => self.cmp(instruction, true),
-            0b00_0011 => self.cmp(instruction, false),
-            0b00_1001 => self.div(instruction, true),
-            0b00_1011 => self.div(instruction, false),
-            0b00_1000 => self.mul_signed(instruction),
-            0b00_1010 => self.mul_unsigned(instruction),
-            0b00_0010 => self.sub(instruction),
+            Opcode::AddImm5 => |cpu, _, decoded| cpu.add(decoded.reg1(), decoded.reg2(), true), // ADD immediate
+            Opcode::AddReg => |cpu, _, decoded| cpu.add(decoded.reg1(), decoded.reg2(), false), // ADD reg
+            Opcode::AddImm16 => |cpu, _, decoded| {
+                cpu.add_16_bit(decoded.reg1(), decoded.reg2(), decoded.second_halfword)
+            },
+            Opcode::CmpImm => |cpu, _, decoded| cpu.cmp(decoded.reg1(), decoded.reg2(), true),
+            Opcode::CmpReg => |cpu, _, decoded| cpu.cmp(decoded.reg1(), decoded.reg2(), false),
+            Opcode::Div => |cpu, _, decoded| cpu.div(decoded.reg1(), decoded.reg2(), true),
+            Opcode::DivU => |cpu, _, decoded| cpu.div(decoded.reg1(), decoded.reg2(), false),
+            Opcode::Mul => |cpu, _, decoded| cpu.mul_signed(decoded.reg1(), decoded.reg2()),
+            Opcode::MulU => |cpu, _, decoded| cpu.mul_unsigned(decoded.reg1(), decoded.reg2()),
+            Opcode::Sub => |cpu, _, decoded| cpu.sub(decoded.reg1(), decoded.reg2()),
 
             // Bitwise
-            0b00_1101 => self.and(instruction),
-            0b10_1101 => self.andi(instruction, bus),
-            0b00_1111 => self.not(instruction),
-            0b00_1100 => self.or(instruction),
-            0b10_1100 => self.ori(instruction, bus),
-            0b01_0111 => self.sar(instruction, true), // SAR Shift arthmetic right by immediate
-            0b00_0111 => self.sar(instruction, false), // SAR Shift arthmetic right by register
-            0b01_0100 => self.shl(instruction, true), // SHL Shift logical left by immediate
-            0b00_0100 => self.shl(instruction, false), // SHL Shift logical left by register
-            0b01_0101 => self.shr(instruction, true), // SHR Shift logical right by immediate
-            0b00_0101 => self.shr(instruction, false), // SHR Shift logical right by register
-            0b00_1110 => self.xor(instruction, false, bus), // XOR register
-            0b10_1110 => self.xor(instruction, true, bus), // XOR immediate, zero extend
+            Opcode::And => |cpu, _, decoded| cpu.and(decoded.reg1(), decoded.reg2()),
+            Opcode::AndI => {
+                |cpu, _, decoded| cpu.andi(decoded.reg1(), decoded.reg2(), decoded.second_halfword)
+            }
+            Opcode::Not => |cpu, _, decoded| cpu.not(decoded.reg1(), decoded.reg2()),
+            Opcode::Or => |cpu, _, decoded| cpu.or(decoded.reg1(), decoded.reg2()),
+            Opcode::OrI => {
+                |cpu, _, decoded| cpu.ori(decoded.reg1(), decoded.reg2(), decoded.second_halfword)
+            }
+            Opcode::SarImm => |cpu, _, decoded| cpu.sar(decoded.reg1(), decoded.reg2(), true), // SAR Shift arthmetic right by immediate
+            Opcode::SarReg => |cpu, _, decoded| cpu.sar(decoded.reg1(), decoded.reg2(), false), // SAR Shift arthmetic right by register
+            Opcode::ShlImm => |cpu, _, decoded| cpu.shl(decoded.reg1(), decoded.reg2(), true), // SHL Shift logical left by immediate
+            Opcode::ShlReg => |cpu, _, decoded| cpu.shl(decoded.reg1(), decoded.reg2(), false), // SHL Shift logical left by register
+            Opcode::ShrImm => |cpu, _, decoded| cpu.shr(decoded.reg1(), decoded.reg2(), true), // SHR Shift logical right by immediate
+            Opcode::ShrReg => |cpu, _, decoded| cpu.shr(decoded.reg1(), decoded.reg2(), false), // SHR Shift logical right by register
+            Opcode::Xor => |cpu, _, decoded| cpu.xor(decoded.reg1(), decoded.reg2(), None), // XOR register
+            Opcode::XorI => |cpu, _, decoded| {
+                cpu.xor(
+                    decoded.reg1(),
+                    decoded.reg2(),
+                    Some(decoded.second_halfword),
+                )
+            }, // XOR immediate, zero extend
 
             // CPU Control
-            // 0b10_0 + 4 bits
-            // Opcode is 6 bits, whereas this needs 7, so we just grab the relevant opcode
-            // ranges, then extract the condition from the instruction inside
-            0b10_0000..=0b10_0111 => self.bcond(instruction),
-            0b01_1010 => self.halt(),
-            0b10_1011 => self.displaced_jump(bus, instruction, true), // JAL Jump and link
-            0b00_0110 => self.jmp(instruction),
-            0b10_1010 => self.displaced_jump(bus, instruction, false), // JR Jump relative
-            0b01_1100 => self.ldsr(instruction), // LDSR Load to system register
-            0b01_1001 => self.reti(),            // RETI Return from trap or interrupt
-            0b01_1101 => self.stsr(instruction), // STSR Store contents of system register
-            0b01_1000 => self.trap(instruction), // TRAP Raise exception
-
-            0b11_1110 => self.float_inst(instruction, bus), // Floating point operations and Nintendo
-
-            0b01_1111 => self.bit_string_inst(instruction, bus), // Bit string operations
+            // The condition is extracted from the instruction inside
+            Opcode::Bv
+            | Opcode::Bc
+            | Opcode::Bz
+            | Opcode::Bnh
+            | Opcode::Bn
+            | Opcode::Br
+            | Opcode::Blt
+            | Opcode::Ble
+            | Opcode::Bnv
+            | Opcode::Bnc
+            | Opcode::Bnz
+            | Opcode::Bh
+            | Opcode::Bp
+            | Opcode::Nop
+            | Opcode::Bge
+            | Opcode::Bgt => |cpu, _, decoded| cpu.bcond(decoded.instruction),
+            Opcode::Halt => |cpu, _, _| cpu.halt(),
+            Opcode::Jal => |cpu, _, decoded| {
+                cpu.displaced_jump(decoded.instruction, decoded.second_halfword, true)
+            }, // JAL Jump and link
+            Opcode::Jmp => |cpu, _, decoded| cpu.jmp(decoded.reg1()),
+            Opcode::Jr => |cpu, _, decoded| {
+                cpu.displaced_jump(decoded.instruction, decoded.second_halfword, false)
+            }, // JR Jump relative
+            Opcode::Ldsr => |cpu, bus, decoded| cpu.ldsr(decoded.reg1(), decoded.reg2(), bus), // LDSR Load to system register
+            Opcode::Reti => |cpu, _, _| cpu.reti(), // RETI Return from trap or interrupt
+            Opcode::Stsr => |cpu, _, decoded| cpu.stsr(decoded.reg1(), decoded.reg2()), // STSR Store contents of system register
+            Opcode::Trap => |cpu, _, decoded| cpu.trap(decoded.reg1()), // TRAP Raise exception
+
+            // Floating point operations and Nintendo
+            Opcode::Extended => |cpu, _, decoded| {
+                cpu.float_inst(
+                    decoded.instruction,
+                    decoded.reg1(),
+                    decoded.reg2(),
+                    decoded.second_halfword,
+                )
+            },
+
+            Opcode::BitString => |cpu, bus, decoded| cpu.bit_string_inst(decoded.instruction, bus), // Bit string operations
 
             // Miscellaneous
-            0b11_1010 => self.caxi(), // CAXI Compare and exchange interlocked
-            0b01_0010 => self.setf(instruction),
+            Opcode::Caxi => |cpu, bus, decoded| {
+                cpu.caxi(decoded.reg1(), decoded.reg2(), decoded.second_halfword, bus)
+            }, // CAXI Compare and exchange interlocked
+            Opcode::Setf => |cpu, _, decoded| cpu.setf(decoded.reg1(), decoded.reg2()),
 
             // Nintendo
-            0b01_0110 => self.cli(), // CLI Clear interrupt disable flag
-            0b01_1110 => self.sei(), // SEI Set interrupt disable flag
-            _ => {
-                #[cfg(feature = "panic")]
-                panic!("Invalid opcode {opcode:x}");
-
-                #[cfg(not(feature = "panic"))]
-                (1, BusActivity::Standard)
-            }
+            Opcode::Cli => |cpu, _, _| cpu.cli(), // CLI Clear interrupt disable flag
+            Opcode::Sei => |cpu, _, _| cpu.sei(), // SEI Set interrupt disable flag
         }
     }
 
-    fn fetch_instruction_word(&mut self, bus: &mut Bus) -> u16 {
-        // let instruction = bus.get_u16(self.pc);
-        // Hack for speed. This will break if there are instructions fetched from outside of ROM
-        let instruction = bus.get_rom(self.pc >> 1);
+    /// Cycles added to fetching an instruction of `size` bytes from memory without the cache, relative to the power
+    /// on wait states. One transfer per halfword
+    #[inline(always)]
+    fn fetch_wait_delta(bus: &Bus, pc: u32, size: u32) -> i32 {
+        let width = if size == 4 {
+            AccessWidth::Word
+        } else {
+            AccessWidth::Halfword
+        };
 
-        // Increment PC by 2 bytes
-        self.pc = self.pc.wrapping_add(2);
+        bus.fetch_wait_delta(pc, width)
+    }
 
-        instruction
+    fn fetch_instruction_word(bus: &mut Bus, address: u32) -> u16 {
+        if address & 0x0700_0000 == 0x0700_0000 {
+            // Fast path for the common case of executing from ROM
+            bus.get_rom((address >> 1) & 0x7F_FFFF)
+        } else {
+            bus.fetch_u16(address)
+        }
     }
 
     fn set_gen_purpose_reg(&mut self, index: usize, value: u32) {
@@ -423,9 +681,12 @@ impl CpuV810 {
 
     // Instructions
 
-    fn mov(&mut self, instruction: u16, use_immediate: bool) -> (u32, BusActivity) {
-        let (reg1_index_or_immediate, reg2_index) = extract_reg1_2_index(instruction);
-
+    fn mov(
+        &mut self,
+        reg1_index_or_immediate: usize,
+        reg2_index: usize,
+        use_immediate: bool,
+    ) -> (u32, BusActivity) {
         let value = if use_immediate {
             sign_extend(reg1_index_or_immediate as u32, 5)
         } else {
@@ -437,14 +698,16 @@ impl CpuV810 {
         (1, BusActivity::Standard)
     }
 
-    fn movea(&mut self, instruction: u16, bus: &mut Bus) -> (u32, BusActivity) {
+    fn movea(
+        &mut self,
+        reg1_index: usize,
+        reg2_index: usize,
+        immediate: u16,
+    ) -> (u32, BusActivity) {
         // MOVEA Add
         // Don't modify flags
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
         let reg1 = self.general_purpose_reg[reg1_index];
 
-        let immediate = self.fetch_instruction_word(bus);
         let immediate = (immediate as i16) as u32;
 
         let result = reg1.wrapping_add(immediate);
@@ -454,15 +717,16 @@ impl CpuV810 {
         (1, BusActivity::Standard)
     }
 
-    fn movhi(&mut self, instruction: u16, bus: &mut Bus) -> (u32, BusActivity) {
+    fn movhi(
+        &mut self,
+        reg1_index: usize,
+        reg2_index: usize,
+        immediate: u16,
+    ) -> (u32, BusActivity) {
         // MOVHI Add upper immediate
         // Don't modify flags
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
         let reg1 = self.general_purpose_reg[reg1_index];
 
-        let immediate = self.fetch_instruction_word(bus);
-
         let result = reg1.wrapping_add((immediate as u32) << 16);
 
         self.set_gen_purpose_reg(reg2_index, result);
@@ -470,10 +734,13 @@ impl CpuV810 {
         (1, BusActivity::Standard)
     }
 
-    fn ld_w(&mut self, instruction: u16, bus: &mut Bus) -> (u32, BusActivity) {
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
-        let disp = self.fetch_instruction_word(bus) as u32;
+    fn ld_w(
+        &mut self,
+        reg1_index: usize,
+        reg2_index: usize,
+        disp: u16,
+        bus: &mut Bus,
+    ) -> (u32, BusActivity) {
         let disp = (disp as i16) as u32;
 
         let address = self.general_purpose_reg[reg1_index].wrapping_add(disp) & 0xFFFF_FFFC;
@@ -482,14 +749,20 @@ impl CpuV810 {
 
         self.set_gen_purpose_reg(reg2_index, value);
 
-        (self.load_inst_cycle_count(), BusActivity::Load)
+        (
+            self.load_inst_cycle_count() + bus.wait_cycles(address, AccessWidth::Word),
+            BusActivity::Load,
+        )
     }
 
-    fn st_b(&mut self, instruction: u16, bus: &mut Bus) -> (u32, BusActivity) {
+    fn st_b(
+        &mut self,
+        reg1_index: usize,
+        reg2_index: usize,
+        disp: u16,
+        bus: &mut Bus,
+    ) -> (u32, BusActivity) {
         // OUT.B/ST.B Store byte
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
-        let disp = self.fetch_instruction_word(bus) as u32;
         let disp = (disp as i16) as u32;
 
         let address = self.general_purpose_reg[reg1_index].wrapping_add(disp);
@@ -497,16 +770,19 @@ impl CpuV810 {
         bus.set_u8(address, (self.general_purpose_reg[reg2_index] & 0xFF) as u8);
 
         (
-            self.store_inst_cycle_count(),
+            self.store_inst_cycle_count() + bus.wait_cycles(address, AccessWidth::Byte),
             self.incrementing_store_bus_activity(),
         )
     }
 
-    fn st_h(&mut self, instruction: u16, bus: &mut Bus) -> (u32, BusActivity) {
+    fn st_h(
+        &mut self,
+        reg1_index: usize,
+        reg2_index: usize,
+        disp: u16,
+        bus: &mut Bus,
+    ) -> (u32, BusActivity) {
         // OUT.H/ST.H Store 16 bit word
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
-        let disp = self.fetch_instruction_word(bus) as u32;
         let disp = (disp as i16) as u32;
 
         let address = self.general_purpose_reg[reg1_index].wrapping_add(disp) & 0xFFFF_FFFE;
@@ -517,16 +793,19 @@ impl CpuV810 {
         );
 
         (
-            self.store_inst_cycle_count(),
+            self.store_inst_cycle_count() + bus.wait_cycles(address, AccessWidth::Halfword),
             self.incrementing_store_bus_activity(),
         )
     }
 
-    fn st_w(&mut self, instruction: u16, bus: &mut Bus) -> (u32, BusActivity) {
+    fn st_w(
+        &mut self,
+        reg1_index: usize,
+        reg2_index: usize,
+        disp: u16,
+        bus: &mut Bus,
+    ) -> (u32, BusActivity) {
         // OUT.W/ST.W Store word
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
-        let disp = self.fetch_instruction_word(bus) as u32;
         let disp = (disp as i16) as u32;
 
         let address = self.general_purpose_reg[reg1_index].wrapping_add(disp) & 0xFFFF_FFFC;
@@ -534,14 +813,17 @@ impl CpuV810 {
         bus.set_u32(address, self.general_purpose_reg[reg2_index]);
 
         (
-            self.store_inst_cycle_count(),
+            self.store_inst_cycle_count() + bus.wait_cycles(address, AccessWidth::Word),
             self.incrementing_store_bus_activity(),
         )
     }
 
-    fn add(&mut self, instruction: u16, use_immediate: bool) -> (u32, BusActivity) {
-        let (reg1_index_or_immediate, reg2_index) = extract_reg1_2_index(instruction);
-
+    fn add(
+        &mut self,
+        reg1_index_or_immediate: usize,
+        reg2_index: usize,
+        use_immediate: bool,
+    ) -> (u32, BusActivity) {
         let value = if use_immediate {
             sign_extend(reg1_index_or_immediate as u32, 5)
         } else {
@@ -553,10 +835,13 @@ impl CpuV810 {
         self.add_inst(value, reg2, reg2_index)
     }
 
-    fn add_16_bit(&mut self, instruction: u16, bus: &mut Bus) -> (u32, BusActivity) {
+    fn add_16_bit(
+        &mut self,
+        reg1_index: usize,
+        reg2_index: usize,
+        immediate: u16,
+    ) -> (u32, BusActivity) {
         // ADD 16 bit immediate
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-        let immediate = self.fetch_instruction_word(bus);
         let immediate = (immediate as i16) as u32;
 
         let reg1 = self.general_purpose_reg[reg1_index];
@@ -564,9 +849,12 @@ impl CpuV810 {
         self.add_inst(reg1, immediate, reg2_index)
     }
 
-    fn cmp(&mut self, instruction: u16, use_immediate: bool) -> (u32, BusActivity) {
-        let (reg1_index_or_immediate, reg2_index) = extract_reg1_2_index(instruction);
-
+    fn cmp(
+        &mut self,
+        reg1_index_or_immediate: usize,
+        reg2_index: usize,
+        use_immediate: bool,
+    ) -> (u32, BusActivity) {
         let value = if use_immediate {
             sign_extend(reg1_index_or_immediate as u32, 5)
         } else {
@@ -578,9 +866,7 @@ impl CpuV810 {
         self.sub_inst(reg2, value, None)
     }
 
-    fn div(&mut self, instruction: u16, signed: bool) -> (u32, BusActivity) {
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
+    fn div(&mut self, reg1_index: usize, reg2_index: usize, signed: bool) -> (u32, BusActivity) {
         let reg1 = self.general_purpose_reg[reg1_index];
         let reg2 = self.general_purpose_reg[reg2_index];
 
@@ -588,6 +874,8 @@ impl CpuV810 {
 
         if reg1 == 0 {
             println!("Divide by zero");
+            // The restore PC is the DIV itself
+            self.pc = self.pc.wrapping_sub(2);
             self.perform_exception(0xFF80);
 
             return (cycles, BusActivity::Long);
@@ -621,9 +909,7 @@ impl CpuV810 {
         (cycles, BusActivity::Long)
     }
 
-    fn mul_signed(&mut self, instruction: u16) -> (u32, BusActivity) {
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
+    fn mul_signed(&mut self, reg1_index: usize, reg2_index: usize) -> (u32, BusActivity) {
         let reg1 = self.general_purpose_reg[reg1_index] as i32;
         let reg2 = self.general_purpose_reg[reg2_index] as i32;
 
@@ -645,9 +931,7 @@ impl CpuV810 {
         (13, BusActivity::Long)
     }
 
-    fn mul_unsigned(&mut self, instruction: u16) -> (u32, BusActivity) {
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
+    fn mul_unsigned(&mut self, reg1_index: usize, reg2_index: usize) -> (u32, BusActivity) {
         let reg1 = self.general_purpose_reg[reg1_index];
         let reg2 = self.general_purpose_reg[reg2_index];
 
@@ -667,18 +951,14 @@ impl CpuV810 {
         (13, BusActivity::Long)
     }
 
-    fn sub(&mut self, instruction: u16) -> (u32, BusActivity) {
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
+    fn sub(&mut self, reg1_index: usize, reg2_index: usize) -> (u32, BusActivity) {
         let reg1 = self.general_purpose_reg[reg1_index];
         let reg2 = self.general_purpose_reg[reg2_index];
 
         self.sub_inst(reg2, reg1, Some(reg2_index))
     }
 
-    fn and(&mut self, instruction: u16) -> (u32, BusActivity) {
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
+    fn and(&mut self, reg1_index: usize, reg2_index: usize) -> (u32, BusActivity) {
         let reg1 = self.general_purpose_reg[reg1_index];
         let reg2 = self.general_purpose_reg[reg2_index];
 
@@ -690,12 +970,10 @@ impl CpuV810 {
         (1, BusActivity::Standard)
     }
 
-    fn andi(&mut self, instruction: u16, bus: &mut Bus) -> (u32, BusActivity) {
+    fn andi(&mut self, reg1_index: usize, reg2_index: usize, immediate: u16) -> (u32, BusActivity) {
         // ANDI immediate, zero extended
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
         let reg1 = self.general_purpose_reg[reg1_index];
-        let immediate = self.fetch_instruction_word(bus) as u32;
+        let immediate = immediate as u32;
 
         let result = reg1 & immediate;
 
@@ -707,9 +985,7 @@ impl CpuV810 {
         (1, BusActivity::Standard)
     }
 
-    fn not(&mut self, instruction: u16) -> (u32, BusActivity) {
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
+    fn not(&mut self, reg1_index: usize, reg2_index: usize) -> (u32, BusActivity) {
         let reg1 = self.general_purpose_reg[reg1_index];
 
         // Interestingly, Rust uses ! for bitwise NOT
@@ -721,9 +997,7 @@ impl CpuV810 {
         (1, BusActivity::Standard)
     }
 
-    fn or(&mut self, instruction: u16) -> (u32, BusActivity) {
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
+    fn or(&mut self, reg1_index: usize, reg2_index: usize) -> (u32, BusActivity) {
         let reg1 = self.general_purpose_reg[reg1_index];
         let reg2 = self.general_purpose_reg[reg2_index];
 
@@ -735,12 +1009,10 @@ impl CpuV810 {
         (1, BusActivity::Standard)
     }
 
-    fn ori(&mut self, instruction: u16, bus: &mut Bus) -> (u32, BusActivity) {
+    fn ori(&mut self, reg1_index: usize, reg2_index: usize, immediate: u16) -> (u32, BusActivity) {
         // ORI immediate, zero extend
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
         let reg1 = self.general_purpose_reg[reg1_index];
-        let immediate = self.fetch_instruction_word(bus) as u32;
+        let immediate = immediate as u32;
 
         let result = reg1 | immediate;
 
@@ -750,10 +1022,13 @@ impl CpuV810 {
         (1, BusActivity::Standard)
     }
 
-    fn sar(&mut self, instruction: u16, use_immediate: bool) -> (u32, BusActivity) {
+    fn sar(
+        &mut self,
+        reg1_index_or_immediate: usize,
+        store_reg_index: usize,
+        use_immediate: bool,
+    ) -> (u32, BusActivity) {
         // SAR Shift arthmetic right by immediate/register
-        let (reg1_index_or_immediate, store_reg_index) = extract_reg1_2_index(instruction);
-
         let reg2 = self.general_purpose_reg[store_reg_index];
         let shift = if use_immediate {
             reg1_index_or_immediate as u32
@@ -787,10 +1062,13 @@ impl CpuV810 {
         (1, BusActivity::Standard)
     }
 
-    fn shr(&mut self, instruction: u16, use_immediate: bool) -> (u32, BusActivity) {
+    fn shr(
+        &mut self,
+        reg1_index_or_immediate: usize,
+        store_reg_index: usize,
+        use_immediate: bool,
+    ) -> (u32, BusActivity) {
         // SHR Shift logical right by immediate/register
-        let (reg1_index_or_immediate, store_reg_index) = extract_reg1_2_index(instruction);
-
         let reg2 = self.general_purpose_reg[store_reg_index];
         let shift = if use_immediate {
             reg1_index_or_immediate as u32
@@ -824,10 +1102,13 @@ impl CpuV810 {
         (1, BusActivity::Standard)
     }
 
-    fn shl(&mut self, instruction: u16, use_immediate: bool) -> (u32, BusActivity) {
+    fn shl(
+        &mut self,
+        reg1_index_or_immediate: usize,
+        store_reg_index: usize,
+        use_immediate: bool,
+    ) -> (u32, BusActivity) {
         // SHL Shift logical left by immediate/register
-        let (reg1_index_or_immediate, store_reg_index) = extract_reg1_2_index(instruction);
-
         let reg2 = self.general_purpose_reg[store_reg_index];
         let shift = if use_immediate {
             reg1_index_or_immediate as u32
@@ -858,12 +1139,15 @@ impl CpuV810 {
         (1, BusActivity::Standard)
     }
 
-    fn xor(&mut self, instruction: u16, use_immediate: bool, bus: &mut Bus) -> (u32, BusActivity) {
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
+    fn xor(
+        &mut self,
+        reg1_index: usize,
+        reg2_index: usize,
+        immediate: Option<u16>,
+    ) -> (u32, BusActivity) {
         let reg1 = self.general_purpose_reg[reg1_index];
-        let value = if use_immediate {
-            self.fetch_instruction_word(bus) as u32
+        let value = if let Some(immediate) = immediate {
+            immediate as u32
         } else {
             self.general_purpose_reg[reg2_index]
         };
@@ -902,9 +1186,7 @@ impl CpuV810 {
         (1, BusActivity::Standard)
     }
 
-    fn jmp(&mut self, instruction: u16) -> (u32, BusActivity) {
-        let (reg1_index, _) = extract_reg1_2_index(instruction);
-
+    fn jmp(&mut self, reg1_index: usize) -> (u32, BusActivity) {
         let reg1 = self.general_purpose_reg[reg1_index];
 
         self.pc = reg1 & 0xFFFF_FFFE;
@@ -912,12 +1194,12 @@ impl CpuV810 {
         (3, BusActivity::Standard)
     }
 
-    fn ldsr(&mut self, instruction: u16) -> (u32, BusActivity) {
+    fn ldsr(&mut self, reg_id: usize, reg2_index: usize, bus: &mut Bus) -> (u32, BusActivity) {
         // LDSR Load to system register
-        let (reg_id, reg2_index) = extract_reg1_2_index(instruction);
-
         let reg2 = self.general_purpose_reg[reg2_index];
 
+        let mut cycles = 0;
+
         match reg_id {
             0 => self.eipc = reg2 & 0xFFFF_FFFE,
             1 => {
@@ -929,14 +1211,10 @@ impl CpuV810 {
             // 4 => ECR not setable
             5 => self.psw.set(reg2),
             // 6 => pir
-            7 => self.tkcw = reg2,
-            24 => {
-                println!("WARNING: Writing to cache control register");
-                // TODO: Finish implementation
-                // Cache enabled is stored here because it can be read
-                self.cache_enabled = reg2 & 0x2 != 0;
-            }
-            25 => self.adtre = reg2,
+            7 => self.tkcw.set(reg2),
+            24 => cycles += self.instruction_cache.write_chcw(bus, reg2),
+            // Instructions are halfword aligned, so bit 0 is always 0
+            25 => self.adtre = reg2 & 0xFFFF_FFFE,
             29 => self.unknown_29 = reg2,
             // 30 not setable
             // 30 => self.unknown_30 = reg2,
@@ -948,7 +1226,7 @@ impl CpuV810 {
 
         // TODO: Mednafen has this set to 1 cycle
         // The V810 manual doesn't list a cycle count
-        (8, BusActivity::Standard)
+        (8 + cycles, BusActivity::Standard)
     }
 
     fn reti(&mut self) -> (u32, BusActivity) {
@@ -966,10 +1244,8 @@ impl CpuV810 {
         (10, BusActivity::Standard)
     }
 
-    fn stsr(&mut self, instruction: u16) -> (u32, BusActivity) {
+    fn stsr(&mut self, reg_id: usize, reg2_index: usize) -> (u32, BusActivity) {
         // STSR Store contents of system register
-        let (reg_id, reg2_index) = extract_reg1_2_index(instruction);
-
         let value = match reg_id {
             0 => self.eipc,
             1 => self.eipsw,
@@ -982,15 +1258,8 @@ impl CpuV810 {
                 // Static value
                 0x5346
             }
-            // 7 => tkcw
-            24 => {
-                // CHCW
-                if self.cache_enabled {
-                    2
-                } else {
-                    0
-                }
-            }
+            7 => self.tkcw.get(),
+            24 => self.instruction_cache.chcw(),
             25 => self.adtre,
             29 => self.unknown_29,
             30 => self.unknown_30,
@@ -1003,135 +1272,147 @@ impl CpuV810 {
         (8, BusActivity::Standard)
     }
 
-    fn trap(&mut self, instruction: u16) -> (u32, BusActivity) {
+    fn trap(&mut self, reg1_index: usize) -> (u32, BusActivity) {
         // TRAP Raise exception and set restore PC
-        let (reg1_index, _) = extract_reg1_2_index(instruction);
-
         self.perform_exception(0xFFA0 + reg1_index);
 
         (15, BusActivity::Standard)
     }
 
-    fn float_inst(&mut self, instruction: u16, bus: &mut Bus) -> (u32, BusActivity) {
-        // Floating point operations and Nintendo
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
-        let second_instruction = self.fetch_instruction_word(bus);
-        let sub_opcode = second_instruction >> 10;
+    fn float_inst(
+        &mut self,
+        instruction: u16,
+        reg1_index: usize,
+        reg2_index: usize,
+        second_instruction: u16,
+    ) -> (u32, BusActivity) {
+        // Floating point operations and Nintendo. Sub-opcodes are decoded in the same way as the disassembler
+        let Some(sub_opcode) = Opcode::Extended.subop(second_instruction >> 10) else {
+            return self.invalid_opcode(instruction, 4);
+        };
 
         let reg1_int = self.general_purpose_reg[reg1_index];
         let reg2_int = self.general_purpose_reg[reg2_index];
         let reg1_float = f32::from_bits(reg1_int);
         let reg2_float = f32::from_bits(reg2_int);
 
+        let rounding = self.tkcw.rounding;
+
         match sub_opcode {
             // Float
-            0b00_0100 => {
+            SubOp::AddfS => {
                 // ADDF.S Add
-                let result = reg2_float + reg1_float;
+                if self.check_reserved_operands(reg1_int, reg2_int) {
+                    return (9, BusActivity::Standard);
+                }
+
+                let result = cpu_float::add(reg2_float, reg1_float, rounding);
+                let cycles = cpu_timing::addf_cycles(reg2_float, reg1_float, result.value);
 
-                self.set_gen_purpose_reg(reg2_index, result.to_bits());
-                self.psw
-                    .update_float_flags(result, true, false, false, true, true, true);
+                self.complete_float_operation(reg2_index, result);
 
-                // TODO: This is a range of 9-28 cycles
-                (28, BusActivity::Standard)
+                (cycles, BusActivity::Standard)
             }
-            0b00_0000 => {
+            SubOp::CmpfS => {
                 // CMPF.S Compare
-                let result = reg2_float - reg1_float;
+                if self.check_reserved_operands(reg1_int, reg2_int) {
+                    return (7, BusActivity::Standard);
+                }
 
-                self.psw
-                    .update_float_flags(result, true, false, false, false, false, false);
+                // Compare exactly, rather than subtracting, so no rounding or exceptions occur
+                self.psw.zero = reg2_float == reg1_float;
+                self.psw.sign = reg2_float < reg1_float;
+                self.psw.overflow = false;
+                self.psw.carry = self.psw.sign;
 
-                // TODO: This is a range of 7-10 cycles
-                (10, BusActivity::Standard)
+                (
+                    cpu_timing::cmpf_cycles(reg2_float, reg1_float),
+                    BusActivity::Standard,
+                )
             }
-            0b00_0011 => {
+            SubOp::CvtSw => {
                 // CVT.SW Convert float to int
-                let result = reg1_float.round() as i32;
+                if self.check_reserved_operands(reg1_int, 0) {
+                    return (9, BusActivity::Standard);
+                }
 
-                self.set_gen_purpose_reg(reg2_index, result as u32);
-                self.psw
-                    .update_float_flags(result as f32, true, true, false, false, false, true);
-                self.psw.update_alu_flags(result as u32, false, None);
+                let rounding = if self.tkcw.truncate_conversion {
+                    RoundingMode::TowardZero
+                } else {
+                    rounding
+                };
+
+                let (result, exceptions) = cpu_float::float_to_int(reg1_float, rounding);
+
+                self.complete_float_to_int(reg2_index, result, exceptions);
 
-                // TODO: This is a range of 9-14 cycles
-                (14, BusActivity::Standard)
+                (cpu_timing::cvt_sw_cycles(reg1_float), BusActivity::Standard)
             }
-            0b00_0010 => {
+            SubOp::CvtWs => {
                 // CVT.WS Convert int to float
-                let result = (reg1_int as i32) as f32;
+                let result = cpu_float::int_to_float(reg1_int as i32, rounding);
 
-                self.set_gen_purpose_reg(reg2_index, result.to_bits());
-                self.psw
-                    .update_float_flags(result, false, false, false, false, false, true);
+                self.complete_float_operation(reg2_index, result);
 
-                // TODO: This is a range of 5-16 cycles
-                (16, BusActivity::Standard)
+                (
+                    cpu_timing::cvt_ws_cycles(reg1_int as i32),
+                    BusActivity::Standard,
+                )
             }
-            0b00_0111 => {
+            SubOp::DivfS => {
                 // DIVF.S Divide
-                if reg1_float == 0.0 {
-                    if reg2_float == 0.0 {
-                        println!("Divide float zero by zero");
-
-                        self.psw.float_zero_divide = true;
-                        self.perform_exception(0xFF70);
-                    } else {
-                        println!("Divide float by zero");
-
-                        self.psw.float_zero_divide = true;
-                        self.perform_exception(0xFF68);
-                    }
-
+                if self.check_reserved_operands(reg1_int, reg2_int) {
                     return (44, BusActivity::Standard);
                 }
 
-                let result = reg2_float / reg1_float;
+                let result = cpu_float::div(reg2_float, reg1_float, rounding);
 
-                self.set_gen_purpose_reg(reg2_index, result.to_bits());
-                self.psw
-                    .update_float_flags(result, true, true, true, true, true, true);
+                self.complete_float_operation(reg2_index, result);
 
                 (44, BusActivity::Standard)
             }
-            0b00_0110 => {
+            SubOp::MulfS => {
                 // MULF.S Multiply
-                let result = reg2_float * reg1_float;
+                if self.check_reserved_operands(reg1_int, reg2_int) {
+                    return (8, BusActivity::Standard);
+                }
+
+                let result = cpu_float::mul(reg2_float, reg1_float, rounding);
+                let cycles = cpu_timing::mulf_cycles(reg2_float, reg1_float, result.value);
 
-                self.set_gen_purpose_reg(reg2_index, result.to_bits());
-                self.psw
-                    .update_float_flags(result, true, false, false, true, true, true);
+                self.complete_float_operation(reg2_index, result);
 
-                // TODO: This is a range of 8-30 cycles
-                (30, BusActivity::Standard)
+                (cycles, BusActivity::Standard)
             }
-            0b00_0101 => {
+            SubOp::SubfS => {
                 // SUBF.S Subtract
-                let result = reg2_float - reg1_float;
+                if self.check_reserved_operands(reg1_int, reg2_int) {
+                    return (12, BusActivity::Standard);
+                }
 
-                self.set_gen_purpose_reg(reg2_index, result.to_bits());
-                self.psw
-                    .update_float_flags(result, true, false, false, true, true, true);
+                let result = cpu_float::sub(reg2_float, reg1_float, rounding);
+                let cycles = cpu_timing::subf_cycles(reg2_float, reg1_float, result.value);
 
-                // TODO: This is a range of 12-28 cycles
-                (28, BusActivity::Standard)
+                self.complete_float_operation(reg2_index, result);
+
+                (cycles, BusActivity::Standard)
             }
-            0b00_1011 => {
+            SubOp::TrncSw => {
                 // TRNC.SW Truncate float to int
-                let result = reg1_float.trunc() as i32;
+                if self.check_reserved_operands(reg1_int, 0) {
+                    return (9, BusActivity::Standard);
+                }
 
-                self.set_gen_purpose_reg(reg2_index, result as u32);
-                self.psw
-                    .update_float_flags(result as f32, true, true, false, false, false, true);
+                let (result, exceptions) =
+                    cpu_float::float_to_int(reg1_float, RoundingMode::TowardZero);
+
+                self.complete_float_to_int(reg2_index, result, exceptions);
 
-                // TODO: This is a range of 9-14 cycles
-                (14, BusActivity::Standard)
+                (cpu_timing::cvt_sw_cycles(reg1_float), BusActivity::Standard)
             }
 
             // Nintendo
-            0b00_1100 => {
+            SubOp::Mpyhw => {
                 // MPYHW Multiply halfword
                 let reg1_int = ((reg1_int << 15) as i32) >> 15;
                 let result = (reg2_int as i32) * reg1_int;
@@ -1140,7 +1421,7 @@ impl CpuV810 {
 
                 (9, BusActivity::Standard)
             }
-            0b00_1010 => {
+            SubOp::Rev => {
                 // REV Reverse bits in word
                 let result = reg1_int.reverse_bits();
 
@@ -1148,7 +1429,7 @@ impl CpuV810 {
 
                 (22, BusActivity::Standard)
             }
-            0b00_1000 => {
+            SubOp::Xb => {
                 // XB Exchange byte
                 // Swaps the bottom two bytes
                 let upper = reg2_int & 0xFFFF_0000;
@@ -1161,7 +1442,7 @@ impl CpuV810 {
 
                 (6, BusActivity::Standard)
             }
-            0b00_1001 => {
+            SubOp::Xh => {
                 // XH Exchange halfword
                 // Swap upper and lower halfwords
                 let result = (reg2_int >> 16) | (reg2_int << 16);
@@ -1170,14 +1451,6 @@ impl CpuV810 {
 
                 (1, BusActivity::Standard)
             }
-
-            _ => {
-                #[cfg(feature = "panic")]
-                panic!("Invalid float or Nintendo instruction {sub_opcode:x}");
-
-                #[cfg(not(feature = "panic"))]
-                (1, BusActivity::Standard)
-            }
         }
     }
 
@@ -1185,6 +1458,13 @@ impl CpuV810 {
         // Bit string operations
         let (sub_opcode, _) = extract_reg1_2_index(instruction);
 
+        if !(sub_opcode < 4 || (0b0_1000..=0b0_1111).contains(&sub_opcode)) {
+            return self.invalid_opcode(instruction, 2);
+        }
+
+        // A continuation of a partially completed bit string operation does not pay the setup cost again
+        let starting = !self.processing_bitstring;
+
         self.processing_bitstring = false;
 
         if sub_opcode < 4 {
@@ -1209,30 +1489,73 @@ impl CpuV810 {
                 _ => unreachable!(),
             };
 
-            self.bit_string_search(bus, upwards_direction, match_1)
+            let source_waits = bus.wait_cycles(self.general_purpose_reg[30], AccessWidth::Word);
+
+            self.bit_string_search(bus, upwards_direction, match_1);
+
+            (
+                cpu_timing::bit_string_search_cycles(starting) + source_waits,
+                BusActivity::Standard,
+            )
         } else {
-            self.bit_string_process_upwards(bus, sub_opcode);
-        }
+            let source_waits = bus.wait_cycles(self.general_purpose_reg[30], AccessWidth::Word);
+            // The destination word is read, then written
+            let dest_waits = 2 * bus.wait_cycles(self.general_purpose_reg[29], AccessWidth::Word);
 
-        // TODO: This should be updated from the table in the V810 manual
-        // No one seems to emulate this correctly
-        (49, BusActivity::Standard)
+            let (source_words, aligned) = self.bit_string_process_upwards(bus, sub_opcode);
+
+            (
+                cpu_timing::bit_string_transfer_cycles(starting, source_words, aligned)
+                    + source_words * source_waits
+                    + dest_waits,
+                BusActivity::Standard,
+            )
+        }
     }
 
-    fn caxi(&mut self) -> (u32, BusActivity) {
+    fn caxi(
+        &mut self,
+        reg1_index: usize,
+        reg2_index: usize,
+        disp: u16,
+        bus: &mut Bus,
+    ) -> (u32, BusActivity) {
         // CAXI Compare and exchange interlocked
-        println!("CAXI doesn't really do anything on VB");
+        let disp = (disp as i16) as u32;
 
-        (26, BusActivity::Standard)
-    }
+        let address = self.general_purpose_reg[reg1_index].wrapping_add(disp) & 0xFFFF_FFFC;
 
-    fn setf(&mut self, instruction: u16) -> (u32, BusActivity) {
-        // SETF Set flag condition
-        let (_, reg2_index) = extract_reg1_2_index(instruction);
+        let token = bus.get_u32(address);
+        let reg2 = self.general_purpose_reg[reg2_index];
+
+        // Flags are set as if by CMP
+        self.sub_inst(reg2, token, None);
+
+        // The word is always written back, leaving it unchanged if the comparison failed
+        let value = if reg2 == token {
+            self.general_purpose_reg[30]
+        } else {
+            token
+        };
+
+        bus.set_u32(address, value);
 
-        let condition = instruction & 0xF;
+        self.set_gen_purpose_reg(reg2_index, token);
+
+        // The read and write are locked together, so the store cannot be pipelined with any previous store
+        (
+            26 + 2 * bus.wait_cycles(address, AccessWidth::Word),
+            BusActivity::StoreInitial,
+        )
+    }
 
-        let value = if self.indexed_flag(condition) { 1 } else { 0 };
+    fn setf(&mut self, condition: usize, reg2_index: usize) -> (u32, BusActivity) {
+        // SETF Set flag condition
+        let value = if self.indexed_flag(condition as u16 & 0xF) {
+            1
+        } else {
+            0
+        };
 
         self.set_gen_purpose_reg(reg2_index, value);
 
@@ -1255,22 +1578,110 @@ impl CpuV810 {
         (12, BusActivity::Standard)
     }
 
+    /// Handles an undefined opcode or sub-opcode. `size` is the size in bytes of the instruction
+    fn invalid_opcode(&mut self, instruction: u16, size: u32) -> (u32, BusActivity) {
+        match self.illegal_opcode_behavior {
+            IllegalOpcodeBehavior::Exception => {
+                // The restore PC is the illegal instruction itself
+                self.pc = self.pc.wrapping_sub(size);
+
+                self.perform_exception(0xFF90);
+
+                (15, BusActivity::Standard)
+            }
+            IllegalOpcodeBehavior::HaltAndReport => {
+                self.crash(CrashCause::InvalidInstruction { instruction });
+
+                (1, BusActivity::Standard)
+            }
+            IllegalOpcodeBehavior::LogAndContinue => {
+                log!(
+                    "Invalid instruction {instruction:04X} at {:08X}",
+                    self.pc.wrapping_sub(size)
+                );
+
+                (1, BusActivity::Standard)
+            }
+        }
+    }
+
     // Utilities
 
+    /// Raises the reserved operand exception if either operand is NaN, infinity, or denormal.
+    ///
+    /// Returns true if the exception was raised
+    fn check_reserved_operands(&mut self, reg1: u32, reg2: u32) -> bool {
+        if cpu_float::is_reserved(reg1) || cpu_float::is_reserved(reg2) {
+            self.psw.float_reserved = true;
+            // The restore PC is the faulting instruction
+            self.pc = self.pc.wrapping_sub(4);
+            self.perform_exception(0xFF60);
+
+            return true;
+        }
+
+        false
+    }
+
+    /// Records the exceptions raised by a floating-point operation, trapping if enabled by TKCW.
+    ///
+    /// Returns true if an exception was raised, in which case the destination register is not written
+    fn raise_float_exceptions(&mut self, exceptions: &FloatExceptions) -> bool {
+        self.psw.update_float_exception_flags(exceptions);
+
+        // In order of priority
+        let code = if exceptions.invalid && self.tkcw.invalid_trap {
+            0xFF70
+        } else if exceptions.zero_divide && self.tkcw.zero_divide_trap {
+            0xFF68
+        } else if exceptions.overflow && self.tkcw.overflow_trap {
+            0xFF64
+        } else {
+            return false;
+        };
+
+        // The restore PC is the faulting instruction
+        self.pc = self.pc.wrapping_sub(4);
+        self.perform_exception(code);
+
+        true
+    }
+
+    fn complete_float_operation(&mut self, reg2_index: usize, result: FloatResult) {
+        if self.raise_float_exceptions(&result.exceptions) {
+            return;
+        }
+
+        self.set_gen_purpose_reg(reg2_index, result.value.to_bits());
+        self.psw.update_float_flags(result.value);
+    }
+
+    fn complete_float_to_int(
+        &mut self,
+        reg2_index: usize,
+        result: i32,
+        exceptions: FloatExceptions,
+    ) {
+        if self.raise_float_exceptions(&exceptions) {
+            return;
+        }
+
+        self.set_gen_purpose_reg(reg2_index, result as u32);
+        self.psw.update_alu_flags(result as u32, false, None);
+    }
+
     fn load_inst_16(
         &mut self,
         bus: &mut Bus,
-        instruction: u16,
+        decoded: &DecodedInstruction,
         address_mask: u32,
         value_mask: u16,
         sign_extend_count: u8,
     ) -> (u32, BusActivity) {
-        let (reg1_index, reg2_index) = extract_reg1_2_index(instruction);
-
-        let disp = self.fetch_instruction_word(bus);
-        let disp = (disp as i16) as u32;
+        let reg2_index = decoded.reg2();
+        let disp = (decoded.second_halfword as i16) as u32;
 
-        let address = self.general_purpose_reg[reg1_index].wrapping_add(disp);
+        let address = self.general_purpose_reg[decoded.reg1()].wrapping_add(disp);
         let address = address & address_mask;
 
         let mut value = bus.get_u16(address);
@@ -1288,7 +1699,16 @@ impl CpuV810 {
 
         self.set_gen_purpose_reg(reg2_index, value);
 
-        (self.load_inst_cycle_count(), BusActivity::Load)
+        let width = if value_mask == 0xFF {
+            AccessWidth::Byte
+        } else {
+            AccessWidth::Halfword
+        };
+
+        (
+            self.load_inst_cycle_count() + bus.wait_cycles(address, width),
+            BusActivity::Load,
+        )
     }
 
     fn add_inst(&mut self, a: u32, b: u32, store_reg_index: usize) -> (u32, BusActivity) {
@@ -1394,93 +1814,65 @@ impl CpuV810 {
         }
     }
 
-    fn displaced_jump(
-        &mut self,
-        bus: &mut Bus,
-        instruction: u16,
-        save_pc: bool,
-    ) -> (u32, BusActivity) {
+    fn displaced_jump(&mut self, instruction: u16, disp: u16, save_pc: bool) -> (u32, BusActivity) {
         let upper_disp = (instruction & 0x3FF) as u32;
-        let disp = self.fetch_instruction_word(bus) as u32;
+        let disp = disp as u32;
 
         let disp = sign_extend((upper_disp << 16) | disp, 26) & 0xFFFF_FFFE;
 
         if save_pc {
-            // PC has already been incremented by 4 by 2x `fetch_instruction_word`
+            // PC has already been incremented by 4 past the 32 bit instruction
             self.set_gen_purpose_reg(31, self.pc);
         }
 
-        // PC has already been incremented by 4 by 2x `fetch_instruction_word`
+        // PC has already been incremented by 4 past the 32 bit instruction
         self.pc = (self.pc - 4).wrapping_add(disp);
 
         (3, BusActivity::Standard)
     }
 
+    /// Processes up to one source word of a bit string search.
+    ///
+    /// r30/r27 hold the word address and bit offset of the next bit to examine, r28 the remaining length, and r29
+    /// accumulates the number of bits skipped. If found, r30/r27 point at the matching bit and Z is cleared
     fn bit_string_search(&mut self, bus: &mut Bus, upwards_direction: bool, match_1: bool) {
-        // Clear mask bits in registers
-        let source_offset = self.general_purpose_reg[27] & 0x3F;
-        self.set_gen_purpose_reg(27, source_offset);
-
+        let mut source_offset = self.general_purpose_reg[27] & 0x1F;
         let mut source_addr = self.general_purpose_reg[30] & 0xFFFF_FFFC;
-        self.set_gen_purpose_reg(30, source_addr);
-
         let mut length = self.general_purpose_reg[28];
+        let mut skipped_count = self.general_purpose_reg[29];
 
-        let mut source_word = bus.get_u32(source_addr);
-
-        // The position of the current bit we're examining
-        let mut word_offset = if upwards_direction { 0 } else { 31 };
-        let mut examined_bit_count = 0;
+        let source_word = bus.get_u32(source_addr);
 
         let mut found = false;
 
         while length > 0 {
-            let source_bit = if upwards_direction {
-                source_word & 1 != 0
-            } else {
-                source_word & 0x8000_0000 != 0
-            };
-
-            if source_bit == match_1 {
-                // We've found our bit
+            if (source_word >> source_offset) & 1 == match_1 as u32 {
                 found = true;
+                break;
             }
 
-            examined_bit_count += 1;
+            skipped_count += 1;
+            length -= 1;
 
-            // Haven't found it, continue processing
             if upwards_direction {
-                if word_offset == 31 {
+                if source_offset == 31 {
                     // Finished word
-                    // Reset value for reg setting below
-                    word_offset = 0;
-
-                    source_addr += 4;
+                    source_offset = 0;
+                    source_addr = source_addr.wrapping_add(4);
                     break;
-                } else {
-                    source_word = source_word << 1;
-
-                    word_offset += 1;
                 }
+
+                source_offset += 1;
             } else {
-                if word_offset == 0 {
+                if source_offset == 0 {
                     // Finished word
-                    word_offset = 31;
-
-                    source_addr -= 4;
+                    source_offset = 31;
+                    source_addr = source_addr.wrapping_sub(4);
                     break;
-                } else {
-                    source_word = source_word >> 1;
-
-                    word_offset -= 1;
                 }
-            }
 
-            if found {
-                break;
+                source_offset -= 1;
             }
-
-            length -= 1;
         }
 
         if !found && length != 0 {
@@ -1489,18 +1881,20 @@ impl CpuV810 {
 
             // Mark that we're still doing a bitstring op
             self.processing_bitstring = true;
-        } else if found {
-            // examined_bit_count counted the matched bit, remove it
-            examined_bit_count -= 1;
+        } else {
+            self.psw.zero = !found;
         }
 
         self.set_gen_purpose_reg(27, source_offset);
-        self.set_gen_purpose_reg(28, word_offset);
-        self.set_gen_purpose_reg(29, self.general_purpose_reg[29] + examined_bit_count);
+        self.set_gen_purpose_reg(28, length);
+        self.set_gen_purpose_reg(29, skipped_count);
         self.set_gen_purpose_reg(30, source_addr);
     }
 
-    fn bit_string_process_upwards(&mut self, bus: &mut Bus, sub_opcode: usize) {
+    /// Processes up to one destination word of a bit string operation.
+    ///
+    /// Returns the number of source words read, and whether the source and destination offsets were aligned
+    fn bit_string_process_upwards(&mut self, bus: &mut Bus, sub_opcode: usize) -> (u32, bool) {
         // println!("Running bit string. May have errors?");
         // Docs seem to be wrong about masking out 26 bits?
         let mut dest_offset = self.general_purpose_reg[26] & 0x1F;
@@ -1517,7 +1911,17 @@ impl CpuV810 {
         let mut source_addr = self.general_purpose_reg[30] & 0xFFFF_FFFC;
         self.set_gen_purpose_reg(30, source_addr);
 
+        let aligned = source_offset == dest_offset;
+
+        let mut source_words = 0;
+        let mut last_source_addr = None;
+
         while length > 0 {
+            if last_source_addr != Some(source_addr) {
+                last_source_addr = Some(source_addr);
+                source_words += 1;
+            }
+
             // TODO: This fetches way more often. Unsure how costly this will be
             let source_word = BitArray::<_, Lsb0>::new(bus.get_u32(source_addr));
             let mut dest_word = BitArray::<_, Lsb0>::new(bus.get_u32(dest_addr));
@@ -1558,10 +1962,7 @@ impl CpuV810 {
                     // XORNBSU XOR NOT bit string
                     *dest_bit = *dest_bit ^ !*source_bit;
                 }
-                _ => {
-                    #[cfg(feature = "panic")]
-                    panic!("Invalid bit string instruction {sub_opcode:x}")
-                }
+                _ => unreachable!(),
             }
 
             // Make sure we can access borrowed data
@@ -1603,6 +2004,8 @@ impl CpuV810 {
         self.set_gen_purpose_reg(28, length);
         self.set_gen_purpose_reg(29, dest_addr);
         self.set_gen_purpose_reg(30, source_addr);
+
+        (source_words, aligned)
     }
 
     fn load_inst_cycle_count(&self) -> u32 {
@@ -1649,6 +2052,37 @@ impl CpuV810 {
     }
 }
 
+fn new_tkcw() -> TaskControlWord {
+    TaskControlWord::new()
+}
+
+fn tkcw_from_u32(value: u32) -> TaskControlWord {
+    let mut tkcw = TaskControlWord::new();
+    tkcw.set(value);
+
+    tkcw
+}
+
+fn new_instruction_cache() -> InstructionCache {
+    InstructionCache::new()
+}
+
+fn new_decode_cache() -> DecodeCache {
+    DecodeCache::new()
+}
+
+fn no_crash() -> Option<CrashCause> {
+    None
+}
+
+fn new_pc_history() -> PcHistory {
+    PcHistory::new()
+}
+
+fn default_illegal_opcode_behavior() -> IllegalOpcodeBehavior {
+    IllegalOpcodeBehavior::default()
+}
+
 #[inline(always)]
 fn extract_reg1_2_index(instruction: u16) -> (usize, usize) {
     (
@@ -1666,3 +2100,13 @@ fn extract_reg2_index(instruction: u16) -> usize {
 fn extract_reg1_index(instruction: u16) -> usize {
     (instruction & 0x1F) as usize
 }
+
+/// Exceptions that share a handler are distinguished by the code stored in ECR
+fn exception_handler_address(code: usize) -> u32 {
+    match code {
+        // All floating point exceptions
+        0xFF60..=0xFF7F => 0xFFFF_FF60,
+        // Interrupts, and TRAP vectors 0x0-0xF and 0x10-0x1F, each share a handler
+        _ => 0xFFFF_0000 | (code as u32 & 0xFFF0),
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+
+use crate::cpu_internals::Registers;
+
+/// Number of executed PCs retained for crash reports
+const PC_HISTORY_LENGTH: usize = 32;
+
+/// Number of bytes of memory captured on either side of the faulting PC
+pub(crate) const CRASH_MEMORY_RADIUS: u32 = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrashCause {
+    /// An exception occurred while handling a duplexed exception (PSW.NP set). Real hardware halts
+    FatalException { code: u16 },
+    /// The halfword does not decode to a valid instruction
+    InvalidInstruction { instruction: u16 },
+    /// Write to an unmapped portion of the VIP address space
+    InvalidVipWrite { address: u32 },
+}
+
+impl fmt::Display for CrashCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrashCause::FatalException { code } => write!(f, "Fatal exception {code:04X}"),
+            CrashCause::InvalidInstruction { instruction } => {
+                write!(f, "Invalid instruction {instruction:04X}")
+            }
+            CrashCause::InvalidVipWrite { address } => {
+                write!(f, "Invalid VIP write to {address:08X}")
+            }
+        }
+    }
+}
+
+/// The state of the system when emulation stopped due to a fatal error.
+///
+/// Once crashed, the core remains halted and returns the report from every `run_video_frame`/`run_audio_frame` until
+/// a savestate is loaded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrashReport {
+    pub cause: CrashCause,
+    /// Address of the instruction that caused the crash
+    pub pc: u32,
+    /// Registers after the crash. PC may have advanced past the faulting instruction
+    pub registers: Registers,
+    /// The most recently executed PCs, oldest first. The last entry is `pc`
+    pub recent_pcs: Vec<u32>,
+    /// Address of the first byte of `memory`
+    pub memory_address: u32,
+    /// Memory surrounding `pc`
+    pub memory: Vec<u8>,
+}
+
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let registers = &self.registers;
+
+        writeln!(f, "{} at PC {:08X}", self.cause, self.pc)?;
+        writeln!(
+            f,
+            "PSW: {:08X}, ECR: {:08X}, EIPC: {:08X}, EIPSW: {:08X}, FEPC: {:08X}, FEPSW: {:08X}",
+            registers.psw,
+            registers.ecr,
+            registers.eipc,
+            registers.eipsw,
+            registers.fepc,
+            registers.fepsw
+        )?;
+
+        for (i, row) in registers.general_purpose.chunks(8).enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                write!(f, "r{:<2} {value:08X}  ", i * 8 + j)?;
+            }
+
+            writeln!(f)?;
+        }
+
+        writeln!(f, "Recent PCs:")?;
+
+        for pc in &self.recent_pcs {
+            writeln!(f, "  {pc:08X}")?;
+        }
+
+        writeln!(f, "Memory:")?;
+
+        for (i, row) in self.memory.chunks(16).enumerate() {
+            write!(
+                f,
+                "  {:08X}:",
+                self.memory_address.wrapping_add(i as u32 * 16)
+            )?;
+
+            for byte in row {
+                write!(f, " {byte:02X}")?;
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Ring buffer of the PCs of recently executed instructions
+pub(crate) struct PcHistory {
+    pcs: [u32; PC_HISTORY_LENGTH],
+    /// Index the next PC will be written to
+    next: usize,
+    len: usize,
+}
+
+impl PcHistory {
+    pub fn new() -> Self {
+        PcHistory {
+            pcs: [0; PC_HISTORY_LENGTH],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    #[inline(always)]
+    pub fn push(&mut self, pc: u32) {
+        self.pcs[self.next] = pc;
+        self.next = (self.next + 1) % PC_HISTORY_LENGTH;
+        self.len = (self.len + 1).min(PC_HISTORY_LENGTH);
+    }
+
+    /// Pushes the sequence `pcs` as if it was executed `count` times
+    pub fn push_repeated(&mut self, pcs: &[u32], count: usize) {
+        // Earlier repetitions would be overwritten
+        let count = count.min(PC_HISTORY_LENGTH);
+
+        for _ in 0..count {
+            for pc in pcs {
+                self.push(*pc);
+            }
+        }
+    }
+
+    /// The recorded PCs, oldest first
+    pub fn to_vec(&self) -> Vec<u32> {
+        let start = (self.next + PC_HISTORY_LENGTH - self.len) % PC_HISTORY_LENGTH;
+
+        (0..self.len)
+            .map(|i| self.pcs[(start + i) % PC_HISTORY_LENGTH])
+            .collect()
+    }
+}
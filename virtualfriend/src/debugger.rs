@@ -0,0 +1,300 @@
+use std::{collections::HashSet, ops::RangeInclusive};
+
+use crate::{
+    bus::Bus,
+    cpu_v810::CpuV810,
+    disasm::{self, Operands, Operation},
+    instruction::Opcode,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchAccess {
+    Read,
+    Write,
+}
+
+/// Stops execution when the CPU accesses an address through `Bus::get_u16`/`Bus::set_u16`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: RangeInclusive<u32>,
+    pub kind: WatchKind,
+    /// Only trigger when the halfword read or written equals this value
+    pub value: Option<u16>,
+}
+
+impl Watchpoint {
+    fn matches(&self, address: u32, value: u16, access: WatchAccess) -> bool {
+        let kind_matches = match self.kind {
+            WatchKind::Read => access == WatchAccess::Read,
+            WatchKind::Write => access == WatchAccess::Write,
+            WatchKind::ReadWrite => true,
+        };
+
+        kind_matches
+            && self.range.contains(&address)
+            && self.value.is_none_or(|expected| expected == value)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchpointHit {
+    pub id: usize,
+    pub address: u32,
+    pub value: u16,
+    pub access: WatchAccess,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// Execution reached a breakpoint. The instruction at `pc` has not executed
+    Breakpoint { pc: u32 },
+    /// The instruction at `pc` triggered a watchpoint. The instruction has completed
+    Watchpoint { pc: u32, hit: WatchpointHit },
+    /// A requested step, step over, or run until return completed. `pc` is the next instruction to execute
+    Step { pc: u32 },
+}
+
+/// Watchpoints are owned by the bus, as that is where accesses are observed
+#[derive(Default)]
+pub(crate) struct Watchpoints {
+    watchpoints: Vec<(usize, Watchpoint)>,
+    next_id: usize,
+
+    hit: Option<WatchpointHit>,
+}
+
+impl Watchpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline(always)]
+    pub fn is_active(&self) -> bool {
+        !self.watchpoints.is_empty()
+    }
+
+    pub fn check(&mut self, address: u32, value: u16, access: WatchAccess) {
+        if self.hit.is_some() {
+            // Only the first access is reported
+            return;
+        }
+
+        for (id, watchpoint) in &self.watchpoints {
+            if watchpoint.matches(address, value, access) {
+                self.hit = Some(WatchpointHit {
+                    id: *id,
+                    address,
+                    value,
+                    access,
+                });
+
+                return;
+            }
+        }
+    }
+
+    pub fn take_hit(&mut self) -> Option<WatchpointHit> {
+        self.hit.take()
+    }
+
+    fn add(&mut self, watchpoint: Watchpoint) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.watchpoints.push((id, watchpoint));
+
+        id
+    }
+
+    fn remove(&mut self, id: usize) -> bool {
+        let length = self.watchpoints.len();
+
+        self.watchpoints
+            .retain(|(existing_id, _)| *existing_id != id);
+
+        self.watchpoints.len() != length
+    }
+}
+
+enum StepMode {
+    Instruction,
+    /// Run until PC reaches `return_address` with the stack no deeper than `stack_pointer`
+    Over {
+        return_address: u32,
+        stack_pointer: u32,
+    },
+    /// Run until the `JMP [r31]` that returns from the current function
+    Return {
+        depth: usize,
+    },
+}
+
+pub(crate) struct Debugger {
+    breakpoints: HashSet<u32>,
+
+    step_mode: Option<StepMode>,
+
+    /// Set when an instruction was started under `StepMode::Return` and it returns from the current function
+    returning: bool,
+
+    /// Set after stopping at a breakpoint so that resuming executes the instruction instead of stopping again
+    resume_pc: Option<u32>,
+
+    /// Address of the most recently started instruction
+    instruction_pc: u32,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger {
+            breakpoints: HashSet::new(),
+            step_mode: None,
+            returning: false,
+            resume_pc: None,
+            instruction_pc: 0,
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_active(&self, bus: &Bus) -> bool {
+        !self.breakpoints.is_empty() || self.step_mode.is_some() || bus.watchpoints.is_active()
+    }
+
+    pub fn add_breakpoint(&mut self, pc: u32) {
+        self.breakpoints.insert(pc);
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: u32) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn add_watchpoint(&mut self, bus: &mut Bus, watchpoint: Watchpoint) -> usize {
+        bus.watchpoints.add(watchpoint)
+    }
+
+    pub fn remove_watchpoint(&mut self, bus: &mut Bus, id: usize) -> bool {
+        bus.watchpoints.remove(id)
+    }
+
+    pub fn step_instruction(&mut self) {
+        self.step_mode = Some(StepMode::Instruction);
+    }
+
+    pub fn step_over(&mut self, cpu: &CpuV810, bus: &Bus) {
+        let registers = cpu.registers();
+        let instruction = disasm::decode(
+            registers.pc,
+            bus.peek_u16(registers.pc),
+            bus.peek_u16(registers.pc.wrapping_add(2)),
+        );
+
+        self.step_mode = if instruction.operation == Operation::Opcode(Opcode::Jal) {
+            Some(StepMode::Over {
+                return_address: instruction.next_address(),
+                stack_pointer: registers.general_purpose[3],
+            })
+        } else {
+            Some(StepMode::Instruction)
+        };
+    }
+
+    pub fn run_until_return(&mut self) {
+        self.step_mode = Some(StepMode::Return { depth: 0 });
+    }
+
+    /// Cancels any in progress step
+    pub fn cancel_step(&mut self) {
+        self.step_mode = None;
+        self.returning = false;
+    }
+
+    /// Checks for a breakpoint at the current PC before the CPU steps
+    pub fn before_step(&mut self, cpu: &CpuV810, bus: &Bus) -> Option<StopReason> {
+        if !cpu.at_instruction_boundary() {
+            return None;
+        }
+
+        let pc = cpu.registers().pc;
+
+        if let Some(StepMode::Over {
+            return_address,
+            stack_pointer,
+        }) = self.step_mode
+        {
+            if pc == return_address && cpu.registers().general_purpose[3] >= stack_pointer {
+                self.step_mode = None;
+
+                return Some(StopReason::Step { pc });
+            }
+        }
+
+        if self.resume_pc.take() != Some(pc) && self.breakpoints.contains(&pc) {
+            self.resume_pc = Some(pc);
+
+            return Some(StopReason::Breakpoint { pc });
+        }
+
+        self.instruction_pc = pc;
+
+        if let Some(StepMode::Return { depth }) = &mut self.step_mode {
+            let instruction =
+                disasm::decode(pc, bus.peek_u16(pc), bus.peek_u16(pc.wrapping_add(2)));
+
+            match (instruction.operation, instruction.operands) {
+                (Operation::Opcode(Opcode::Jal), _) => *depth += 1,
+                (Operation::Opcode(Opcode::Jmp), Operands::Jump { reg1: 31 }) => {
+                    if *depth == 0 {
+                        self.returning = true;
+                    } else {
+                        *depth -= 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// Checks for watchpoint hits and step completion after the CPU steps
+    pub fn after_step(&mut self, cpu: &CpuV810, bus: &mut Bus) -> Option<StopReason> {
+        if let Some(hit) = bus.watchpoints.take_hit() {
+            return Some(StopReason::Watchpoint {
+                pc: self.instruction_pc,
+                hit,
+            });
+        }
+
+        if cpu.is_processing_bitstring() {
+            // Instruction is still in progress
+            return None;
+        }
+
+        let completed = match self.step_mode {
+            Some(StepMode::Instruction) => true,
+            Some(StepMode::Return { .. }) => self.returning,
+            _ => false,
+        };
+
+        if completed {
+            self.cancel_step();
+
+            return Some(StopReason::Step {
+                pc: cpu.registers().pc,
+            });
+        }
+
+        None
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{
+    bus::Bus,
+    cpu_v810::{BusActivity, CpuV810},
+};
+
+/// Executes a single decoded instruction
+pub(crate) type InstructionHandler =
+    fn(&mut CpuV810, &mut Bus, &DecodedInstruction) -> (u32, BusActivity);
+
+#[derive(Clone, Copy)]
+pub(crate) struct DecodedInstruction {
+    pub handler: InstructionHandler,
+    pub instruction: u16,
+    /// Only meaningful for 32 bit instructions
+    pub second_halfword: u16,
+    /// Size of the instruction in bytes
+    pub size: u32,
+    /// The reg1 and reg2 fields of the first halfword, extracted once at decode. reg1 may instead hold an immediate
+    /// or sub-opcode, depending on the format
+    reg1: u8,
+    reg2: u8,
+}
+
+impl DecodedInstruction {
+    pub fn new(
+        handler: InstructionHandler,
+        instruction: u16,
+        second_halfword: u16,
+        size: u32,
+    ) -> Self {
+        DecodedInstruction {
+            handler,
+            instruction,
+            second_halfword,
+            size,
+            reg1: (instruction & 0x1F) as u8,
+            reg2: ((instruction >> 5) & 0x1F) as u8,
+        }
+    }
+
+    #[inline(always)]
+    pub fn reg1(&self) -> usize {
+        self.reg1 as usize
+    }
+
+    #[inline(always)]
+    pub fn reg2(&self) -> usize {
+        self.reg2 as usize
+    }
+}
+
+/// Number of cached instructions. Must be a power of two
+const DECODE_CACHE_SIZE: usize = 0x4000;
+
+/// Tag for an empty entry. Instructions are halfword aligned, so no PC can match
+const EMPTY_TAG: u32 = 1;
+
+/// Direct mapped cache of decoded instructions, indexed by PC.
+///
+/// Only instructions fetched from ROM are cached, as ROM is rarely modified. The cache is cleared if a cartridge mapper
+/// changes the contents of the ROM region. Code running from RAM is always decoded from scratch, so there is nothing
+/// to invalidate.
+pub(crate) struct DecodeCache {
+    tags: Box<[u32]>,
+    entries: Box<[DecodedInstruction]>,
+}
+
+impl DecodeCache {
+    pub fn new() -> Self {
+        let empty = DecodedInstruction::new(|_, _, _| (1, BusActivity::Standard), 0, 0, 2);
+
+        DecodeCache {
+            tags: vec![EMPTY_TAG; DECODE_CACHE_SIZE].into_boxed_slice(),
+            entries: vec![empty; DECODE_CACHE_SIZE].into_boxed_slice(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn get(&self, pc: u32) -> Option<DecodedInstruction> {
+        let index = Self::index(pc);
+
+        if self.tags[index] == pc {
+            Some(self.entries[index])
+        } else {
+            None
+        }
+    }
+
+    #[inline(always)]
+    pub fn insert(&mut self, pc: u32, decoded: DecodedInstruction) {
+        let index = Self::index(pc);
+
+        self.tags[index] = pc;
+        self.entries[index] = decoded;
+    }
+
+    pub fn clear(&mut self) {
+        self.tags.fill(EMPTY_TAG);
+    }
+
+    #[inline(always)]
+    fn index(pc: u32) -> usize {
+        (pc as usize >> 1) & (DECODE_CACHE_SIZE - 1)
+    }
+}
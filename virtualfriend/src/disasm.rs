@@ -0,0 +1,335 @@
+use std::fmt;
+
+use crate::{
+    instruction::{BitStringOp, Condition, InstructionFormat, Opcode, SubOp, SystemRegister},
+    util::sign_extend,
+};
+
+/// A single decoded V810 instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    /// Address of the first halfword of the instruction
+    pub address: u32,
+    /// The raw halfwords of the instruction. The second halfword is only meaningful if `size` is 4
+    pub halfwords: [u16; 2],
+    /// Size of the instruction in bytes. Either 2 or 4
+    pub size: u32,
+    pub operation: Operation,
+    pub operands: Operands,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Opcode(Opcode),
+    BitString(BitStringOp),
+    Extended(SubOp),
+    /// An undefined opcode, bit string op, or Format VII sub-op
+    Invalid,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operands {
+    None,
+    /// `[reg1]` for JMP
+    Jump {
+        reg1: u8,
+    },
+    /// `reg2` for XB and XH
+    Register {
+        reg2: u8,
+    },
+    /// `reg1, reg2` for Format I and Format VII
+    Registers {
+        reg1: u8,
+        reg2: u8,
+    },
+    /// `imm5, reg2`. Sign extended for MOV, ADD, and CMP, zero extended for shifts
+    Immediate {
+        imm: i32,
+        reg2: u8,
+    },
+    /// `cond, reg2` for SETF
+    Condition {
+        condition: Condition,
+        reg2: u8,
+    },
+    /// `reg2, regID` for LDSR and `regID, reg2` for STSR
+    SystemRegister {
+        register: SystemRegister,
+        reg2: u8,
+    },
+    /// `vector` for TRAP
+    Vector(u8),
+    /// Absolute branch target for Format III and Format IV
+    Branch {
+        target: u32,
+    },
+    /// `imm16, reg1, reg2` for Format V
+    Immediate16 {
+        imm: u16,
+        reg1: u8,
+        reg2: u8,
+    },
+    /// `disp16[reg1], reg2` for loads, or `reg2, disp16[reg1]` for stores
+    Memory {
+        disp: i16,
+        reg1: u8,
+        reg2: u8,
+    },
+}
+
+/// Decodes the instruction at `address`.
+///
+/// `second_halfword` is only consumed by formats that carry one (IV, V, VI, VII), otherwise it is ignored.
+pub fn decode(address: u32, halfword: u16, second_halfword: u16) -> Instruction {
+    let reg1 = (halfword & 0x1F) as u8;
+    let reg2 = ((halfword >> 5) & 0x1F) as u8;
+
+    let opcode = match Opcode::from_halfword(halfword) {
+        Some(opcode) => opcode,
+        None => return Instruction::invalid(address, halfword, second_halfword, 2),
+    };
+
+    let format = opcode.instruction_format();
+    let size = if format.has_second_halfword() { 4 } else { 2 };
+
+    let (operation, operands) = match format {
+        InstructionFormat::I => {
+            let operands = if opcode == Opcode::Jmp {
+                Operands::Jump { reg1 }
+            } else {
+                Operands::Registers { reg1, reg2 }
+            };
+
+            (Operation::Opcode(opcode), operands)
+        }
+        InstructionFormat::II => {
+            let imm5 = (halfword & 0x1F) as u32;
+
+            let operands = match opcode {
+                Opcode::MovImm | Opcode::AddImm5 | Opcode::CmpImm => Operands::Immediate {
+                    imm: sign_extend(imm5, 5) as i32,
+                    reg2,
+                },
+                Opcode::ShlImm | Opcode::ShrImm | Opcode::SarImm => Operands::Immediate {
+                    imm: imm5 as i32,
+                    reg2,
+                },
+                Opcode::Setf => Operands::Condition {
+                    condition: Condition::from_bits(imm5),
+                    reg2,
+                },
+                Opcode::Ldsr | Opcode::Stsr => Operands::SystemRegister {
+                    register: opcode.system_register(imm5),
+                    reg2,
+                },
+                Opcode::Trap => Operands::Vector(imm5 as u8),
+                Opcode::BitString => {
+                    return match opcode.bit_string_op(imm5) {
+                        Some(op) => Instruction {
+                            address,
+                            halfwords: [halfword, second_halfword],
+                            size,
+                            operation: Operation::BitString(op),
+                            operands: Operands::None,
+                        },
+                        None => Instruction::invalid(address, halfword, second_halfword, size),
+                    };
+                }
+                _ => Operands::None,
+            };
+
+            (Operation::Opcode(opcode), operands)
+        }
+        InstructionFormat::III => {
+            let operands = if opcode == Opcode::Nop {
+                Operands::None
+            } else {
+                let disp = sign_extend((halfword & 0x1FF) as u32, 9);
+
+                Operands::Branch {
+                    target: address.wrapping_add(disp),
+                }
+            };
+
+            (Operation::Opcode(opcode), operands)
+        }
+        InstructionFormat::IV => {
+            let disp = (((halfword & 0x3FF) as u32) << 16) | second_halfword as u32;
+            let disp = sign_extend(disp, 26);
+
+            (
+                Operation::Opcode(opcode),
+                Operands::Branch {
+                    target: address.wrapping_add(disp),
+                },
+            )
+        }
+        InstructionFormat::V => (
+            Operation::Opcode(opcode),
+            Operands::Immediate16 {
+                imm: second_halfword,
+                reg1,
+                reg2,
+            },
+        ),
+        InstructionFormat::VI => (
+            Operation::Opcode(opcode),
+            Operands::Memory {
+                disp: second_halfword as i16,
+                reg1,
+                reg2,
+            },
+        ),
+        InstructionFormat::VII => {
+            let subop = match opcode.subop(second_halfword >> 10) {
+                Some(subop) => subop,
+                None => return Instruction::invalid(address, halfword, second_halfword, size),
+            };
+
+            let operands = match subop {
+                SubOp::Xb | SubOp::Xh => Operands::Register { reg2 },
+                _ => Operands::Registers { reg1, reg2 },
+            };
+
+            (Operation::Extended(subop), operands)
+        }
+    };
+
+    Instruction {
+        address,
+        halfwords: [halfword, second_halfword],
+        size,
+        operation,
+        operands,
+    }
+}
+
+/// Disassembles a little endian byte buffer (such as a region of a ROM file), treating the first byte as `base_address`.
+///
+/// Stops at the end of the buffer, or at an instruction that would run past it.
+pub fn disassemble(base_address: u32, bytes: &[u8]) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+
+    while offset + 2 <= bytes.len() {
+        let halfword = u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let second_halfword = if offset + 4 <= bytes.len() {
+            u16::from_le_bytes([bytes[offset + 2], bytes[offset + 3]])
+        } else {
+            0
+        };
+
+        let instruction = decode(
+            base_address.wrapping_add(offset as u32),
+            halfword,
+            second_halfword,
+        );
+
+        if offset + instruction.size as usize > bytes.len() {
+            break;
+        }
+
+        offset += instruction.size as usize;
+        instructions.push(instruction);
+    }
+
+    instructions
+}
+
+impl Instruction {
+    fn invalid(address: u32, halfword: u16, second_halfword: u16, size: u32) -> Self {
+        Instruction {
+            address,
+            halfwords: [halfword, second_halfword],
+            size,
+            operation: Operation::Invalid,
+            operands: Operands::None,
+        }
+    }
+
+    /// The address of the instruction that follows this one
+    pub fn next_address(&self) -> u32 {
+        self.address.wrapping_add(self.size)
+    }
+
+    /// Whether this instruction writes its `reg2` operand to memory, rather than loading into it
+    fn is_store(&self) -> bool {
+        matches!(
+            self.operation,
+            Operation::Opcode(
+                Opcode::Stb
+                    | Opcode::Sth
+                    | Opcode::Stw
+                    | Opcode::Outb
+                    | Opcode::Outh
+                    | Opcode::Outw
+            )
+        )
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Operation::Opcode(opcode) => write!(f, "{}", opcode),
+            Operation::BitString(op) => write!(f, "{}", op),
+            Operation::Extended(subop) => write!(f, "{}", subop),
+            Operation::Invalid => write!(f, "???"),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mnemonic = self.operation.to_string();
+
+        match self.operands {
+            Operands::None => {
+                if self.operation == Operation::Invalid {
+                    write!(f, "{:<8}0x{:04X}", mnemonic, self.halfwords[0])
+                } else {
+                    write!(f, "{}", mnemonic)
+                }
+            }
+            Operands::Jump { reg1 } => write!(f, "{:<8}[r{}]", mnemonic, reg1),
+            Operands::Register { reg2 } => write!(f, "{:<8}r{}", mnemonic, reg2),
+            Operands::Registers { reg1, reg2 } => write!(f, "{:<8}r{}, r{}", mnemonic, reg1, reg2),
+            Operands::Immediate { imm, reg2 } => {
+                write!(f, "{:<8}{}, r{}", mnemonic, format_signed(imm), reg2)
+            }
+            Operands::Condition { condition, reg2 } => {
+                write!(f, "{:<8}{}, r{}", mnemonic, condition, reg2)
+            }
+            Operands::SystemRegister { register, reg2 } => {
+                if self.operation == Operation::Opcode(Opcode::Ldsr) {
+                    write!(f, "{:<8}r{}, {}", mnemonic, reg2, register)
+                } else {
+                    write!(f, "{:<8}{}, r{}", mnemonic, register, reg2)
+                }
+            }
+            Operands::Vector(vector) => write!(f, "{:<8}0x{:X}", mnemonic, vector),
+            Operands::Branch { target } => write!(f, "{:<8}0x{:08X}", mnemonic, target),
+            Operands::Immediate16 { imm, reg1, reg2 } => {
+                write!(f, "{:<8}0x{:04X}, r{}, r{}", mnemonic, imm, reg1, reg2)
+            }
+            Operands::Memory { disp, reg1, reg2 } => {
+                let disp = format_signed(disp as i32);
+
+                if self.is_store() {
+                    write!(f, "{:<8}r{}, {}[r{}]", mnemonic, reg2, disp, reg1)
+                } else {
+                    write!(f, "{:<8}{}[r{}], r{}", mnemonic, disp, reg1, reg2)
+                }
+            }
+        }
+    }
+}
+
+fn format_signed(value: i32) -> String {
+    if value < 0 {
+        format!("-0x{:X}", value.unsigned_abs())
+    } else {
+        format!("0x{:X}", value)
+    }
+}
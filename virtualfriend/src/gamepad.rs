@@ -8,9 +8,11 @@ use crate::constants::GAMEPAD_HARDWARE_READ_CYCLE_COUNT;
 #[derive(Savefile)]
 pub struct Gamepad {
     /// K-Int-Inh When clear, key input interrupt is enabled.
-    ///
-    /// TODO: This is not implemented since it does nothing with the normal Gamepad.
     interrupt_enable: bool,
+    /// Set when a hardware read completes with a button held, while the key input interrupt is enabled. Cleared by
+    /// setting K-Int-Inh
+    #[savefile_versions = "3.."]
+    interrupt_pending: bool,
 
     /// Para/Si When set, reset read operation.
     reset: bool,
@@ -26,7 +28,7 @@ pub struct Gamepad {
     button_state: u16,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 pub struct GamepadInputs {
     pub a_button: bool,
     pub b_button: bool,
@@ -52,6 +54,7 @@ impl Gamepad {
     pub fn new() -> Self {
         Gamepad {
             interrupt_enable: false,
+            interrupt_pending: false,
             reset: false,
             soft_clk: false,
             is_hardware_reading: false,
@@ -97,12 +100,32 @@ impl Gamepad {
                     if self.hardware_read_button_index == 16 {
                         self.hardware_read_button_index = 0;
                         self.is_hardware_reading = false;
+
+                        if self.interrupt_enable && self.button_state != 0 {
+                            self.interrupt_pending = true;
+                        }
                     }
                 }
             }
         }
     }
 
+    /// The key input interrupt line is held from the end of a hardware read with a button held, until it is acknowledged
+    pub fn interrupt_line(&self) -> bool {
+        self.interrupt_pending
+    }
+
+    /// Number of cycles until the next button is shifted in by a hardware read
+    pub fn cycles_until_event(&self) -> usize {
+        if !self.is_hardware_reading {
+            return usize::MAX;
+        }
+
+        GAMEPAD_HARDWARE_READ_CYCLE_COUNT
+            .saturating_sub(self.hardware_read_counter)
+            .max(1)
+    }
+
     /// SDLR/SDHR Serial data register
     ///
     /// Controler data
@@ -157,5 +180,10 @@ impl Gamepad {
         self.reset = *array.get(5).unwrap();
 
         self.interrupt_enable = !*array.get(7).unwrap();
+
+        if !self.interrupt_enable {
+            // Setting K-Int-Inh acknowledges the interrupt
+            self.interrupt_pending = false;
+        }
     }
 }
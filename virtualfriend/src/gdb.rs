@@ -0,0 +1,472 @@
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+};
+
+use crate::{
+    crash::CrashCause,
+    debugger::{StopReason, WatchAccess, WatchKind, Watchpoint},
+    gamepad::GamepadInputs,
+    Registers, VirtualFriend,
+};
+
+/// r0-r31, 32 system registers (by LDSR/STSR ID), then PC. Matches the register layout of v810-gdb
+const REGISTER_COUNT: usize = 65;
+const PC_REGISTER: usize = 64;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+/// Serves the GDB Remote Serial Protocol on a localhost TCP port
+pub struct GdbServer {
+    listener: TcpListener,
+}
+
+impl GdbServer {
+    /// Listens on `127.0.0.1:port`. A port of 0 picks an unused port
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+
+        Ok(GdbServer { listener })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts a single debugger connection and serves it until the debugger detaches or kills the session.
+    ///
+    /// Blocks for the duration of the session. Frames produced while the target is running are discarded.
+    pub fn serve(
+        &self,
+        virtualfriend: &mut VirtualFriend,
+        inputs: GamepadInputs,
+    ) -> io::Result<()> {
+        let (stream, _) = self.listener.accept()?;
+        stream.set_nodelay(true)?;
+
+        let mut session = Session {
+            connection: Connection::new(stream),
+            virtualfriend,
+            inputs,
+            watchpoints: HashMap::new(),
+        };
+
+        session.run()
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        Connection {
+            stream,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<bool> {
+        let mut bytes = [0; 1024];
+        let count = self.stream.read(&mut bytes)?;
+
+        self.buffer.extend_from_slice(&bytes[..count]);
+
+        Ok(count > 0)
+    }
+
+    /// Reads the next packet, acknowledging it. Returns `None` if the connection closed
+    fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            // Drop acks and anything outside of a packet
+            while let Some(&byte) = self.buffer.first() {
+                if byte == b'$' || byte == 0x03 {
+                    break;
+                }
+
+                self.buffer.remove(0);
+            }
+
+            if self.buffer.first() == Some(&0x03) {
+                // A stray interrupt while stopped
+                self.buffer.remove(0);
+                continue;
+            }
+
+            if let Some(end) = self.buffer.iter().position(|&byte| byte == b'#') {
+                if self.buffer.len() >= end + 3 {
+                    let data = self.buffer[1..end].to_vec();
+                    let checksum = std::str::from_utf8(&self.buffer[end + 1..end + 3])
+                        .ok()
+                        .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+
+                    self.buffer.drain(..end + 3);
+
+                    if checksum == Some(checksum_of(&data)) {
+                        self.stream.write_all(b"+")?;
+
+                        return Ok(Some(data));
+                    } else {
+                        self.stream.write_all(b"-")?;
+
+                        continue;
+                    }
+                }
+            }
+
+            if !self.fill()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn write_packet(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        packet.extend_from_slice(data);
+        packet.extend_from_slice(format!("#{:02x}", checksum_of(data)).as_bytes());
+
+        self.stream.write_all(&packet)
+    }
+
+    /// Checks for a Ctrl-C from the debugger without blocking
+    fn poll_interrupt(&mut self) -> io::Result<bool> {
+        self.stream.set_nonblocking(true)?;
+
+        let result = match self.fill() {
+            Ok(_) => Ok(()),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(()),
+            Err(error) => Err(error),
+        };
+
+        self.stream.set_nonblocking(false)?;
+        result?;
+
+        if let Some(index) = self.buffer.iter().position(|&byte| byte == 0x03) {
+            self.buffer.remove(index);
+
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+}
+
+struct Session<'a> {
+    connection: Connection,
+    virtualfriend: &'a mut VirtualFriend,
+    inputs: GamepadInputs,
+
+    /// Watchpoint IDs by (kind, address, length)
+    watchpoints: HashMap<(u8, u32, u32), usize>,
+}
+
+impl<'a> Session<'a> {
+    fn run(&mut self) -> io::Result<()> {
+        while let Some(packet) = self.connection.read_packet()? {
+            let packet = String::from_utf8_lossy(&packet).into_owned();
+
+            let response = match packet.as_bytes().first() {
+                Some(b'?') => format!("S{SIGTRAP:02x}"),
+                Some(b'g') => self.read_registers(),
+                Some(b'G') => self.write_registers(&packet[1..]),
+                Some(b'p') => self.read_register(&packet[1..]),
+                Some(b'P') => self.write_register(&packet[1..]),
+                Some(b'm') => self.read_memory(&packet[1..]),
+                Some(b'M') => self.write_memory(&packet[1..]),
+                Some(b'Z') => self.set_breakpoint(&packet[1..], true),
+                Some(b'z') => self.set_breakpoint(&packet[1..], false),
+                Some(b'c') => self.resume(false)?,
+                Some(b's') => self.resume(true)?,
+                Some(b'H') | Some(b'T') => "OK".to_string(),
+                Some(b'k') => return Ok(()),
+                Some(b'D') => {
+                    self.connection.write_packet(b"OK")?;
+
+                    return Ok(());
+                }
+                Some(b'q') if packet.starts_with("qSupported") => {
+                    "PacketSize=1000;swbreak+".to_string()
+                }
+                Some(b'q') if packet == "qAttached" => "1".to_string(),
+                Some(b'q') if packet == "qC" => "QC1".to_string(),
+                Some(b'q') if packet == "qfThreadInfo" => "m1".to_string(),
+                Some(b'q') if packet == "qsThreadInfo" => "l".to_string(),
+                // Unsupported packets receive an empty response
+                _ => String::new(),
+            };
+
+            self.connection.write_packet(response.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn read_registers(&self) -> String {
+        let words = registers_to_words(&self.virtualfriend.registers());
+
+        words.iter().map(|word| encode_word(*word)).collect()
+    }
+
+    fn write_registers(&mut self, data: &str) -> String {
+        let mut words = registers_to_words(&self.virtualfriend.registers());
+
+        for (i, word) in words.iter_mut().enumerate() {
+            match data.get(i * 8..i * 8 + 8).and_then(decode_word) {
+                Some(value) => *word = value,
+                None => break,
+            }
+        }
+
+        self.virtualfriend
+            .set_registers(&words_to_registers(&words));
+
+        "OK".to_string()
+    }
+
+    fn read_register(&self, data: &str) -> String {
+        let words = registers_to_words(&self.virtualfriend.registers());
+
+        match usize::from_str_radix(data, 16) {
+            Ok(index) if index < REGISTER_COUNT => encode_word(words[index]),
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn write_register(&mut self, data: &str) -> String {
+        let mut words = registers_to_words(&self.virtualfriend.registers());
+
+        let parsed = data.split_once('=').and_then(|(index, value)| {
+            Some((usize::from_str_radix(index, 16).ok()?, decode_word(value)?))
+        });
+
+        match parsed {
+            Some((index, value)) if index < REGISTER_COUNT => {
+                words[index] = value;
+
+                self.virtualfriend
+                    .set_registers(&words_to_registers(&words));
+
+                "OK".to_string()
+            }
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn read_memory(&self, data: &str) -> String {
+        match parse_address_length(data) {
+            Some((address, length)) => self
+                .virtualfriend
+                .read_memory(address, length as usize)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+            None => "E01".to_string(),
+        }
+    }
+
+    fn write_memory(&mut self, data: &str) -> String {
+        let parsed = data.split_once(':').and_then(|(range, bytes)| {
+            let (address, length) = parse_address_length(range)?;
+            let bytes = decode_hex(bytes)?;
+
+            if bytes.len() != length as usize {
+                return None;
+            }
+
+            Some((address, bytes))
+        });
+
+        match parsed {
+            Some((address, bytes)) => {
+                self.virtualfriend.write_memory(address, &bytes);
+
+                "OK".to_string()
+            }
+            None => "E01".to_string(),
+        }
+    }
+
+    fn set_breakpoint(&mut self, data: &str, insert: bool) -> String {
+        let mut parts = data.split(',');
+
+        let kind = parts.next().and_then(|kind| kind.parse::<u8>().ok());
+        let address = parts
+            .next()
+            .and_then(|address| u32::from_str_radix(address, 16).ok());
+        let length = parts
+            .next()
+            .and_then(|length| u32::from_str_radix(length, 16).ok());
+
+        let (kind, address, length) = match (kind, address, length) {
+            (Some(kind), Some(address), Some(length)) => (kind, address, length),
+            _ => return "E01".to_string(),
+        };
+
+        match kind {
+            // Software and hardware breakpoints are equivalent, as we never patch memory
+            0 | 1 => {
+                if insert {
+                    self.virtualfriend.add_breakpoint(address);
+                } else {
+                    self.virtualfriend.remove_breakpoint(address);
+                }
+            }
+            2..=4 => {
+                let key = (kind, address, length);
+
+                if insert {
+                    let watch_kind = match kind {
+                        2 => WatchKind::Write,
+                        3 => WatchKind::Read,
+                        _ => WatchKind::ReadWrite,
+                    };
+
+                    // Watchpoints operate on halfwords
+                    let start = address & 0xFFFF_FFFE;
+                    let end = address.wrapping_add(length.max(1) - 1) & 0xFFFF_FFFE;
+
+                    let id = self.virtualfriend.add_watchpoint(Watchpoint {
+                        range: start..=end,
+                        kind: watch_kind,
+                        value: None,
+                    });
+
+                    self.watchpoints.insert(key, id);
+                } else if let Some(id) = self.watchpoints.remove(&key) {
+                    self.virtualfriend.remove_watchpoint(id);
+                }
+            }
+            _ => return String::new(),
+        }
+
+        "OK".to_string()
+    }
+
+    /// Runs until the debugger core stops execution, or the debugger sends Ctrl-C
+    fn resume(&mut self, step: bool) -> io::Result<String> {
+        if step {
+            self.virtualfriend.step_instruction();
+        }
+
+        loop {
+            let frame = self.virtualfriend.run_video_frame(self.inputs);
+
+            if let Some(crash) = frame.crash {
+                let signal = match crash.cause {
+                    CrashCause::InvalidInstruction { .. } => SIGILL,
+                    CrashCause::FatalException { .. } | CrashCause::InvalidVipWrite { .. } => {
+                        SIGSEGV
+                    }
+                };
+
+                return Ok(format!("S{signal:02x}"));
+            }
+
+            match frame.stop_reason {
+                Some(StopReason::Watchpoint { hit, .. }) => {
+                    let kind = match hit.access {
+                        WatchAccess::Read => "rwatch",
+                        WatchAccess::Write => "watch",
+                    };
+
+                    return Ok(format!("T{SIGTRAP:02x}{kind}:{:08x};", hit.address));
+                }
+                Some(StopReason::Breakpoint { .. }) => {
+                    return Ok(format!("T{SIGTRAP:02x}swbreak:;"));
+                }
+                Some(StopReason::Step { .. }) => return Ok(format!("S{SIGTRAP:02x}")),
+                None => {}
+            }
+
+            if self.connection.poll_interrupt()? {
+                self.virtualfriend.cancel_step();
+
+                return Ok(format!("S{SIGINT:02x}"));
+            }
+        }
+    }
+}
+
+fn registers_to_words(registers: &Registers) -> [u32; REGISTER_COUNT] {
+    let mut words = [0; REGISTER_COUNT];
+
+    words[0..32].copy_from_slice(&registers.general_purpose);
+    words[32] = registers.eipc;
+    words[33] = registers.eipsw;
+    words[34] = registers.fepc;
+    words[35] = registers.fepsw;
+    words[36] = registers.ecr;
+    words[37] = registers.psw;
+    // PIR
+    words[38] = 0x5346;
+    words[39] = registers.tkcw;
+    words[32 + 24] = registers.chcw;
+    words[32 + 25] = registers.adtre;
+    words[PC_REGISTER] = registers.pc;
+
+    words
+}
+
+fn words_to_registers(words: &[u32; REGISTER_COUNT]) -> Registers {
+    let mut general_purpose = [0; 32];
+    general_purpose.copy_from_slice(&words[0..32]);
+
+    Registers {
+        pc: words[PC_REGISTER],
+        general_purpose,
+        eipc: words[32],
+        eipsw: words[33],
+        fepc: words[34],
+        fepsw: words[35],
+        ecr: words[36],
+        psw: words[37],
+        tkcw: words[39],
+        chcw: words[32 + 24],
+        adtre: words[32 + 25],
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Registers are transferred in target (little endian) byte order
+fn encode_word(word: u32) -> String {
+    word.to_le_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn decode_word(data: &str) -> Option<u32> {
+    let bytes = decode_hex(data)?;
+
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn decode_hex(data: &str) -> Option<Vec<u8>> {
+    if data.len() % 2 == 1 {
+        return None;
+    }
+
+    (0..data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(data.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_address_length(data: &str) -> Option<(u32, u32)> {
+    let (address, length) = data.split_once(',')?;
+
+    Some((
+        u32::from_str_radix(address, 16).ok()?,
+        u32::from_str_radix(length, 16).ok()?,
+    ))
+}
@@ -4,6 +4,7 @@ use bitvec::{bitarr, field::BitField};
 
 use crate::gamepad::Gamepad;
 use crate::timer::Timer;
+use crate::wait_control::WaitControl;
 
 #[derive(Savefile)]
 pub struct Hardware {
@@ -12,6 +13,10 @@ pub struct Hardware {
     // TODO: Remove pub
     pub timer: Timer,
 
+    #[savefile_versions = "4.."]
+    #[savefile_default_fn = "new_wait_control"]
+    pub wait_control: WaitControl,
+
     comm_interrupt_enable: bool,
     comm_external_clock: bool,
     comm_inprogress: bool,
@@ -22,6 +27,7 @@ impl Hardware {
         Hardware {
             gamepad: Gamepad::new(),
             timer: Timer::new(),
+            wait_control: WaitControl::new(),
             comm_interrupt_enable: false,
             comm_external_clock: false,
             comm_inprogress: false,
@@ -83,8 +89,7 @@ impl Hardware {
             }
             0x24..=0x27 => {
                 // WCR Wait control register
-                // TODO: Implement
-                0
+                self.wait_control.get()
             }
             0x28..=0x2B => {
                 // SCR Serial control register
@@ -141,7 +146,7 @@ impl Hardware {
             }
             0x24..=0x27 => {
                 // WCR Wait control register
-                // TODO: Implement
+                self.wait_control.set(value);
             }
             0x28..=0x2B => {
                 // SCR Serial control register
@@ -153,3 +158,7 @@ impl Hardware {
         }
     }
 }
+
+fn new_wait_control() -> WaitControl {
+    WaitControl::new()
+}
@@ -0,0 +1,270 @@
+//! Detects short loops that spin until hardware state changes, such as polling INTPND, XPSTTS, or a WRAM flag set by
+//! an interrupt handler. Once an iteration is seen to leave the CPU unchanged, every following iteration will do the
+//! same until the hardware changes, so whole iterations can be skipped up to the next bus event.
+//!
+//! Loop bodies are limited to loads and ALU instructions, so skipped iterations cannot have side effects. Two matching
+//! iterations are required, as the first may be slowed by instruction cache misses.
+
+use crate::{
+    bus::Bus,
+    cpu_v810::CpuV810,
+    disasm::{self, Operation},
+    instruction::Opcode,
+    Registers,
+};
+
+/// Longest loop body, in bytes, that will be considered
+const MAX_LOOP_LENGTH: u32 = 0x40;
+
+struct IdleLoop {
+    /// Address of the first instruction in the loop
+    start: u32,
+    /// Address of the branch back to `start`
+    end: u32,
+    /// Address of each instruction in the loop, in execution order
+    pcs: Vec<u32>,
+
+    /// Registers at the start of the current iteration
+    registers: Registers,
+    /// Cycle count at the start of the current iteration
+    iteration_start: usize,
+    /// Cycle count at which the bus state next changes, as of the start of the current iteration
+    next_event: usize,
+    /// Cycles taken by the previous iteration, if it left the CPU unchanged
+    iteration_cycles: Option<usize>,
+}
+
+pub(crate) struct IdleLoopDetector {
+    current: Option<IdleLoop>,
+    /// The most recent loop that contained an instruction with side effects, to avoid decoding it every iteration
+    rejected: Option<(u32, u32)>,
+}
+
+/// Loop iterations that can be skipped
+pub(crate) struct IdleSkip {
+    pub cycles: usize,
+    pub iterations: usize,
+}
+
+impl IdleLoopDetector {
+    pub fn new() -> Self {
+        IdleLoopDetector {
+            current: None,
+            rejected: None,
+        }
+    }
+
+    /// Forgets the current loop. Must be called when the system state is changed from outside of emulation
+    pub fn reset(&mut self) {
+        self.current = None;
+        self.rejected = None;
+    }
+
+    /// Address of each instruction in the current loop
+    pub fn loop_pcs(&self) -> &[u32] {
+        self.current
+            .as_ref()
+            .map(|idle_loop| idle_loop.pcs.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Observes the instruction that just executed at `step_pc`, and the bus steps that followed.
+    ///
+    /// Returns the iterations that can be skipped, limited to fewer than `max_cycles`. The caller must then run the bus
+    /// for the skipped cycles, and call `skipped`
+    pub fn after_step(
+        &mut self,
+        step_pc: u32,
+        cpu: &CpuV810,
+        bus: &Bus,
+        cycle_count: usize,
+        max_cycles: usize,
+    ) -> Option<IdleSkip> {
+        let pc = cpu.pc();
+
+        if let Some(idle_loop) = &mut self.current {
+            if pc == idle_loop.start && step_pc == idle_loop.end {
+                return idle_loop.complete_iteration(cpu, bus, cycle_count, max_cycles);
+            }
+
+            if pc >= idle_loop.start && pc <= idle_loop.end {
+                // Still inside the loop
+                return None;
+            }
+
+            // Exited the loop, or an interrupt was taken
+            self.current = None;
+        }
+
+        if pc < step_pc
+            && step_pc - pc <= MAX_LOOP_LENGTH
+            && self.rejected != Some((pc, step_pc))
+            && cpu.at_instruction_boundary()
+        {
+            match IdleLoop::decode(pc, step_pc, cpu, bus, cycle_count) {
+                Some(idle_loop) => self.current = Some(idle_loop),
+                None => self.rejected = Some((pc, step_pc)),
+            }
+        }
+
+        None
+    }
+
+    /// Records that the iterations returned by `after_step` were skipped
+    pub fn skipped(&mut self, skip: &IdleSkip, bus: &Bus) {
+        if let Some(idle_loop) = &mut self.current {
+            idle_loop.iteration_start += skip.cycles;
+            idle_loop.next_event = idle_loop.iteration_start + bus.cycles_until_event();
+        }
+    }
+}
+
+impl IdleLoop {
+    /// Decodes the loop from `start` to the branch at `end`, if it is free of side effects
+    fn decode(
+        start: u32,
+        end: u32,
+        cpu: &CpuV810,
+        bus: &Bus,
+        cycle_count: usize,
+    ) -> Option<IdleLoop> {
+        let mut pcs = Vec::new();
+        let mut address = start;
+
+        while address <= end {
+            let instruction = disasm::decode(
+                address,
+                bus.peek_u16(address),
+                bus.peek_u16(address.wrapping_add(2)),
+            );
+
+            let Operation::Opcode(opcode) = instruction.operation else {
+                return None;
+            };
+
+            let allowed = if address == end {
+                is_branch(opcode)
+            } else {
+                is_side_effect_free(opcode)
+            };
+
+            if !allowed {
+                return None;
+            }
+
+            pcs.push(address);
+            address = instruction.next_address();
+        }
+
+        if pcs.last() != Some(&end) {
+            // The branch is not aligned with the preceding instructions
+            return None;
+        }
+
+        Some(IdleLoop {
+            start,
+            end,
+            pcs,
+            registers: cpu.registers(),
+            iteration_start: cycle_count,
+            next_event: cycle_count + bus.cycles_until_event(),
+            iteration_cycles: None,
+        })
+    }
+
+    fn complete_iteration(
+        &mut self,
+        cpu: &CpuV810,
+        bus: &Bus,
+        cycle_count: usize,
+        max_cycles: usize,
+    ) -> Option<IdleSkip> {
+        let registers = cpu.registers();
+        let cycles = cycle_count - self.iteration_start;
+
+        // If the bus changed during the iteration, its loads may not have seen the current state
+        let unchanged = registers == self.registers && cycle_count < self.next_event;
+        let repeated = unchanged && self.iteration_cycles == Some(cycles);
+
+        self.registers = registers;
+        self.iteration_start = cycle_count;
+        self.next_event = cycle_count + bus.cycles_until_event();
+        self.iteration_cycles = if unchanged { Some(cycles) } else { None };
+
+        if !repeated {
+            return None;
+        }
+
+        // Every iteration must finish before the bus changes
+        let available_cycles = bus.cycles_until_event().min(max_cycles) - 1;
+        let iterations = available_cycles / cycles;
+
+        if iterations == 0 {
+            return None;
+        }
+
+        Some(IdleSkip {
+            cycles: iterations * cycles,
+            iterations,
+        })
+    }
+}
+
+fn is_branch(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::Bv
+            | Opcode::Bc
+            | Opcode::Bz
+            | Opcode::Bnh
+            | Opcode::Bn
+            | Opcode::Br
+            | Opcode::Blt
+            | Opcode::Ble
+            | Opcode::Bnv
+            | Opcode::Bnc
+            | Opcode::Bnz
+            | Opcode::Bh
+            | Opcode::Bp
+            | Opcode::Bge
+            | Opcode::Bgt
+    )
+}
+
+/// Instructions that only modify registers, and cannot raise an exception
+fn is_side_effect_free(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::MovReg
+            | Opcode::AddReg
+            | Opcode::Sub
+            | Opcode::CmpReg
+            | Opcode::ShlReg
+            | Opcode::ShrReg
+            | Opcode::SarReg
+            | Opcode::Or
+            | Opcode::And
+            | Opcode::Xor
+            | Opcode::Not
+            | Opcode::MovImm
+            | Opcode::AddImm5
+            | Opcode::Setf
+            | Opcode::CmpImm
+            | Opcode::ShlImm
+            | Opcode::ShrImm
+            | Opcode::SarImm
+            | Opcode::Nop
+            | Opcode::Movea
+            | Opcode::AddImm16
+            | Opcode::OrI
+            | Opcode::AndI
+            | Opcode::XorI
+            | Opcode::Movhi
+            | Opcode::Ldb
+            | Opcode::Ldh
+            | Opcode::Ldw
+            | Opcode::Inb
+            | Opcode::Inh
+            | Opcode::Inw
+    )
+}
@@ -43,6 +43,7 @@ pub const OPCODE_BITS_SHL_IMM: u16 = 0b010100;
 pub const OPCODE_BITS_SHR_IMM: u16 = 0b010101;
 pub const OPCODE_BITS_CLI: u16 = 0b010110;
 pub const OPCODE_BITS_SAR_IMM: u16 = 0b010111;
+pub const OPCODE_BITS_TRAP: u16 = 0b011000;
 pub const OPCODE_BITS_RETI: u16 = 0b011001;
 pub const OPCODE_BITS_HALT: u16 = 0b011010;
 pub const OPCODE_BITS_LDSR: u16 = 0b011100;
@@ -65,12 +66,17 @@ pub const OPCODE_BITS_STH: u16 = 0b110101;
 pub const OPCODE_BITS_STW: u16 = 0b110111;
 pub const OPCODE_BITS_INB: u16 = 0b111000;
 pub const OPCODE_BITS_INH: u16 = 0b111001;
+pub const OPCODE_BITS_CAXI: u16 = 0b111010;
 pub const OPCODE_BITS_INW: u16 = 0b111011;
 pub const OPCODE_BITS_OUTB: u16 = 0b111100;
 pub const OPCODE_BITS_OUTH: u16 = 0b111101;
 pub const OPCODE_BITS_EXTENDED: u16 = 0b111110;
 pub const OPCODE_BITS_OUTW: u16 = 0b111111;
 
+pub const OPCODE_BITS_BIT_STRING_OP_SCH0BSU: u32 = 0b00000;
+pub const OPCODE_BITS_BIT_STRING_OP_SCH0BSD: u32 = 0b00001;
+pub const OPCODE_BITS_BIT_STRING_OP_SCH1BSU: u32 = 0b00010;
+pub const OPCODE_BITS_BIT_STRING_OP_SCH1BSD: u32 = 0b00011;
 pub const OPCODE_BITS_BIT_STRING_OP_ORBSU: u32 = 0b01000;
 pub const OPCODE_BITS_BIT_STRING_OP_ANDBSU: u32 = 0b01001;
 pub const OPCODE_BITS_BIT_STRING_OP_XORBSU: u32 = 0b01010;
@@ -99,7 +105,10 @@ pub const OPCODE_SYSTEM_REGISTER_ID_FEPC: u32 = 2;
 pub const OPCODE_SYSTEM_REGISTER_ID_FEPSW: u32 = 3;
 pub const OPCODE_SYSTEM_REGISTER_ID_ECR: u32 = 4;
 pub const OPCODE_SYSTEM_REGISTER_ID_PSW: u32 = 5;
+pub const OPCODE_SYSTEM_REGISTER_ID_PIR: u32 = 6;
+pub const OPCODE_SYSTEM_REGISTER_ID_TKCW: u32 = 7;
 pub const OPCODE_SYSTEM_REGISTER_ID_CHCW: u32 = 24;
+pub const OPCODE_SYSTEM_REGISTER_ID_ADTRE: u32 = 25;
 
 pub const OPCODE_CONDITION_BITS_V: u32 = 0x00;
 pub const OPCODE_CONDITION_BITS_C: u32 = 0x01;
@@ -118,7 +127,7 @@ pub const OPCODE_CONDITION_BITS_F: u32 = 0x0d;
 pub const OPCODE_CONDITION_BITS_GE: u32 = 0x0e;
 pub const OPCODE_CONDITION_BITS_GT: u32 = 0x0f;
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Opcode {
     MovReg,
     AddReg,
@@ -144,6 +153,7 @@ pub enum Opcode {
     ShrImm,
     Cli,
     SarImm,
+    Trap,
     Reti,
     Halt,
     Ldsr,
@@ -182,6 +192,7 @@ pub enum Opcode {
     Stw,
     Inb,
     Inh,
+    Caxi,
     Inw,
     Outb,
     Outh,
@@ -190,10 +201,13 @@ pub enum Opcode {
 }
 
 impl Opcode {
-    pub fn from_halfword(halfword: u16) -> Opcode {
+    /// Decodes the opcode of the first halfword of an instruction.
+    ///
+    /// Returns `None` for the opcodes the V810 leaves undefined.
+    pub fn from_halfword(halfword: u16) -> Option<Opcode> {
         if halfword >> 13 == OPCODE_BITS_BCOND_PREFIX {
             let cond_bits = (halfword >> 9) & 0x0f;
-            match cond_bits {
+            let opcode = match cond_bits {
                 OPCODE_BITS_BCOND_BV => Opcode::Bv,
                 OPCODE_BITS_BCOND_BC => Opcode::Bc,
                 OPCODE_BITS_BCOND_BZ => Opcode::Bz,
@@ -210,11 +224,13 @@ impl Opcode {
                 OPCODE_BITS_BCOND_NOP => Opcode::Nop,
                 OPCODE_BITS_BCOND_BGE => Opcode::Bge,
                 OPCODE_BITS_BCOND_BGT => Opcode::Bgt,
-                _ => panic!("Unrecognized cond bits: {:04b} (halfword: 0b{:016b})", cond_bits, halfword)
-            }
+                _ => unreachable!(),
+            };
+
+            Some(opcode)
         } else {
             let opcode_bits = halfword >> 10;
-            match opcode_bits {
+            let opcode = match opcode_bits {
                 OPCODE_BITS_MOV_REG => Opcode::MovReg,
                 OPCODE_BITS_ADD_REG => Opcode::AddReg,
                 OPCODE_BITS_SUB => Opcode::Sub,
@@ -239,6 +255,7 @@ impl Opcode {
                 OPCODE_BITS_SHR_IMM => Opcode::ShrImm,
                 OPCODE_BITS_CLI => Opcode::Cli,
                 OPCODE_BITS_SAR_IMM => Opcode::SarImm,
+                OPCODE_BITS_TRAP => Opcode::Trap,
                 OPCODE_BITS_RETI => Opcode::Reti,
                 OPCODE_BITS_HALT => Opcode::Halt,
                 OPCODE_BITS_LDSR => Opcode::Ldsr,
@@ -261,13 +278,16 @@ impl Opcode {
                 OPCODE_BITS_STW => Opcode::Stw,
                 OPCODE_BITS_INB => Opcode::Inb,
                 OPCODE_BITS_INH => Opcode::Inh,
+                OPCODE_BITS_CAXI => Opcode::Caxi,
                 OPCODE_BITS_INW => Opcode::Inw,
                 OPCODE_BITS_OUTB => Opcode::Outb,
                 OPCODE_BITS_OUTH => Opcode::Outh,
                 OPCODE_BITS_EXTENDED => Opcode::Extended,
                 OPCODE_BITS_OUTW => Opcode::Outw,
-                _ => panic!("Unrecognized opcode bits: {:06b} (halfword: 0b{:016b})", opcode_bits, halfword),
-            }
+                _ => return None,
+            };
+
+            Some(opcode)
         }
     }
 
@@ -297,6 +317,7 @@ impl Opcode {
             &Opcode::ShrImm => InstructionFormat::II,
             &Opcode::Cli => InstructionFormat::II,
             &Opcode::SarImm => InstructionFormat::II,
+            &Opcode::Trap => InstructionFormat::II,
             &Opcode::Reti => InstructionFormat::II,
             &Opcode::Halt => InstructionFormat::II,
             &Opcode::Ldsr => InstructionFormat::II,
@@ -335,6 +356,7 @@ impl Opcode {
             &Opcode::Stw => InstructionFormat::VI,
             &Opcode::Inb => InstructionFormat::VI,
             &Opcode::Inh => InstructionFormat::VI,
+            &Opcode::Caxi => InstructionFormat::VI,
             &Opcode::Inw => InstructionFormat::VI,
             &Opcode::Outb => InstructionFormat::VI,
             &Opcode::Outh => InstructionFormat::VI,
@@ -343,8 +365,12 @@ impl Opcode {
         }
     }
 
-    pub fn bit_string_op(&self, bit_string_op: u32) -> BitStringOp {
-        match bit_string_op {
+    pub fn bit_string_op(&self, bit_string_op: u32) -> Option<BitStringOp> {
+        let op = match bit_string_op {
+            OPCODE_BITS_BIT_STRING_OP_SCH0BSU => BitStringOp::Sch0bsu,
+            OPCODE_BITS_BIT_STRING_OP_SCH0BSD => BitStringOp::Sch0bsd,
+            OPCODE_BITS_BIT_STRING_OP_SCH1BSU => BitStringOp::Sch1bsu,
+            OPCODE_BITS_BIT_STRING_OP_SCH1BSD => BitStringOp::Sch1bsd,
             OPCODE_BITS_BIT_STRING_OP_ORBSU => BitStringOp::Orbsu,
             OPCODE_BITS_BIT_STRING_OP_ANDBSU => BitStringOp::Andbsu,
             OPCODE_BITS_BIT_STRING_OP_XORBSU => BitStringOp::Xorbsu,
@@ -353,12 +379,14 @@ impl Opcode {
             OPCODE_BITS_BIT_STRING_OP_ANDNBSU => BitStringOp::Andnbsu,
             OPCODE_BITS_BIT_STRING_OP_XORNBSU => BitStringOp::Xornbsu,
             OPCODE_BITS_BIT_STRING_OP_NOTBSU => BitStringOp::Notbsu,
-            _ => panic!("Unrecognized bit string op: {:05b}", bit_string_op)
-        }
+            _ => return None,
+        };
+
+        Some(op)
     }
 
-    pub fn subop(&self, subop: u16) -> SubOp {
-        match subop {
+    pub fn subop(&self, subop: u16) -> Option<SubOp> {
+        let subop = match subop {
             OPCODE_BITS_SUB_OP_CMPF_S => SubOp::CmpfS,
             OPCODE_BITS_SUB_OP_CVT_WS => SubOp::CvtWs,
             OPCODE_BITS_SUB_OP_CVT_SW => SubOp::CvtSw,
@@ -371,8 +399,10 @@ impl Opcode {
             OPCODE_BITS_SUB_OP_REV => SubOp::Rev,
             OPCODE_BITS_SUB_OP_TRNC_SW => SubOp::TrncSw,
             OPCODE_BITS_SUB_OP_MPYHW => SubOp::Mpyhw,
-            _ => panic!("Unrecognized subop bits: {:06b}", subop),
-        }
+            _ => return None,
+        };
+
+        Some(subop)
     }
 
     pub fn system_register(&self, imm5: u32) -> SystemRegister {
@@ -383,7 +413,10 @@ impl Opcode {
             OPCODE_SYSTEM_REGISTER_ID_FEPSW => SystemRegister::Fepsw,
             OPCODE_SYSTEM_REGISTER_ID_ECR => SystemRegister::Ecr,
             OPCODE_SYSTEM_REGISTER_ID_PSW => SystemRegister::Psw,
+            OPCODE_SYSTEM_REGISTER_ID_PIR => SystemRegister::Pir,
+            OPCODE_SYSTEM_REGISTER_ID_TKCW => SystemRegister::Tkcw,
             OPCODE_SYSTEM_REGISTER_ID_CHCW => SystemRegister::Chcw,
+            OPCODE_SYSTEM_REGISTER_ID_ADTRE => SystemRegister::Adtre,
             _ => SystemRegister::Unknown(imm5),
         }
     }
@@ -410,6 +443,7 @@ impl fmt::Display for Opcode {
             &Opcode::Not => "not",
             &Opcode::Setf => "setf",
             &Opcode::Cli => "cli",
+            &Opcode::Trap => "trap",
             &Opcode::Reti => "reti",
             &Opcode::Halt => "halt",
             &Opcode::Ldsr => "ldsr",
@@ -448,6 +482,7 @@ impl fmt::Display for Opcode {
             &Opcode::Stw => "st.w",
             &Opcode::Inb => "in.b",
             &Opcode::Inh => "in.h",
+            &Opcode::Caxi => "caxi",
             &Opcode::Inw => "in.w",
             &Opcode::Outb => "out.b",
             &Opcode::Outh => "out.h",
@@ -458,6 +493,7 @@ impl fmt::Display for Opcode {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum InstructionFormat {
     I,
     II,
@@ -482,7 +518,12 @@ impl InstructionFormat {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BitStringOp {
+    Sch0bsu,
+    Sch0bsd,
+    Sch1bsu,
+    Sch1bsd,
     Orbsu,
     Andbsu,
     Xorbsu,
@@ -496,6 +537,10 @@ pub enum BitStringOp {
 impl fmt::Display for BitStringOp {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mnemonic = match self {
+            &BitStringOp::Sch0bsu => "sch0bsu",
+            &BitStringOp::Sch0bsd => "sch0bsd",
+            &BitStringOp::Sch1bsu => "sch1bsu",
+            &BitStringOp::Sch1bsd => "sch1bsd",
             &BitStringOp::Orbsu => "orbsu",
             &BitStringOp::Andbsu => "andbsu",
             &BitStringOp::Xorbsu => "xorbsu",
@@ -509,6 +554,7 @@ impl fmt::Display for BitStringOp {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SubOp {
     CmpfS,
     CvtWs,
@@ -544,6 +590,7 @@ impl fmt::Display for SubOp {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SystemRegister {
     Eipc,
     Eipsw,
@@ -551,7 +598,10 @@ pub enum SystemRegister {
     Fepsw,
     Ecr,
     Psw,
+    Pir,
+    Tkcw,
     Chcw,
+    Adtre,
     Unknown(u32),
 }
 
@@ -564,8 +614,79 @@ impl fmt::Display for SystemRegister {
             &SystemRegister::Fepsw => write!(f, "{}", "fepsw"),
             &SystemRegister::Ecr => write!(f, "{}", "ecr"),
             &SystemRegister::Psw => write!(f, "{}", "psw"),
+            &SystemRegister::Pir => write!(f, "{}", "pir"),
+            &SystemRegister::Tkcw => write!(f, "{}", "tkcw"),
             &SystemRegister::Chcw => write!(f, "{}", "chcw"),
+            &SystemRegister::Adtre => write!(f, "{}", "adtre"),
             &SystemRegister::Unknown(imm5) => write!(f, "??? ({})", imm5),
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    V,
+    C,
+    Z,
+    Nh,
+    N,
+    T,
+    Lt,
+    Le,
+    Nv,
+    Nc,
+    Nz,
+    H,
+    P,
+    F,
+    Ge,
+    Gt,
+}
+
+impl Condition {
+    pub fn from_bits(bits: u32) -> Condition {
+        match bits & 0xF {
+            OPCODE_CONDITION_BITS_V => Condition::V,
+            OPCODE_CONDITION_BITS_C => Condition::C,
+            OPCODE_CONDITION_BITS_Z => Condition::Z,
+            OPCODE_CONDITION_BITS_NH => Condition::Nh,
+            OPCODE_CONDITION_BITS_N => Condition::N,
+            OPCODE_CONDITION_BITS_T => Condition::T,
+            OPCODE_CONDITION_BITS_LT => Condition::Lt,
+            OPCODE_CONDITION_BITS_LE => Condition::Le,
+            OPCODE_CONDITION_BITS_NV => Condition::Nv,
+            OPCODE_CONDITION_BITS_NC => Condition::Nc,
+            OPCODE_CONDITION_BITS_NZ => Condition::Nz,
+            OPCODE_CONDITION_BITS_H => Condition::H,
+            OPCODE_CONDITION_BITS_P => Condition::P,
+            OPCODE_CONDITION_BITS_F => Condition::F,
+            OPCODE_CONDITION_BITS_GE => Condition::Ge,
+            OPCODE_CONDITION_BITS_GT => Condition::Gt,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mnemonic = match self {
+            &Condition::V => "v",
+            &Condition::C => "c",
+            &Condition::Z => "z",
+            &Condition::Nh => "nh",
+            &Condition::N => "n",
+            &Condition::T => "t",
+            &Condition::Lt => "lt",
+            &Condition::Le => "le",
+            &Condition::Nv => "nv",
+            &Condition::Nc => "nc",
+            &Condition::Nz => "nz",
+            &Condition::H => "h",
+            &Condition::P => "p",
+            &Condition::F => "f",
+            &Condition::Ge => "ge",
+            &Condition::Gt => "gt",
+        };
+        write!(f, "{}", mnemonic)
+    }
+}
@@ -0,0 +1,194 @@
+use crate::bus::Bus;
+
+/// The 1KB cache is direct mapped with 128 entries of two 32 bit subblocks
+const ENTRY_COUNT: usize = 128;
+
+/// Extra cycles to fill a subblock on a miss, on top of the wait states of reading it. The VB bus is 16 bits wide, so
+/// each subblock takes two reads.
+///
+/// Taken from Mednafen, which notes that a miss appears to be more expensive than running with the cache disabled. Hits
+/// take no fetch cycles at all, so loops that fit in the cache run faster than from uncached ROM
+const MISS_PENALTY_CYCLES: u32 = 3;
+
+/// Cycles to transfer a single word to or from memory during a dump or restore
+const TRANSFER_WORD_CYCLES: u32 = 2;
+
+/// ICC Instruction cache clear
+const CHCW_CLEAR: u32 = 0x1;
+/// ICE Instruction cache enable
+const CHCW_ENABLE: u32 = 0x2;
+/// ICD Instruction cache dump
+const CHCW_DUMP: u32 = 0x10;
+/// ICR Instruction cache restore
+const CHCW_RESTORE: u32 = 0x20;
+
+#[derive(Savefile, Clone, Copy)]
+struct CacheEntry {
+    /// Address bits 31-10 of the cached block
+    tag: u32,
+    data: [u32; 2],
+    valid: [bool; 2],
+}
+
+impl CacheEntry {
+    const EMPTY: CacheEntry = CacheEntry {
+        tag: 0,
+        data: [0; 2],
+        valid: [false; 2],
+    };
+}
+
+/// The V810 instruction cache, configured by the CHCW system register
+#[derive(Savefile)]
+pub struct InstructionCache {
+    enabled: bool,
+    entries: [CacheEntry; ENTRY_COUNT],
+    /// Entries have been loaded by ICR, so may not match memory. Cleared once every entry is cleared by ICC
+    #[savefile_versions = "6.."]
+    restored: bool,
+}
+
+impl InstructionCache {
+    pub fn new() -> Self {
+        InstructionCache {
+            enabled: false,
+            entries: [CacheEntry::EMPTY; ENTRY_COUNT],
+            restored: false,
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether instructions must be decoded from the cached data rather than memory, as it was restored by ICR and may
+    /// differ. Otherwise cached data always matches memory at the time it was cached
+    #[inline(always)]
+    pub fn holds_restored_data(&self) -> bool {
+        self.enabled && self.restored
+    }
+
+    /// Sets ICE without performing any cache commands. Used by debuggers
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// The readable portion of CHCW. Only ICE is retained
+    pub fn chcw(&self) -> u32 {
+        if self.enabled {
+            CHCW_ENABLE
+        } else {
+            0
+        }
+    }
+
+    /// Handles a write to CHCW, performing any requested clear, dump, or restore command.
+    ///
+    /// Returns the number of additional cycles consumed by the command
+    pub fn write_chcw(&mut self, bus: &mut Bus, value: u32) -> u32 {
+        let cycles = if value & CHCW_CLEAR != 0 {
+            // CEN: First entry to clear
+            let start = ((value >> 20) & 0xFFF) as usize;
+            // CEC: Number of entries to clear
+            let count = ((value >> 8) & 0xFFF) as usize;
+
+            for entry in self.entries.iter_mut().skip(start).take(count) {
+                *entry = CacheEntry::EMPTY;
+            }
+
+            if start == 0 && count >= ENTRY_COUNT {
+                self.restored = false;
+            }
+
+            0
+        } else if value & CHCW_DUMP != 0 {
+            self.dump(bus, value & 0xFFFF_FF00)
+        } else if value & CHCW_RESTORE != 0 {
+            self.restore(bus, value & 0xFFFF_FF00)
+        } else {
+            0
+        };
+
+        self.enabled = value & CHCW_ENABLE != 0;
+
+        cycles
+    }
+
+    /// Reads the instruction halfword at `address` through the cache, filling the containing subblock with
+    /// `read_word` on a miss. `read_word` returns the word, and the difference in its wait cycles from the power on
+    /// setting.
+    ///
+    /// Returns the halfword and the number of cycles spent filling the cache
+    #[inline(always)]
+    pub fn fetch(&mut self, address: u32, read_word: impl FnOnce(u32) -> (u32, i32)) -> (u16, u32) {
+        let index = ((address >> 3) as usize) & (ENTRY_COUNT - 1);
+        let subblock = ((address >> 2) & 1) as usize;
+        let tag = address >> 10;
+
+        let entry = &mut self.entries[index];
+
+        let mut cycles = 0;
+
+        if entry.tag != tag {
+            // Replace the entire block
+            entry.tag = tag;
+            entry.valid = [false; 2];
+        }
+
+        if !entry.valid[subblock] {
+            let (word, wait_delta) = read_word(address & 0xFFFF_FFFC);
+
+            entry.data[subblock] = word;
+            entry.valid[subblock] = true;
+
+            // Mednafen's penalty is for the power on wait states
+            cycles = MISS_PENALTY_CYCLES.saturating_add_signed(wait_delta);
+        }
+
+        let word = entry.data[subblock];
+
+        let halfword = if address & 2 != 0 { word >> 16 } else { word };
+
+        (halfword as u16, cycles)
+    }
+
+    /// Writes the subblock data, followed by the tag and valid bits of each entry, to memory
+    fn dump(&self, bus: &mut Bus, address: u32) -> u32 {
+        for (i, entry) in self.entries.iter().enumerate() {
+            let entry_address = address.wrapping_add(i as u32 * 8);
+
+            bus.set_u32(entry_address, entry.data[0]);
+            bus.set_u32(entry_address.wrapping_add(4), entry.data[1]);
+        }
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            let tag = entry.tag | ((entry.valid[0] as u32) << 22) | ((entry.valid[1] as u32) << 23);
+
+            bus.set_u32(address.wrapping_add(0x400 + i as u32 * 4), tag);
+        }
+
+        (ENTRY_COUNT as u32) * 3 * TRANSFER_WORD_CYCLES
+    }
+
+    /// Loads cache contents from memory in the format written by `dump`
+    fn restore(&mut self, bus: &mut Bus, address: u32) -> u32 {
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            let entry_address = address.wrapping_add(i as u32 * 8);
+
+            entry.data[0] = bus.get_u32(entry_address);
+            entry.data[1] = bus.get_u32(entry_address.wrapping_add(4));
+        }
+
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            let tag = bus.get_u32(address.wrapping_add(0x400 + i as u32 * 4));
+
+            entry.tag = tag & 0x3F_FFFF;
+            entry.valid = [tag & (1 << 22) != 0, tag & (1 << 23) != 0];
+        }
+
+        self.restored = true;
+
+        (ENTRY_COUNT as u32) * 3 * TRANSFER_WORD_CYCLES
+    }
+}
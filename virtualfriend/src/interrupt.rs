@@ -1,4 +1,4 @@
-#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum InterruptRequest {
     GamePad,
     TimerZero,
@@ -8,6 +8,15 @@ pub enum InterruptRequest {
 }
 
 impl InterruptRequest {
+    /// Every source, from highest to lowest priority
+    pub const PRIORITY_ORDER: [InterruptRequest; 5] = [
+        Self::VIP,
+        Self::Communication,
+        Self::GamePak,
+        Self::TimerZero,
+        Self::GamePad,
+    ];
+
     pub fn code(&self) -> usize {
         match self {
             Self::VIP => 0xFE40,
@@ -17,4 +26,47 @@ impl InterruptRequest {
             Self::GamePad => 0xFE00,
         }
     }
+
+    /// Interrupt level, compared against PSW.I. Second nibble of the code
+    pub fn level(&self) -> u8 {
+        ((self.code() >> 4) & 0xF) as u8
+    }
+}
+
+/// The interrupt lines from each hardware source into the CPU.
+///
+/// Sources hold their line asserted until software acknowledges them (Z-Stat-Clr, INTCLR, etc.), so a line that is
+/// masked by the CPU remains pending, and is serviced once PSW allows it. Only the highest priority line is
+/// presented to the CPU.
+#[derive(Savefile)]
+pub struct InterruptController {
+    /// Bit per source, indexed by interrupt level
+    pending: u8,
+}
+
+impl InterruptController {
+    pub fn new() -> Self {
+        InterruptController { pending: 0 }
+    }
+
+    pub fn set_line(&mut self, source: InterruptRequest, asserted: bool) {
+        let mask = 1 << source.level();
+
+        if asserted {
+            self.pending |= mask;
+        } else {
+            self.pending &= !mask;
+        }
+    }
+
+    pub fn is_pending(&self, source: InterruptRequest) -> bool {
+        self.pending & (1 << source.level()) != 0
+    }
+
+    /// The highest priority pending source. If the CPU masks it, it masks every lower priority source as well
+    pub fn highest_pending(&self) -> Option<InterruptRequest> {
+        InterruptRequest::PRIORITY_ORDER
+            .into_iter()
+            .find(|source| self.is_pending(*source))
+    }
 }
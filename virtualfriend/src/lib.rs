@@ -3,38 +3,76 @@ extern crate savefile;
 #[macro_use]
 extern crate savefile_derive;
 
+use std::io;
+
+use cartridge::{Cartridge, CartridgeMapper, RomError, RomInfo, StandardMapper};
+use config::EmulatorConfig;
+use crash::{CrashCause, CrashReport, CRASH_MEMORY_RADIUS};
+use debugger::{Debugger, StopReason, Watchpoint};
+use disasm::Instruction;
+use idle::IdleLoopDetector;
+use profiler::{Profile, Profiler};
 use savestates::{savestate::UnparsedSavestate, SavestateController};
 use system::System;
+use trace::{TraceFilter, TraceSink, Tracer};
 use vsu::traits::{AudioFrame, Sink};
 
 use crate::{constants::LEFT_FRAME_BUFFER_CYCLE_OFFSET, gamepad::GamepadInputs};
 
+// Declared first so its macros are visible to every other module
+#[macro_use]
+mod log;
+
+#[cfg(feature = "test-util")]
+pub mod assembler;
 mod bus;
-mod cartridge;
+pub mod cartridge;
+pub mod config;
 mod constants;
+mod cpu_float;
 mod cpu_internals;
+mod cpu_timing;
 mod cpu_v810;
+pub mod crash;
+pub mod debugger;
+mod decode_cache;
+pub mod disasm;
 pub mod gamepad;
+pub mod gdb;
 mod hardware;
+mod idle;
+pub mod instruction;
+mod instruction_cache;
 mod interrupt;
-#[macro_use]
-mod log;
+pub mod lockstep;
 pub mod manifest;
+pub mod profiler;
 pub mod savestates;
 mod system;
 mod timer;
+pub mod trace;
 mod util;
 mod vip;
 pub mod vsu;
+mod wait_control;
+
+pub use cpu_internals::Registers;
 
 pub struct VirtualFriend {
     system: System,
 
-    rom: Vec<u8>,
-
     savestate: SavestateController,
 
-    // writer: BufWriter<File>,
+    tracer: Option<Tracer>,
+
+    profiler: Option<Profiler>,
+
+    debugger: Debugger,
+
+    /// Skip ahead through HALT and idle loops to the next bus event, rather than stepping cycle by cycle
+    idle_skipping: bool,
+    idle_loop_detector: IdleLoopDetector,
+
     video_frame_serviced: bool,
     cycle_count: usize,
 }
@@ -47,6 +85,10 @@ pub struct VideoFrame {
 pub struct Frame {
     pub video: Option<VideoFrame>,
     pub audio_buffer: Vec<AudioFrame>,
+    /// Set if the debugger stopped execution before the frame completed
+    pub stop_reason: Option<StopReason>,
+    /// Set if the system has crashed. No further frames will be produced
+    pub crash: Option<CrashReport>,
 }
 
 struct SimpleAudioFrameSink {
@@ -66,41 +108,37 @@ impl Sink<AudioFrame> for SimpleAudioFrameSink {
 }
 
 impl VirtualFriend {
-    pub fn new(rom: Vec<u8>) -> Self {
-        println!("Loading ROM");
-
-        // TODO: Ideally we don't maintain two copies of the ROM in RAM for savestate loading
-        let system = System::new(rom.clone());
-
-        let savestate = SavestateController::new();
-
-        // let mut temp_dir = env::temp_dir();
-
-        // println!("{temp_dir:?}");
+    pub fn new(rom: Vec<u8>) -> Result<Self, RomError> {
+        Self::new_with_config(rom, EmulatorConfig::default())
+    }
 
-        // fs::create_dir_all(&temp_dir).unwrap();
-        // temp_dir.push("instructions.log");
+    /// Loads `rom` as a standard Game Pak. Images that are not a power of two in size are mirrored to fill the ROM
+    /// region, as on hardware
+    pub fn new_with_config(rom: Vec<u8>, config: EmulatorConfig) -> Result<Self, RomError> {
+        Ok(Self::new_with_mapper(
+            Box::new(StandardMapper::new(rom)?),
+            config,
+        ))
+    }
 
-        // println!("Logging to {:?}", temp_dir);
+    /// Creates the system with a Game Pak other than a standard ROM and RAM, such as a flash cartridge
+    pub fn new_with_mapper(mapper: Box<dyn CartridgeMapper>, config: EmulatorConfig) -> Self {
+        println!("Loading ROM");
 
-        // let log_file = OpenOptions::new()
-        //     .write(true)
-        //     .create(true)
-        //     .open(
-        //         // temp_dir
-        //         "instructions.log",
-        //     )
-        //     .unwrap();
+        let idle_skipping = !config.disable_idle_skipping;
 
-        // let writer = BufWriter::with_capacity(4000, log_file);
+        let system = System::new(Cartridge::new(mapper), config);
 
-        // cpu.debug_init();
+        let savestate = SavestateController::new();
 
         Self {
             system,
-            rom,
             savestate,
-            // writer,
+            tracer: None,
+            profiler: None,
+            debugger: Debugger::new(),
+            idle_skipping,
+            idle_loop_detector: IdleLoopDetector::new(),
             video_frame_serviced: false,
             cycle_count: 0,
         }
@@ -110,12 +148,30 @@ impl VirtualFriend {
         let mut emu_audio_sink = SimpleAudioFrameSink::new();
 
         loop {
-            self.system_tick(&mut emu_audio_sink, &inputs);
+            if let Some(stop_reason) = self.system_tick(&mut emu_audio_sink, &inputs, None) {
+                return Frame {
+                    video: None,
+                    audio_buffer: emu_audio_sink.inner,
+                    stop_reason: Some(stop_reason),
+                    crash: None,
+                };
+            }
+
+            if let Some(crash) = self.crash_report() {
+                return Frame {
+                    video: None,
+                    audio_buffer: emu_audio_sink.inner,
+                    stop_reason: None,
+                    crash: Some(crash),
+                };
+            }
 
             if let Some(frame) = self.frame_tick() {
                 return Frame {
                     video: Some(frame),
                     audio_buffer: emu_audio_sink.inner,
+                    stop_reason: None,
+                    crash: None,
                 };
             }
         }
@@ -131,10 +187,26 @@ impl VirtualFriend {
         let mut buffered_video_frame: Option<VideoFrame> = None;
 
         loop {
-            // self.cpu
-            //     .log_instruction(Some(&mut self.writer), self.cycle_count, None);
+            // Idle skipping must not run past the point the buffer is filled
+            if let Some(stop_reason) =
+                self.system_tick(&mut emu_audio_sink, &inputs, Some(buffer_size))
+            {
+                return Frame {
+                    video: buffered_video_frame,
+                    audio_buffer: emu_audio_sink.inner,
+                    stop_reason: Some(stop_reason),
+                    crash: None,
+                };
+            }
 
-            self.system_tick(&mut emu_audio_sink, &inputs);
+            if let Some(crash) = self.crash_report() {
+                return Frame {
+                    video: buffered_video_frame,
+                    audio_buffer: emu_audio_sink.inner,
+                    stop_reason: None,
+                    crash: Some(crash),
+                };
+            }
 
             if let Some(frame) = self.frame_tick() {
                 buffered_video_frame = Some(frame);
@@ -145,6 +217,8 @@ impl VirtualFriend {
                 return Frame {
                     video: buffered_video_frame,
                     audio_buffer: emu_audio_sink.inner,
+                    stop_reason: None,
+                    crash: None,
                 };
             }
         }
@@ -152,8 +226,8 @@ impl VirtualFriend {
 
     pub fn run_rewind_frame(&mut self) -> Option<VideoFrame> {
         if let Some(savestate) = self.savestate.rewind_tick() {
-            self.system
-                .replace_from_savestate(savestate.contents(), self.rom.clone());
+            self.system.replace_from_savestate(savestate.contents());
+            self.idle_loop_detector.reset();
 
             return Some(VideoFrame {
                 left: savestate.left_frame,
@@ -164,6 +238,11 @@ impl VirtualFriend {
         None
     }
 
+    /// The header and hashes of the loaded ROM, for hosts to identify the game
+    pub fn rom_info(&self) -> &RomInfo {
+        self.system.bus.cart.rom_info()
+    }
+
     pub fn load_ram(&mut self, ram: Vec<u8>) {
         self.system.bus.cart.load_ram(ram)
     }
@@ -183,7 +262,8 @@ impl VirtualFriend {
     pub fn load_savestate(&mut self, savestate: &UnparsedSavestate) {
         let system = self.savestate.load_savestate_to_system(savestate);
 
-        self.system.replace_from_savestate(system, self.rom.clone());
+        self.system.replace_from_savestate(system);
+        self.idle_loop_detector.reset();
     }
 
     // TODO: This should be failable
@@ -193,18 +273,314 @@ impl VirtualFriend {
         self.load_savestate(&savestate);
     }
 
-    fn system_tick(&mut self, emu_audio_sink: &mut SimpleAudioFrameSink, inputs: &GamepadInputs) {
-        let step_cycle_count = self.system.cpu.step(&mut self.system.bus);
+    /// Disassembles `count` instructions starting at `address`, reading through the bus without side effects
+    pub fn disassemble(&self, address: u32, count: usize) -> Vec<Instruction> {
+        let bus = &self.system.bus;
+
+        let mut address = address;
+        let mut instructions = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let instruction = disasm::decode(
+                address,
+                bus.peek_u16(address),
+                bus.peek_u16(address.wrapping_add(2)),
+            );
+
+            address = instruction.next_address();
+            instructions.push(instruction);
+        }
+
+        instructions
+    }
+
+    /// Describes the fatal error that stopped emulation, if the system has crashed
+    pub fn crash_report(&self) -> Option<CrashReport> {
+        let cause = self.system.cpu.crash_cause()?;
+
+        let registers = self.system.cpu.registers();
+        let recent_pcs = self.system.cpu.pc_history().to_vec();
+        let pc = recent_pcs.last().copied().unwrap_or(registers.pc);
+
+        let memory_address = pc.wrapping_sub(CRASH_MEMORY_RADIUS);
+
+        Some(CrashReport {
+            cause,
+            pc,
+            registers,
+            recent_pcs,
+            memory_address,
+            memory: self.read_memory(memory_address, CRASH_MEMORY_RADIUS as usize * 2),
+        })
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.system.cpu.registers()
+    }
+
+    pub fn set_registers(&mut self, registers: &Registers) {
+        self.system.cpu.set_registers(registers);
+        self.idle_loop_detector.reset();
+    }
+
+    /// Reads memory through the bus without side effects
+    pub fn read_memory(&self, address: u32, length: usize) -> Vec<u8> {
+        (0..length as u32)
+            .map(|offset| {
+                let address = address.wrapping_add(offset);
+                let halfword = self.system.bus.peek_u16(address & 0xFFFF_FFFE);
+
+                if address & 1 == 0 {
+                    halfword as u8
+                } else {
+                    (halfword >> 8) as u8
+                }
+            })
+            .collect()
+    }
+
+    /// Writes memory through the bus, as if written by the CPU. ROM is not writable
+    pub fn write_memory(&mut self, address: u32, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().enumerate() {
+            self.system
+                .bus
+                .set_u8(address.wrapping_add(offset as u32), *byte);
+        }
+
+        self.idle_loop_detector.reset();
+    }
+
+    /// Attaches a sink that receives every executed instruction matching `filter`. Tracing starts immediately.
+    ///
+    /// Replaces any previously attached sink.
+    pub fn attach_trace_sink(&mut self, sink: Box<dyn TraceSink>, filter: TraceFilter) {
+        self.tracer = Some(Tracer {
+            sink,
+            filter,
+            running: true,
+        });
+    }
+
+    /// Flushes, detaches, and returns the current trace sink
+    pub fn detach_trace_sink(&mut self) -> io::Result<Option<Box<dyn TraceSink>>> {
+        let Some(mut tracer) = self.tracer.take() else {
+            return Ok(None);
+        };
+
+        tracer.sink.flush()?;
+
+        Ok(Some(tracer.sink))
+    }
+
+    /// Resumes tracing to the attached sink
+    pub fn start_trace(&mut self) {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.running = true;
+        }
+    }
+
+    /// Pauses tracing without detaching the sink, and flushes it
+    pub fn stop_trace(&mut self) -> io::Result<()> {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.running = false;
+
+            return tracer.sink.flush();
+        }
+
+        Ok(())
+    }
+
+    pub fn set_trace_filter(&mut self, filter: TraceFilter) {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.filter = filter;
+        }
+    }
+
+    /// Starts attributing executed cycles to guest PCs and call stacks, discarding any profile in progress
+    pub fn start_profiling(&mut self) {
+        self.profiler = Some(Profiler::new());
+    }
+
+    /// Stops profiling, returning the collected profile
+    pub fn stop_profiling(&mut self) -> Option<Profile> {
+        self.profiler.take().map(|profiler| profiler.into_profile())
+    }
+
+    /// Stops execution before the instruction at `pc` executes
+    pub fn add_breakpoint(&mut self, pc: u32) {
+        self.debugger.add_breakpoint(pc);
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: u32) -> bool {
+        self.debugger.remove_breakpoint(pc)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.debugger.clear_breakpoints();
+    }
+
+    /// Adds a watchpoint, returning an ID that can be used to remove it
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+        self.debugger
+            .add_watchpoint(&mut self.system.bus, watchpoint)
+    }
+
+    pub fn remove_watchpoint(&mut self, id: usize) -> bool {
+        self.debugger.remove_watchpoint(&mut self.system.bus, id)
+    }
+
+    /// Executes a single instruction on the next `run_video_frame`/`run_audio_frame`, then stops with `StopReason::Step`
+    pub fn step_instruction(&mut self) {
+        self.debugger.step_instruction();
+    }
+
+    /// Like `step_instruction`, but if the next instruction is a `JAL`, stops once the called function returns
+    pub fn step_over(&mut self) {
+        self.debugger.step_over(&self.system.cpu, &self.system.bus);
+    }
+
+    /// Runs until the current function returns with `JMP [r31]`, then stops with `StopReason::Step`
+    pub fn run_until_return(&mut self) {
+        self.debugger.run_until_return();
+    }
+
+    pub fn cancel_step(&mut self) {
+        self.debugger.cancel_step();
+    }
+
+    /// Executes a single instruction, or while halted, a single cycle.
+    ///
+    /// If idle skipping is enabled, a halted CPU or idle loop instead runs up to the next bus event, or until
+    /// `audio_buffer_size` samples have been output
+    fn system_tick(
+        &mut self,
+        emu_audio_sink: &mut SimpleAudioFrameSink,
+        inputs: &GamepadInputs,
+        audio_buffer_size: Option<usize>,
+    ) -> Option<StopReason> {
+        let debugging = self.debugger.is_active(&self.system.bus);
+
+        if debugging {
+            if let Some(stop_reason) = self
+                .debugger
+                .before_step(&self.system.cpu, &self.system.bus)
+            {
+                return Some(stop_reason);
+            }
+        }
+
+        if let Some(tracer) = &mut self.tracer {
+            if tracer.running && self.system.cpu.at_instruction_boundary() {
+                tracer.trace(self.system.cpu.registers(), self.cycle_count);
+            }
+        }
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.before_step(&self.system.cpu, &self.system.bus);
+        }
+
+        let step_pc = self.system.cpu.pc();
+        let idle_skipping = self.idle_skipping && !debugging;
+
+        let step_cycle_count = if idle_skipping && self.system.cpu.is_halted() {
+            // Nothing can happen until an interrupt wakes the CPU
+            let idle_cycle_limit = self.idle_cycle_limit(emu_audio_sink, audio_buffer_size);
+
+            self.system.bus.cycles_until_event().min(idle_cycle_limit)
+        } else {
+            self.system.cpu.step(&mut self.system.bus)
+        };
 
         self.cycle_count += step_cycle_count;
 
-        if let Some(request) = self
-            .system
+        if let Some(profiler) = &mut self.profiler {
+            profiler.after_step(&self.system.cpu, step_cycle_count);
+        }
+
+        self.system
             .bus
-            .step(step_cycle_count, emu_audio_sink, inputs)
-        {
+            .step(step_cycle_count, emu_audio_sink, inputs);
+
+        if let Some(request) = self.system.bus.pending_interrupt() {
+            self.system.cpu.request_interrupt(request);
+        }
+
+        if let Some(address) = self.system.bus.vip.take_invalid_write() {
+            self.system
+                .cpu
+                .crash(CrashCause::InvalidVipWrite { address });
+        }
+
+        // Skipped iterations cannot be traced or profiled
+        let tracing = self.tracer.as_ref().is_some_and(|tracer| tracer.running);
+
+        if idle_skipping && !tracing && self.profiler.is_none() {
+            self.skip_idle_loop(step_pc, emu_audio_sink, inputs, audio_buffer_size);
+        }
+
+        if debugging {
+            return self
+                .debugger
+                .after_step(&self.system.cpu, &mut self.system.bus);
+        }
+
+        None
+    }
+
+    fn skip_idle_loop(
+        &mut self,
+        step_pc: u32,
+        emu_audio_sink: &mut SimpleAudioFrameSink,
+        inputs: &GamepadInputs,
+        audio_buffer_size: Option<usize>,
+    ) {
+        if audio_buffer_size.is_some_and(|size| emu_audio_sink.inner.len() >= size) {
+            // The step filled the buffer
+            return;
+        }
+
+        // The step has already output samples, so the limit is taken after it
+        let idle_cycle_limit = self.idle_cycle_limit(emu_audio_sink, audio_buffer_size);
+
+        let Some(skip) = self.idle_loop_detector.after_step(
+            step_pc,
+            &self.system.cpu,
+            &self.system.bus,
+            self.cycle_count,
+            idle_cycle_limit,
+        ) else {
+            return;
+        };
+
+        self.cycle_count += skip.cycles;
+
+        self.system
+            .cpu
+            .record_skipped_iterations(self.idle_loop_detector.loop_pcs(), skip.iterations);
+
+        self.system.bus.step(skip.cycles, emu_audio_sink, inputs);
+
+        if let Some(request) = self.system.bus.pending_interrupt() {
+            // A masked interrupt, as the loop would not have been skipped otherwise
             self.system.cpu.request_interrupt(request);
         }
+
+        self.idle_loop_detector.skipped(&skip, &self.system.bus);
+    }
+
+    /// Number of cycles idle skipping can run before the audio buffer of `audio_buffer_size` samples is filled
+    fn idle_cycle_limit(
+        &self,
+        emu_audio_sink: &SimpleAudioFrameSink,
+        audio_buffer_size: Option<usize>,
+    ) -> usize {
+        match audio_buffer_size {
+            Some(size) => self
+                .system
+                .bus
+                .cycles_until_audio_samples(size - emu_audio_sink.inner.len()),
+            None => usize::MAX,
+        }
     }
 
     fn frame_tick(&mut self) -> Option<VideoFrame> {
@@ -215,6 +591,10 @@ impl VirtualFriend {
 
                 self.savestate.frame_tick(&self.system);
 
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.end_frame();
+                }
+
                 return Some(VideoFrame {
                     left: self.system.bus.vip.left_rendered_framebuffer.clone(),
                     right: self.system.bus.vip.right_rendered_framebuffer.clone(),
@@ -0,0 +1,205 @@
+// Differential testing against a reference trace, usually produced by Mednafen. The emulator is stepped one
+// instruction at a time alongside the trace, stopping at the first instruction whose register state differs.
+//
+// For traces from Mednafen, create the `VirtualFriend` with `PowerOnState::Mednafen` so that the initial
+// register and WRAM contents match.
+
+use std::{cell::RefCell, collections::VecDeque, fmt, io, rc::Rc};
+
+use crate::{
+    crash::CrashReport,
+    disasm::Instruction,
+    gamepad::GamepadInputs,
+    trace::{registers_to_words, TraceFilter, TraceRecord, TraceSink, TRACE_REGISTER_NAMES},
+    VirtualFriend,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegisterDifference {
+    /// Register name, as written in Mednafen's trace log
+    pub name: &'static str,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+/// An executed instruction and the registers immediately before it executed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub record: TraceRecord,
+    pub instruction: Instruction,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the first mismatched record in the trace
+    pub index: usize,
+    pub expected: TraceRecord,
+    pub actual: TraceRecord,
+    pub differences: Vec<RegisterDifference>,
+    /// The most recently executed instructions, oldest first. The last entry is the instruction that produced the
+    /// mismatched state
+    pub history: Vec<HistoryEntry>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Diverged at trace record {} (TStamp={:06X})",
+            self.index, self.expected.cycle_count
+        )?;
+
+        match self.history.last() {
+            Some(entry) => writeln!(
+                f,
+                "After {:08X}  {}",
+                entry.instruction.address, entry.instruction
+            )?,
+            None => writeln!(f, "Before the first instruction")?,
+        }
+
+        writeln!(f, "Registers:")?;
+
+        for difference in &self.differences {
+            writeln!(
+                f,
+                "  {:<5} expected {:08X}, actual {:08X}",
+                difference.name, difference.expected, difference.actual
+            )?;
+        }
+
+        writeln!(f, "Previous instructions:")?;
+
+        for entry in &self.history {
+            writeln!(
+                f,
+                "  {:08X}  {}",
+                entry.instruction.address, entry.instruction
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LockstepResult {
+    /// Every record in the trace matched
+    Matched {
+        count: usize,
+    },
+    Diverged(Divergence),
+    /// The emulator crashed before reaching the record at `index`
+    Crashed {
+        index: usize,
+        report: CrashReport,
+    },
+}
+
+/// Runs `virtualfriend` against `trace` until the first mismatched record or the end of the trace, holding `inputs`.
+///
+/// All registers in the trace are compared, but not `TStamp`, as Mednafen resets it every frame. `history_length`
+/// instructions are retained for the divergence report. Replaces any attached trace sink
+pub fn run_lockstep(
+    virtualfriend: &mut VirtualFriend,
+    trace: impl IntoIterator<Item = io::Result<TraceRecord>>,
+    inputs: GamepadInputs,
+    history_length: usize,
+) -> io::Result<LockstepResult> {
+    let records = Rc::new(RefCell::new(VecDeque::new()));
+
+    virtualfriend.attach_trace_sink(
+        Box::new(QueueSink {
+            records: records.clone(),
+        }),
+        TraceFilter::default(),
+    );
+
+    let result = compare(virtualfriend, trace, inputs, history_length, &records);
+
+    virtualfriend.detach_trace_sink()?;
+
+    result
+}
+
+fn compare(
+    virtualfriend: &mut VirtualFriend,
+    trace: impl IntoIterator<Item = io::Result<TraceRecord>>,
+    inputs: GamepadInputs,
+    history_length: usize,
+    records: &RefCell<VecDeque<TraceRecord>>,
+) -> io::Result<LockstepResult> {
+    let mut history = VecDeque::with_capacity(history_length);
+    let mut count = 0;
+
+    for (index, expected) in trace.into_iter().enumerate() {
+        let expected = expected?;
+
+        let actual = loop {
+            if let Some(record) = records.borrow_mut().pop_front() {
+                break record;
+            }
+
+            virtualfriend.step_instruction();
+
+            if let Some(report) = virtualfriend.run_video_frame(inputs).crash {
+                return Ok(LockstepResult::Crashed { index, report });
+            }
+        };
+
+        let differences = diff_registers(&expected, &actual);
+
+        if !differences.is_empty() {
+            return Ok(LockstepResult::Diverged(Divergence {
+                index,
+                expected,
+                actual,
+                differences,
+                history: history.into(),
+            }));
+        }
+
+        if history_length > 0 {
+            if history.len() == history_length {
+                history.pop_front();
+            }
+
+            history.push_back(HistoryEntry {
+                record: actual,
+                instruction: virtualfriend.disassemble(actual.registers.pc, 1)[0],
+            });
+        }
+
+        count += 1;
+    }
+
+    Ok(LockstepResult::Matched { count })
+}
+
+fn diff_registers(expected: &TraceRecord, actual: &TraceRecord) -> Vec<RegisterDifference> {
+    let expected_words = registers_to_words(&expected.registers);
+    let actual_words = registers_to_words(&actual.registers);
+
+    expected_words
+        .iter()
+        .zip(actual_words.iter())
+        .zip(TRACE_REGISTER_NAMES.iter())
+        .filter(|((expected, actual), _)| expected != actual)
+        .map(|((expected, actual), name)| RegisterDifference {
+            name,
+            expected: *expected,
+            actual: *actual,
+        })
+        .collect()
+}
+
+/// Buffers traced records until the lockstep loop consumes them
+struct QueueSink {
+    records: Rc<RefCell<VecDeque<TraceRecord>>>,
+}
+
+impl TraceSink for QueueSink {
+    fn record(&mut self, record: &TraceRecord) {
+        self.records.borrow_mut().push_back(*record);
+    }
+}
@@ -12,3 +12,19 @@
 //         ()
 //     };
 // }
+
+/// Prints diagnostics about the running game, which are dropped unless the `stdout` feature is enabled
+#[cfg(feature = "stdout")]
+macro_rules! log {
+    ($($rest:tt)*) => {
+        std::println!($($rest)*)
+    }
+}
+
+#[cfg(not(feature = "stdout"))]
+macro_rules! log {
+    ($($rest:tt)*) => {
+        // Do nothing
+        ()
+    };
+}
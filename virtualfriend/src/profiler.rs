@@ -0,0 +1,269 @@
+// Guest code profiler. Attributes the cycles consumed by each CPU step to the executing PC, and to a call stack inferred
+// from JAL, `JMP [r31]`, exception entry, and RETI. Cycles spent in HALT are attributed to a `[halted]` leaf under the
+// stack that halted.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+use crate::{
+    bus::Bus,
+    cpu_v810::CpuV810,
+    disasm::{self, Operands, Operation},
+    instruction::Opcode,
+};
+
+/// Interrupt and exception handlers start at or above this address
+const VECTOR_BASE_ADDRESS: u32 = 0xFFFF_FE00;
+
+/// Calls that never return would otherwise grow the stack without bound. The outermost frames are dropped past this
+const MAX_STACK_DEPTH: usize = 256;
+
+/// Number of functions listed per frame by `Profile::write_frame_summary`
+const SUMMARY_FUNCTION_COUNT: usize = 3;
+
+/// Cycles consumed during a single video frame
+#[derive(Clone, Debug, Default)]
+pub struct FrameSummary {
+    pub cycles: u64,
+    pub halted_cycles: u64,
+    /// Cycles spent in each function, excluding its callees, busiest first. `None` is code outside of any inferred call
+    pub function_cycles: Vec<(Option<u32>, u64)>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub total_cycles: u64,
+    pub halted_cycles: u64,
+    /// Cycles spent executing the instruction at each address
+    pub pc_cycles: HashMap<u32, u64>,
+    /// Cycles spent executing with each call stack, outermost function first
+    pub stack_cycles: HashMap<Vec<u32>, u64>,
+    /// Cycles spent halted with each call stack
+    pub halted_stack_cycles: HashMap<Vec<u32>, u64>,
+    /// One entry per completed video frame
+    pub frames: Vec<FrameSummary>,
+}
+
+impl Profile {
+    /// Writes the call stacks in the folded format read by flamegraph tools. Each line is the `;` separated stack,
+    /// starting with `[root]`, followed by the cycle count. Functions are named by their hex address
+    pub fn write_folded(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut lines = Vec::new();
+
+        for (stack, cycles) in &self.stack_cycles {
+            lines.push((folded_stack(stack), *cycles));
+        }
+
+        for (stack, cycles) in &self.halted_stack_cycles {
+            lines.push((format!("{};[halted]", folded_stack(stack)), *cycles));
+        }
+
+        lines.sort();
+
+        for (stack, cycles) in lines {
+            writeln!(writer, "{stack} {cycles}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a line per video frame with its halted time and busiest functions. A frame that is rarely halted is
+    /// close to exceeding its cycle budget
+    pub fn write_frame_summary(&self, writer: &mut impl Write) -> io::Result<()> {
+        for (i, frame) in self.frames.iter().enumerate() {
+            let halted_percent = if frame.cycles > 0 {
+                frame.halted_cycles as f64 * 100.0 / frame.cycles as f64
+            } else {
+                0.0
+            };
+
+            write!(
+                writer,
+                "Frame {i}: {} cycles, {halted_percent:.1}% halted",
+                frame.cycles
+            )?;
+
+            for (j, (function, cycles)) in frame
+                .function_cycles
+                .iter()
+                .take(SUMMARY_FUNCTION_COUNT)
+                .enumerate()
+            {
+                let separator = if j == 0 { ", busiest:" } else { "," };
+
+                write!(
+                    writer,
+                    "{separator} {} ({cycles})",
+                    function_name(*function)
+                )?;
+            }
+
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn function_name(function: Option<u32>) -> String {
+    match function {
+        Some(address) => format!("{address:08X}"),
+        None => "[root]".to_string(),
+    }
+}
+
+fn folded_stack(stack: &[u32]) -> String {
+    let mut folded = String::from("[root]");
+
+    for address in stack {
+        folded += &format!(";{address:08X}");
+    }
+
+    folded
+}
+
+/// How an instruction affects the inferred call stack
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StackEffect {
+    None,
+    Call,
+    Return,
+}
+
+pub(crate) struct Profiler {
+    profile: Profile,
+
+    stack: Vec<u32>,
+
+    /// The PC after the previous step. If the next step starts elsewhere, an interrupt was taken in between
+    expected_pc: Option<u32>,
+
+    step_pc: u32,
+    step_halted: bool,
+    step_effect: StackEffect,
+
+    frame: FrameSummary,
+    frame_function_cycles: HashMap<Option<u32>, u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            profile: Profile::default(),
+            stack: Vec::new(),
+            expected_pc: None,
+            step_pc: 0,
+            step_halted: false,
+            step_effect: StackEffect::None,
+            frame: FrameSummary::default(),
+            frame_function_cycles: HashMap::new(),
+        }
+    }
+
+    pub fn into_profile(self) -> Profile {
+        self.profile
+    }
+
+    pub fn before_step(&mut self, cpu: &CpuV810, bus: &Bus) {
+        let pc = cpu.pc();
+
+        if self
+            .expected_pc
+            .is_some_and(|expected_pc| expected_pc != pc)
+        {
+            if pc >= VECTOR_BASE_ADDRESS {
+                // Interrupt
+                self.push(pc);
+            } else {
+                // The registers were replaced, such as by loading a savestate
+                self.stack.clear();
+            }
+        }
+
+        self.step_pc = pc;
+        self.step_halted = cpu.is_halted();
+
+        self.step_effect = if cpu.at_instruction_boundary() {
+            let instruction =
+                disasm::decode(pc, bus.peek_u16(pc), bus.peek_u16(pc.wrapping_add(2)));
+
+            match (instruction.operation, instruction.operands) {
+                (Operation::Opcode(Opcode::Jal), _) => StackEffect::Call,
+                (Operation::Opcode(Opcode::Jmp), Operands::Jump { reg1: 31 })
+                | (Operation::Opcode(Opcode::Reti), _) => StackEffect::Return,
+                _ => StackEffect::None,
+            }
+        } else {
+            StackEffect::None
+        };
+    }
+
+    pub fn after_step(&mut self, cpu: &CpuV810, cycles: usize) {
+        let cycles = cycles as u64;
+
+        self.profile.total_cycles += cycles;
+        self.frame.cycles += cycles;
+
+        if self.step_halted {
+            self.profile.halted_cycles += cycles;
+            self.frame.halted_cycles += cycles;
+
+            add_stack_cycles(&mut self.profile.halted_stack_cycles, &self.stack, cycles);
+        } else {
+            *self.profile.pc_cycles.entry(self.step_pc).or_default() += cycles;
+            *self
+                .frame_function_cycles
+                .entry(self.stack.last().copied())
+                .or_default() += cycles;
+
+            add_stack_cycles(&mut self.profile.stack_cycles, &self.stack, cycles);
+        }
+
+        let pc = cpu.pc();
+
+        match self.step_effect {
+            StackEffect::Call => self.push(pc),
+            StackEffect::Return => {
+                self.stack.pop();
+            }
+            StackEffect::None => {
+                if pc >= VECTOR_BASE_ADDRESS && self.step_pc < VECTOR_BASE_ADDRESS {
+                    // Exception
+                    self.push(pc);
+                }
+            }
+        }
+
+        self.expected_pc = Some(pc);
+    }
+
+    pub fn end_frame(&mut self) {
+        let mut frame = std::mem::take(&mut self.frame);
+
+        frame.function_cycles = self.frame_function_cycles.drain().collect();
+        frame
+            .function_cycles
+            .sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        self.profile.frames.push(frame);
+    }
+
+    fn push(&mut self, function: u32) {
+        if self.stack.len() == MAX_STACK_DEPTH {
+            self.stack.remove(0);
+        }
+
+        self.stack.push(function);
+    }
+}
+
+fn add_stack_cycles(stack_cycles: &mut HashMap<Vec<u32>, u64>, stack: &[u32], cycles: u64) {
+    match stack_cycles.get_mut(stack) {
+        Some(total) => *total += cycles,
+        None => {
+            stack_cycles.insert(stack.to_vec(), cycles);
+        }
+    }
+}
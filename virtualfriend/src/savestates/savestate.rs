@@ -1,12 +1,13 @@
-use std::{
-    fs::read,
-    time::{SystemTime, UNIX_EPOCH},
-};
+use std::fs::read;
 
 use savefile::{load_from_mem, save_to_mem};
 
 use crate::System;
 
+/// Version of the savestate contents, incremented whenever the saved state changes. Fields added since version 0
+/// are marked with the version that added them, and older savestates load them with their power on values
+const SAVESTATE_VERSION: u32 = 6;
+
 pub struct UnparsedSavestate {
     pub left_frame: Vec<u8>,
     pub right_frame: Vec<u8>,
@@ -51,16 +52,15 @@ impl UnparsedSavestate {
         Self {
             left_frame: contents.bus.vip.left_rendered_framebuffer.clone(),
             right_frame: contents.bus.vip.right_rendered_framebuffer.clone(),
-            timestamp_s: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            contents: save_to_mem(0, contents).expect("Could not generate savestate"),
+            timestamp_s: contents.config().clock.now_s(),
+            contents: save_to_mem(SAVESTATE_VERSION, contents)
+                .expect("Could not generate savestate"),
         }
     }
 
     pub(crate) fn contents(&self) -> System {
-        load_from_mem::<System>(&self.contents, 0).expect("Could not parse savestate contents")
+        load_from_mem::<System>(&self.contents, SAVESTATE_VERSION)
+            .expect("Could not parse savestate contents")
     }
 
     pub fn data(&self) -> Vec<u8> {
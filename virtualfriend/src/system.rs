@@ -1,34 +1,82 @@
+use rand::{thread_rng, Rng};
+
 use crate::{
-    bus::Bus, cartridge::Cartridge, cpu_v810::CpuV810, hardware::Hardware, vip::VIP, vsu::VSU,
+    bus::Bus,
+    cartridge::Cartridge,
+    config::{EmulatorConfig, PowerOnState},
+    cpu_v810::CpuV810,
+    hardware::Hardware,
+    vip::VIP,
+    vsu::VSU,
 };
 
 #[derive(Savefile)]
 pub(crate) struct System {
     pub cpu: CpuV810,
     pub bus: Bus,
-}
 
-impl System {
-    pub fn new(vec: Vec<u8>) -> Self {
-        println!("Loading ROM");
+    #[savefile_ignore]
+    #[savefile_introspect_ignore]
+    #[savefile_default_fn = "default_config"]
+    config: EmulatorConfig,
+}
 
-        let rom = Cartridge::load_from_vec(vec);
+fn default_config() -> EmulatorConfig {
+    EmulatorConfig::default()
+}
 
+impl System {
+    pub fn new(cart: Cartridge, config: EmulatorConfig) -> Self {
         let cpu = CpuV810::new();
 
         let vip = VIP::new();
         let vsu = VSU::new();
 
         let hardware = Hardware::new();
-        let bus = Bus::new(rom, vip, vsu, hardware);
+        let bus = Bus::new(cart, vip, vsu, hardware);
+
+        let mut system = Self { cpu, bus, config };
+
+        match system.config.power_on {
+            PowerOnState::Random => system.bus.randomize_wram(thread_rng().gen()),
+            PowerOnState::RandomSeeded(seed) => system.bus.randomize_wram(seed),
+            PowerOnState::Zeroed => {}
+            // Mednafen clears WRAM
+            PowerOnState::Mednafen => {
+                system.cpu.mednafen_power_on();
+                system.bus.cart.mednafen_power_on();
+            }
+        }
+
+        system.apply_config();
 
-        Self { cpu, bus }
+        system
     }
 
-    pub fn replace_from_savestate(&mut self, system: System, rom: Vec<u8>) {
+    pub fn config(&self) -> &EmulatorConfig {
+        &self.config
+    }
+
+    pub fn replace_from_savestate(&mut self, system: System) {
+        let watchpoints = std::mem::take(&mut self.bus.watchpoints);
+
+        let mut bus = system.bus;
+
+        // The savestate only holds the mutable state of the cartridge. Keep the running mapper, and its ROM
+        std::mem::swap(&mut self.bus.cart, &mut bus.cart);
+        bus.cart.restore_from(&self.bus.cart);
+
         self.cpu = system.cpu;
-        self.bus = system.bus;
+        self.bus = bus;
+
+        // Debugger state is not part of the savestate
+        self.bus.watchpoints = watchpoints;
+
+        self.apply_config();
+    }
 
-        self.bus.cart.populate_rom(rom);
+    fn apply_config(&mut self) {
+        self.cpu
+            .set_illegal_opcode_behavior(self.config.illegal_opcode);
     }
 }
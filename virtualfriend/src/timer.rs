@@ -15,7 +15,7 @@ pub struct Timer {
 
     tick_interval_counter: usize,
 
-    /// Reload value was set to zero by software, interrupt is queued to next tick
+    /// Reload value was set to zero by software, zero status is raised on the next tick
     deferred_interrupt: bool,
 }
 
@@ -91,80 +91,82 @@ impl Timer {
 
         if value.did_zero_clear() {
             // Write to Z-Stat-Clr
+            // Acknowledges the interrupt by dropping the interrupt line
             self.did_zero = false;
-
-            // TODO: Do we need to do something special to acknowledge the interrupt?
         }
 
         self.interrupt_enabled = value.interrupt_enabled();
         self.timer_interval = value.timer_interval();
     }
 
-    /// Run the timer for 1 cycle.
+    /// Run the timer for `cycles_to_run` cycles.
     ///
     /// Timer does not tick every cycle, so this will run every so often.
-    ///
-    /// Returns true if an interrupt should fire.
-    pub fn step(&mut self, cycles_to_run: usize) -> bool {
+    pub fn step(&mut self, cycles_to_run: usize) {
         if !self.enabled {
             // Do nothing
-            return false;
+            return;
         }
 
-        let was_deferred_interrupt = self.deferred_interrupt;
-
         if self.deferred_interrupt {
             self.deferred_interrupt = false;
+            self.did_zero = true;
         }
 
-        let mut request_interrupt = false;
-
         for _ in 0..cycles_to_run {
-            let required_cycle_count = if self.timer_interval {
-                TIMER_MIN_INTERVAL_CYCLE_COUNT
-            } else {
-                TIMER_MIN_INTERVAL_CYCLE_COUNT * 5
-            };
-
             self.tick_interval_counter += 1;
 
-            if self.tick_interval_counter >= required_cycle_count {
+            if self.tick_interval_counter >= self.interval_cycle_count() {
                 // Fire timer tick
                 self.tick_interval_counter = 0;
 
-                if self.tick() {
-                    // println!("Timer fire");
-                    // This technically allows the interrupt to become desynced with the timer, as it fires, but the timer can keep running
-                    request_interrupt = self.interrupt_enabled;
-                }
+                self.tick();
             }
         }
+    }
 
-        request_interrupt || was_deferred_interrupt
+    /// The interrupt line is held from the counter reaching zero until Z-Stat-Clr is written, while interrupts are
+    /// enabled
+    pub fn interrupt_line(&self) -> bool {
+        self.did_zero && self.interrupt_enabled
     }
 
-    /// Tick the timer.
-    ///
-    /// Returns true if an interrupt should fire
-    fn tick(&mut self) -> bool {
+    /// Number of cycles until the counter next changes, or an interrupt is raised
+    pub fn cycles_until_event(&self) -> usize {
+        if !self.enabled {
+            return usize::MAX;
+        }
+
+        if self.deferred_interrupt {
+            return 1;
+        }
+
+        self.interval_cycle_count()
+            .saturating_sub(self.tick_interval_counter)
+            .max(1)
+    }
+
+    fn interval_cycle_count(&self) -> usize {
+        if self.timer_interval {
+            TIMER_MIN_INTERVAL_CYCLE_COUNT
+        } else {
+            TIMER_MIN_INTERVAL_CYCLE_COUNT * 5
+        }
+    }
+
+    /// Tick the timer
+    fn tick(&mut self) {
         if self.counter == 0 {
             // Reset counter
-            // Separating this from the interrupt (1) case allows setting the timer to 0
+            // Separating this from the zero (1) case allows setting the timer to 0
             // to not infinitely interrupt
             self.counter = self.reload;
-
-            false
         } else if self.counter == 1 {
             // Value will be 0 after this
-            // Fire interrupt and zero
             self.counter -= 1;
             self.did_zero = true;
-
-            true
         } else {
             self.counter -= 1;
-
-            false
         }
     }
 }
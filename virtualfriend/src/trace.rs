@@ -0,0 +1,499 @@
+use std::{
+    io::{self, BufRead, Read, Write},
+    ops::{Range, RangeInclusive},
+};
+
+use crate::cpu_internals::Registers;
+
+/// The CPU state immediately before an instruction executes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceRecord {
+    /// Total cycles elapsed since power on
+    pub cycle_count: usize,
+    pub registers: Registers,
+}
+
+/// Receives a record for every executed instruction while tracing is running.
+///
+/// Steps spent in HALT and the continuation steps of a bit string instruction are not traced, matching Mednafen.
+pub trait TraceSink {
+    fn record(&mut self, record: &TraceRecord);
+
+    /// Called when tracing is stopped or the sink is detached
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Restricts which instructions are passed to the `TraceSink`
+#[derive(Clone, Debug, Default)]
+pub struct TraceFilter {
+    /// Only trace instructions whose address falls in this range
+    pub pc_range: Option<RangeInclusive<u32>>,
+    /// Only trace instructions starting within this range of elapsed cycles
+    pub cycle_range: Option<Range<usize>>,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, pc: u32, cycle_count: usize) -> bool {
+        if let Some(pc_range) = &self.pc_range {
+            if !pc_range.contains(&pc) {
+                return false;
+            }
+        }
+
+        if let Some(cycle_range) = &self.cycle_range {
+            if !cycle_range.contains(&cycle_count) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+pub(crate) struct Tracer {
+    pub sink: Box<dyn TraceSink>,
+    pub filter: TraceFilter,
+    pub running: bool,
+}
+
+impl Tracer {
+    pub fn trace(&mut self, registers: Registers, cycle_count: usize) {
+        if !self.filter.matches(registers.pc, cycle_count) {
+            return;
+        }
+
+        self.sink.record(&TraceRecord {
+            cycle_count,
+            registers,
+        });
+    }
+}
+
+/// PC, r1-r31, and the 9 traced system registers
+pub(crate) const TRACE_WORD_COUNT: usize = 41;
+
+/// The name Mednafen's trace log uses for each register, in the order used by `registers_to_words`
+pub(crate) const TRACE_REGISTER_NAMES: [&str; TRACE_WORD_COUNT] = [
+    "PC", "R1", "FP", "SP", "GP", "TP", "R6", "R7", "R8", "R9", "R10", "R11", "R12", "R13", "R14",
+    "R15", "R16", "R17", "R18", "R19", "R20", "R21", "R22", "R23", "R24", "R25", "R26", "R27",
+    "R28", "R29", "R30", "LP", "EIPC", "EIPSW", "FEPC", "FEPSW", "ECR", "PSW", "TKCW", "CHCW",
+    "ADTRE",
+];
+
+/// Writes one line per instruction in the format of Mednafen's CPU trace log, so traces can be diffed directly
+pub struct MednafenTraceWriter<W: Write> {
+    writer: W,
+
+    /// Append ` TStamp=` with the elapsed cycle count to each line
+    include_timestamp: bool,
+
+    /// The first write error encountered. Writing stops after an error
+    error: Option<io::Error>,
+}
+
+impl<W: Write> MednafenTraceWriter<W> {
+    pub fn new(writer: W, include_timestamp: bool) -> Self {
+        MednafenTraceWriter {
+            writer,
+            include_timestamp,
+            error: None,
+        }
+    }
+
+    /// Flushes and returns the underlying writer, or the first error encountered while tracing
+    pub fn into_inner(mut self) -> io::Result<W> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn write_record(&mut self, record: &TraceRecord) -> io::Result<()> {
+        let r = &record.registers;
+        let gpr = &r.general_purpose;
+
+        write!(
+            self.writer,
+            "PC={:08X} R1={:08X} FP={:08X} SP={:08X} GP={:08X} TP={:08X}",
+            r.pc, gpr[1], gpr[2], gpr[3], gpr[4], gpr[5]
+        )?;
+
+        for (i, value) in gpr.iter().enumerate().take(31).skip(6) {
+            write!(self.writer, " R{i}={value:08X}")?;
+        }
+
+        write!(
+            self.writer,
+            " LP={:08X} EIPC={:08X} EIPSW={:08X} FEPC={:08X} FEPSW={:08X} ECR={:08X} PSW={:08X} PIR=00005347 TKCW={:08X} CHCW={:08X} ADTRE={:08X}",
+            gpr[31], r.eipc, r.eipsw, r.fepc, r.fepsw, r.ecr, r.psw, r.tkcw, r.chcw, r.adtre
+        )?;
+
+        if self.include_timestamp {
+            // TODO: Mednafen seems to wrap cycle count at the arbitrary? value 0x061200
+            write!(self.writer, " TStamp={:06X}", record.cycle_count)?;
+        }
+
+        writeln!(self.writer)
+    }
+}
+
+impl<W: Write> TraceSink for MednafenTraceWriter<W> {
+    fn record(&mut self, record: &TraceRecord) {
+        if self.error.is_some() {
+            return;
+        }
+
+        if let Err(error) = self.write_record(record) {
+            self.error = Some(error);
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        self.writer.flush()
+    }
+}
+
+/// Reads a trace written by `MednafenTraceWriter` or Mednafen's CPU trace log. `TStamp` is optional, and is read as
+/// the record's `cycle_count`
+pub struct MednafenTraceReader<R: BufRead> {
+    reader: R,
+
+    line: String,
+    line_number: usize,
+}
+
+impl<R: BufRead> MednafenTraceReader<R> {
+    pub fn new(reader: R) -> Self {
+        MednafenTraceReader {
+            reader,
+            line: String::new(),
+            line_number: 0,
+        }
+    }
+
+    /// Reads the next record, skipping blank lines. Returns `Ok(None)` at the end of the trace
+    pub fn read_record(&mut self) -> io::Result<Option<TraceRecord>> {
+        loop {
+            self.line.clear();
+
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+
+            self.line_number += 1;
+
+            if !self.line.trim().is_empty() {
+                break;
+            }
+        }
+
+        parse_mednafen_record(&self.line)
+            .map(Some)
+            .map_err(|message| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Line {}: {message}", self.line_number),
+                )
+            })
+    }
+}
+
+impl<R: BufRead> Iterator for MednafenTraceReader<R> {
+    type Item = io::Result<TraceRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+fn parse_mednafen_record(line: &str) -> Result<TraceRecord, String> {
+    let mut words = [None; TRACE_WORD_COUNT];
+    let mut cycle_count = 0;
+
+    for field in line.split_whitespace() {
+        let (name, value) = field
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid field {field}"))?;
+
+        let value =
+            u32::from_str_radix(value, 16).map_err(|_| format!("Invalid value in {field}"))?;
+
+        match name {
+            "TStamp" => cycle_count = value as usize,
+            // Always the same value
+            "PIR" => {}
+            _ => {
+                let index = TRACE_REGISTER_NAMES
+                    .iter()
+                    .position(|register_name| *register_name == name)
+                    .ok_or_else(|| format!("Unknown register {name}"))?;
+
+                words[index] = Some(value);
+            }
+        }
+    }
+
+    let mut values = [0; TRACE_WORD_COUNT];
+
+    for (i, word) in words.iter().enumerate() {
+        values[i] = word.ok_or_else(|| format!("Missing register {}", TRACE_REGISTER_NAMES[i]))?;
+    }
+
+    Ok(TraceRecord {
+        cycle_count,
+        registers: words_to_registers(&values),
+    })
+}
+
+const BINARY_TRACE_MAGIC: &[u8; 4] = b"VFTR";
+const BINARY_TRACE_VERSION: u8 = 1;
+
+/// Writes a compact binary trace.
+///
+/// After a 5 byte header (`VFTR` and a version byte), each record is:
+///
+/// * The cycle delta from the previous record as a LEB128 varint
+/// * A LEB128 bitmask of which registers changed since the previous record. Bit 0 is PC, bits 1-31 are r1-r31, and
+///   bits 32-40 are EIPC, EIPSW, FEPC, FEPSW, ECR, PSW, TKCW, CHCW, ADTRE
+/// * The new value of each changed register as a little endian u32, in bit order
+pub struct BinaryTraceWriter<W: Write> {
+    writer: W,
+
+    wrote_header: bool,
+    last_cycle_count: usize,
+    last_words: [u32; TRACE_WORD_COUNT],
+
+    /// The first write error encountered. Writing stops after an error
+    error: Option<io::Error>,
+}
+
+impl<W: Write> BinaryTraceWriter<W> {
+    pub fn new(writer: W) -> Self {
+        BinaryTraceWriter {
+            writer,
+            wrote_header: false,
+            last_cycle_count: 0,
+            last_words: [0; TRACE_WORD_COUNT],
+            error: None,
+        }
+    }
+
+    /// Flushes and returns the underlying writer, or the first error encountered while tracing
+    pub fn into_inner(mut self) -> io::Result<W> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        self.write_header()?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.wrote_header {
+            self.wrote_header = true;
+
+            self.writer.write_all(BINARY_TRACE_MAGIC)?;
+            self.writer.write_all(&[BINARY_TRACE_VERSION])?;
+        }
+
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &TraceRecord) -> io::Result<()> {
+        self.write_header()?;
+
+        let words = registers_to_words(&record.registers);
+
+        let mut mask = 0_u64;
+        for (i, (word, last_word)) in words.iter().zip(self.last_words.iter()).enumerate() {
+            if word != last_word {
+                mask |= 1 << i;
+            }
+        }
+
+        let cycle_delta = record.cycle_count.wrapping_sub(self.last_cycle_count);
+
+        write_varint(&mut self.writer, cycle_delta as u64)?;
+        write_varint(&mut self.writer, mask)?;
+
+        for (i, word) in words.iter().enumerate() {
+            if mask & (1 << i) != 0 {
+                self.writer.write_all(&word.to_le_bytes())?;
+            }
+        }
+
+        self.last_cycle_count = record.cycle_count;
+        self.last_words = words;
+
+        Ok(())
+    }
+}
+
+impl<W: Write> TraceSink for BinaryTraceWriter<W> {
+    fn record(&mut self, record: &TraceRecord) {
+        if self.error.is_some() {
+            return;
+        }
+
+        if let Err(error) = self.write_record(record) {
+            self.error = Some(error);
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        self.writer.flush()
+    }
+}
+
+/// Reads a trace produced by `BinaryTraceWriter`
+pub struct BinaryTraceReader<R: Read> {
+    reader: R,
+
+    last_cycle_count: usize,
+    last_words: [u32; TRACE_WORD_COUNT],
+}
+
+impl<R: Read> BinaryTraceReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+
+        if &header[0..4] != BINARY_TRACE_MAGIC || header[4] != BINARY_TRACE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a VirtualFriend binary trace",
+            ));
+        }
+
+        Ok(BinaryTraceReader {
+            reader,
+            last_cycle_count: 0,
+            last_words: [0; TRACE_WORD_COUNT],
+        })
+    }
+
+    /// Reads the next record. Returns `Ok(None)` at the end of the trace
+    pub fn read_record(&mut self) -> io::Result<Option<TraceRecord>> {
+        let cycle_delta = match read_varint(&mut self.reader) {
+            Ok(value) => value,
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        let mask = read_varint(&mut self.reader)?;
+
+        for i in 0..TRACE_WORD_COUNT {
+            if mask & (1 << i) != 0 {
+                let mut bytes = [0; 4];
+                self.reader.read_exact(&mut bytes)?;
+
+                self.last_words[i] = u32::from_le_bytes(bytes);
+            }
+        }
+
+        self.last_cycle_count = self.last_cycle_count.wrapping_add(cycle_delta as usize);
+
+        Ok(Some(TraceRecord {
+            cycle_count: self.last_cycle_count,
+            registers: words_to_registers(&self.last_words),
+        }))
+    }
+}
+
+impl<R: Read> Iterator for BinaryTraceReader<R> {
+    type Item = io::Result<TraceRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+pub(crate) fn registers_to_words(registers: &Registers) -> [u32; TRACE_WORD_COUNT] {
+    let mut words = [0; TRACE_WORD_COUNT];
+
+    words[0] = registers.pc;
+    words[1..32].copy_from_slice(&registers.general_purpose[1..32]);
+    words[32] = registers.eipc;
+    words[33] = registers.eipsw;
+    words[34] = registers.fepc;
+    words[35] = registers.fepsw;
+    words[36] = registers.ecr;
+    words[37] = registers.psw;
+    words[38] = registers.tkcw;
+    words[39] = registers.chcw;
+    words[40] = registers.adtre;
+
+    words
+}
+
+fn words_to_registers(words: &[u32; TRACE_WORD_COUNT]) -> Registers {
+    let mut general_purpose = [0; 32];
+    general_purpose[1..32].copy_from_slice(&words[1..32]);
+
+    Registers {
+        pc: words[0],
+        general_purpose,
+        eipc: words[32],
+        eipsw: words[33],
+        fepc: words[34],
+        fepsw: words[35],
+        ecr: words[36],
+        psw: words[37],
+        tkcw: words[38],
+        chcw: words[39],
+        adtre: words[40],
+    }
+}
+
+fn write_varint(writer: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(reader: &mut impl Read) -> io::Result<u64> {
+    let mut value = 0_u64;
+    let mut shift = 0;
+
+    loop {
+        let mut byte = [0; 1];
+        reader.read_exact(&mut byte)?;
+
+        value |= ((byte[0] & 0x7F) as u64) << shift;
+
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+
+        shift += 7;
+
+        if shift >= 64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Varint is too long",
+            ));
+        }
+    }
+}
@@ -14,7 +14,7 @@ use crate::constants::{
     LEFT_FRAME_BUFFER_COMPLETE_CYCLE_OFFSET, LEFT_FRAME_BUFFER_CYCLE_OFFSET,
     RIGHT_FRAME_BUFFER_COMPLETE_CYCLE_OFFSET, RIGHT_FRAME_BUFFER_CYCLE_OFFSET,
 };
-use crate::constants::{FRAMEBUFFER_HEIGHT, SBOUT_HIGH_CYCLE_COUNT};
+use crate::constants::{FRAMEBUFFER_HEIGHT, SBOUT_HIGH_CYCLE_COUNT, VRAM_DRAWING_WAIT_CYCLE_COUNT};
 
 use super::drawing::draw_block_row;
 use super::util::{framebuffer_addresses, RenderState};
@@ -75,6 +75,12 @@ pub struct VIP {
     drawing_cycle_count: usize,
 
     frame_count: u8,
+
+    /// Address of the most recent write to unmapped VIP memory, which crashes the system
+    #[savefile_ignore]
+    #[savefile_introspect_ignore]
+    #[savefile_default_fn = "no_invalid_write"]
+    invalid_write: Option<u32>,
 }
 
 #[derive(PartialEq, Savefile)]
@@ -106,6 +112,10 @@ bitfield! {
     }
 }
 
+fn no_invalid_write() -> Option<u32> {
+    None
+}
+
 fn new_framebuffer() -> Vec<u8> {
     let mut framebuffer = Vec::with_capacity(DISPLAY_PIXEL_LENGTH);
 
@@ -141,6 +151,7 @@ impl VIP {
             in_drawing: false,
             drawing_cycle_count: 0,
             frame_count: 0,
+            invalid_write: None,
         }
     }
 
@@ -272,6 +283,12 @@ impl VIP {
         }
     }
 
+    /// Takes the address of a write to unmapped VIP memory since the last call, if any
+    #[inline(always)]
+    pub fn take_invalid_write(&mut self) -> Option<u32> {
+        self.invalid_write.take()
+    }
+
     pub fn set_bus(&mut self, address: u32, value: u16) {
         let address = address as usize;
 
@@ -356,10 +373,7 @@ impl VIP {
             0x5_F86C..=0x5_F86D => self.render_state.object_palette_control2.set(value),
             0x5_F86E..=0x5_F86F => self.render_state.object_palette_control3.set(value),
             0x5_F870..=0x5_F871 => self.render_state.bkcol = (value & 0x3) as u8,
-            0x6_0000..=0x7_7FFF => {
-                #[cfg(feature = "panic")]
-                panic!("Invalid VIP address")
-            }
+            0x6_0000..=0x7_7FFF => self.invalid_write = Some(address as u32),
             0x7_8000..=0x7_9FFF => {
                 // Character table 1 remap
                 self.vram.set_u16((address & 0x1FFF) + 0x6000, value);
@@ -380,10 +394,8 @@ impl VIP {
         }
     }
 
-    /// Runs the VIP for `cycles_to_run`.
-    ///
-    /// Returns true if an interrupt is requested from the VIP
-    pub fn step(&mut self, cycles_to_run: usize) -> bool {
+    /// Runs the VIP for `cycles_to_run`
+    pub fn step(&mut self, cycles_to_run: usize) {
         for _ in 0..cycles_to_run {
             // Display process
             match self.current_display_clock_cycle {
@@ -490,11 +502,63 @@ impl VIP {
                 }
             }
         }
+    }
 
+    /// Wait states added to each CPU transfer to VRAM, due to contention with drawing
+    pub fn vram_wait_cycles(&self) -> u32 {
+        if self.in_drawing {
+            VRAM_DRAWING_WAIT_CYCLE_COUNT
+        } else {
+            0
+        }
+    }
+
+    /// The interrupt line is held while any enabled interrupt is pending in INTPND
+    pub fn interrupt_line(&self) -> bool {
         self.interrupt_pending
             .check_intersection(&self.interrupt_enabled)
     }
 
+    /// Number of cycles until the next change to the display or drawing state. Stepping fewer cycles changes
+    /// nothing observable but the clock, and cannot raise an interrupt
+    pub fn cycles_until_event(&self) -> usize {
+        let clock = self.current_display_clock_cycle;
+
+        // The final cycle wraps the clock back to 0, ending the video frame
+        let display_event_offset = [
+            0,
+            LEFT_FRAME_BUFFER_CYCLE_OFFSET,
+            LEFT_FRAME_BUFFER_COMPLETE_CYCLE_OFFSET,
+            FCLK_LOW_CYCLE_OFFSET,
+            RIGHT_FRAME_BUFFER_CYCLE_OFFSET,
+            RIGHT_FRAME_BUFFER_COMPLETE_CYCLE_OFFSET,
+            FRAME_COMPLETE_CYCLE_OFFSET - 1,
+        ]
+        .into_iter()
+        .find(|offset| *offset >= clock)
+        .unwrap_or(clock);
+
+        let mut cycles = display_event_offset - clock + 1;
+
+        if self.in_drawing {
+            cycles = cycles.min(
+                DRAWING_BLOCK_CYCLE_COUNT
+                    .saturating_sub(self.drawing_cycle_count)
+                    .max(1),
+            );
+        }
+
+        if self.render_state.sbout {
+            cycles = cycles.min(
+                SBOUT_HIGH_CYCLE_COUNT
+                    .saturating_sub(self.sbout_cycle_high_count)
+                    .max(1),
+            );
+        }
+
+        cycles
+    }
+
     fn init_display_frame(&mut self) {
         self.fclk = true;
 
@@ -148,6 +148,15 @@ impl VSU {
         }
     }
 
+    /// Number of cycles until `count` more samples have been output
+    pub fn cycles_until_samples(&self, count: usize) -> usize {
+        let next_sample = SOUND_SAMPLE_RATE_CYCLE_COUNT
+            .saturating_sub(self.sample_output_counter)
+            .max(1);
+
+        next_sample + count.saturating_sub(1) * SOUND_SAMPLE_RATE_CYCLE_COUNT
+    }
+
     fn sample(&mut self, audio_sink: &mut dyn Sink<AudioFrame>) {
         let mut left_acc = 0;
         let mut right_acc = 0;
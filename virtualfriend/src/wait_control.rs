@@ -0,0 +1,134 @@
+//! Access timing for each region of the bus.
+//!
+//! Load and store cycle counts in the CPU are those of an access without wait states, as to WRAM. The VIP, VSU, and
+//! hardware control registers also respond without waits. The Game Pak ROM and expansion areas insert 1 or 2 wait
+//! states per transfer, as configured by WCR, with 2 waits at power on. The data bus is 16 bits wide, so word accesses
+//! take two transfers, and wait twice. CPU transfers to VRAM additionally wait on the VIP while it is drawing.
+//!
+//! Instruction cycle counts are taken from Mednafen, and already include fetching with the power on setting. Fetches
+//! are only charged the difference from that setting, one transfer per halfword of the instruction, so code fetched
+//! from the Game Pak runs faster once WCR selects 1 wait state. Overlap of fetches with execution in the prefetch
+//! queue is not modelled.
+
+/// A region of the address space with its own access timing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusRegion {
+    /// VRAM and DRAM, including the character table mirrors
+    Vram,
+    VipRegisters,
+    Vsu,
+    Hardware,
+    GamePakExpansion,
+    Wram,
+    GamePakRam,
+    GamePakRom,
+    Unmapped,
+}
+
+impl BusRegion {
+    pub fn from_address(address: u32) -> Self {
+        // Mask top 5 bits to mirror bus
+        let address = address & 0x07FF_FFFF;
+
+        match address {
+            0x0005_E000..=0x0005_FFFF => BusRegion::VipRegisters,
+            0x0000_0000..=0x00FF_FFFF => BusRegion::Vram,
+            0x0100_0000..=0x01FF_FFFF => BusRegion::Vsu,
+            0x0200_0000..=0x02FF_FFFF => BusRegion::Hardware,
+            0x0400_0000..=0x04FF_FFFF => BusRegion::GamePakExpansion,
+            0x0500_0000..=0x05FF_FFFF => BusRegion::Wram,
+            0x0600_0000..=0x06FF_FFFF => BusRegion::GamePakRam,
+            0x0700_0000..=0x07FF_FFFF => BusRegion::GamePakRom,
+            _ => BusRegion::Unmapped,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessWidth {
+    Byte,
+    Halfword,
+    Word,
+}
+
+impl AccessWidth {
+    /// Number of transfers over the 16 bit data bus
+    pub fn transfer_count(&self) -> u32 {
+        match self {
+            AccessWidth::Byte | AccessWidth::Halfword => 1,
+            AccessWidth::Word => 2,
+        }
+    }
+}
+
+/// WCR Wait control register
+#[derive(Savefile)]
+pub struct WaitControl {
+    /// ROM1W When set, Game Pak ROM is accessed with 1 wait state instead of 2
+    rom_one_wait: bool,
+    /// EXP1W When set, the Game Pak expansion area and RAM are accessed with 1 wait state instead of 2
+    expansion_one_wait: bool,
+}
+
+impl WaitControl {
+    pub fn new() -> Self {
+        WaitControl {
+            rom_one_wait: false,
+            expansion_one_wait: false,
+        }
+    }
+
+    pub fn get(&self) -> u16 {
+        // Unused bits read as set
+        let mut value = 0xFC;
+
+        if self.rom_one_wait {
+            value |= 0x1;
+        }
+
+        if self.expansion_one_wait {
+            value |= 0x2;
+        }
+
+        value
+    }
+
+    pub fn set(&mut self, value: u16) {
+        self.rom_one_wait = value & 0x1 != 0;
+        self.expansion_one_wait = value & 0x2 != 0;
+    }
+
+    /// Cycles added to an access of `width` to `region`, beyond those of an access without waits
+    pub fn wait_cycles(&self, region: BusRegion, width: AccessWidth) -> u32 {
+        let waits = match region {
+            BusRegion::GamePakRom => Self::waits(self.rom_one_wait),
+            BusRegion::GamePakExpansion | BusRegion::GamePakRam => {
+                Self::waits(self.expansion_one_wait)
+            }
+            BusRegion::Vram
+            | BusRegion::VipRegisters
+            | BusRegion::Vsu
+            | BusRegion::Hardware
+            | BusRegion::Wram
+            | BusRegion::Unmapped => 0,
+        };
+
+        waits * width.transfer_count()
+    }
+
+    /// Cycles added to an instruction fetch of `width` from `region`, relative to the power on setting. Zero at power
+    /// on, and negative once WCR selects fewer wait states
+    pub fn fetch_wait_delta(&self, region: BusRegion, width: AccessWidth) -> i32 {
+        let power_on = WaitControl::new().wait_cycles(region, width);
+
+        self.wait_cycles(region, width) as i32 - power_on as i32
+    }
+
+    fn waits(one_wait: bool) -> u32 {
+        if one_wait {
+            1
+        } else {
+            2
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use virtualfriend::{
+    assembler::{assemble, assemble_rom, ROM_BASE_ADDRESS},
+    disasm::disassemble,
+    gamepad::GamepadInputs,
+    VirtualFriend,
+};
+
+/// One instruction per line, in the syntax `disasm` produces
+const ROUND_TRIP: &str = "
+mov     r1, r2
+mov     -0x10, r3
+add     r4, r5
+add     0xF, r6
+sub     r7, r8
+cmp     r9, r10
+cmp     -0x1, r11
+shl     r12, r13
+shl     0x1F, r14
+shr     r15, r16
+shr     0x4, r17
+sar     r18, r19
+sar     0x8, r20
+mul     r21, r22
+div     r23, r24
+mulu    r25, r26
+divu    r27, r28
+or      r29, r30
+and     r31, r1
+xor     r2, r3
+not     r4, r5
+jmp     [r31]
+setf    nz, r6
+cli
+sei
+reti
+halt
+trap    0x1C
+ldsr    r7, psw
+stsr    adtre, r8
+sch0bsu
+sch1bsd
+movbsu
+notbsu
+bz      0x07000050
+bgt     0x0700004E
+br      0x07000000
+nop
+jr      0x07100000
+jal     0x06F00000
+movea   0x8000, r0, r1
+addi    0xFFFF, r2, r3
+ori     0x1234, r4, r5
+andi    0x00FF, r6, r7
+xori    0xF0F0, r8, r9
+movhi   0x0700, r0, r10
+ld.b    -0x4[r1], r2
+ld.h    0x2[r3], r4
+ld.w    0x7FFC[r5], r6
+in.b    0x0[r7], r8
+in.h    0x2[r9], r10
+in.w    0x4[r11], r12
+caxi    0x0[r13], r14
+st.b    r15, 0x1[r16]
+st.h    r17, -0x2[r18]
+st.w    r19, -0x8000[r20]
+out.b   r21, 0x0[r22]
+out.h   r23, 0x2[r24]
+out.w   r25, 0x4[r26]
+cmpf.s  r1, r2
+cvt.ws  r3, r4
+cvt.sw  r5, r6
+addf.s  r7, r8
+subf.s  r9, r10
+mulf.s  r11, r12
+divf.s  r13, r14
+rev     r15, r16
+trnc.sw r17, r18
+mpyhw   r19, r20
+xb      r21
+xh      r22
+";
+
+#[test]
+fn round_trips_through_disassembler() {
+    let program = assemble(ROM_BASE_ADDRESS, ROUND_TRIP).unwrap();
+
+    let disassembly = disassemble(program.base_address, &program.bytes)
+        .iter()
+        .map(|instruction| instruction.to_string())
+        .collect::<Vec<_>>();
+
+    let expected = ROUND_TRIP
+        .lines()
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+
+    let normalize = |line: &str| line.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    assert_eq!(disassembly.len(), expected.len());
+
+    for (actual, expected) in disassembly.iter().zip(expected.iter()) {
+        assert_eq!(normalize(actual), normalize(expected));
+    }
+}
+
+#[test]
+fn resolves_labels() {
+    let program = assemble(
+        ROM_BASE_ADDRESS,
+        "
+        start:
+            movhi   hi(data), r0, r1
+            movea   lo(data), r1, r1
+        loop:   add     -1, r2
+            bnz     loop
+            jr      end
+        data:   .word   0x12345678, start
+        end:    halt
+        ",
+    )
+    .unwrap();
+
+    let data = program.label("data").unwrap();
+
+    assert_eq!(program.label("start"), Some(ROM_BASE_ADDRESS));
+    assert_eq!(data, 0x0700_0010);
+    assert_eq!(program.label("end"), Some(0x0700_0018));
+
+    let disassembly = disassemble(program.base_address, &program.bytes[..0x10])
+        .iter()
+        .map(|instruction| instruction.to_string())
+        .collect::<Vec<_>>();
+
+    assert!(disassembly[0].ends_with("0x0700, r0, r1"));
+    assert!(disassembly[1].ends_with("0x0010, r1, r1"));
+    assert!(disassembly[3].ends_with("0x07000008"));
+    assert!(disassembly[4].ends_with("0x07000018"));
+
+    assert_eq!(
+        program.bytes[0x10..0x18],
+        [0x78, 0x56, 0x34, 0x12, 0x00, 0x00, 0x00, 0x07]
+    );
+}
+
+#[test]
+fn reports_errors() {
+    let error = assemble(ROM_BASE_ADDRESS, "nop\nadd 16, r1").unwrap_err();
+    assert_eq!(error.line, 2);
+
+    let error = assemble(ROM_BASE_ADDRESS, "bz missing").unwrap_err();
+    assert_eq!(error.line, 1);
+
+    let error = assemble(ROM_BASE_ADDRESS, "frobnicate r1").unwrap_err();
+    assert_eq!(error.line, 1);
+
+    let error = assemble(ROM_BASE_ADDRESS, "a: nop\na: nop").unwrap_err();
+    assert_eq!(error.line, 2);
+}
+
+#[test]
+fn runs_assembled_rom() {
+    let rom = assemble_rom(
+        "
+            ldsr    r0, psw
+            movea   5, r0, r10
+            trap    0
+            add     1, r10
+            halt
+
+        trap_0:
+            movea   0x77, r0, r11
+            reti
+        ",
+    )
+    .unwrap();
+
+    assert_eq!(rom.len(), 1024);
+
+    let mut virtualfriend = VirtualFriend::new(rom).unwrap();
+
+    // The first frame only executes the reset vector
+    virtualfriend.run_video_frame(GamepadInputs::default());
+    virtualfriend.run_video_frame(GamepadInputs::default());
+
+    let registers = virtualfriend.registers();
+
+    assert_eq!(registers.general_purpose[10], 6);
+    assert_eq!(registers.general_purpose[11], 0x77);
+}
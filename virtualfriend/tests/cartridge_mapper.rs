@@ -0,0 +1,217 @@
+use common::{read_u16s, read_u32s};
+use virtualfriend::{
+    assembler::{assemble, assemble_rom, ROM_BASE_ADDRESS},
+    cartridge::FlashMapper,
+    config::EmulatorConfig,
+    gamepad::GamepadInputs,
+    VirtualFriend,
+};
+
+mod common;
+
+/// Erases and programs flash with the completion interrupt enabled, counting interrupts into WRAM, then reads the
+/// programmed halfwords back into WRAM
+const PROGRAM_FLASH: &str = "
+    ldsr    r0, psw
+    movhi   0x0500, r0, r20
+    movhi   0x0400, r0, r21
+    movhi   0x0701, r0, r22
+    st.w    r0, 0x0[r20]
+
+    // Programming and completion interrupt enabled
+    mov     0x3, r6
+    st.h    r6, 0x2[r21]
+
+    // Erase sector 1, then program its first halfword
+    mov     0x1, r6
+    st.h    r6, 0x6[r21]
+    movea   0x1234, r0, r6
+    st.h    r6, 0x0[r22]
+
+    // Programming is disabled, so this is ignored
+    st.h    r0, 0x2[r21]
+    st.h    r0, 0x2[r22]
+
+    ld.h    0x0[r22], r6
+    st.h    r6, 0x4[r20]
+    ld.h    0x2[r22], r6
+    st.h    r6, 0x6[r20]
+
+idle:
+    halt
+    br      idle
+
+game_pak_interrupt:
+    // Acknowledge completion
+    mov     0x1, r25
+    st.h    r25, 0x4[r21]
+    ld.w    0x0[r20], r25
+    add     0x1, r25
+    st.w    r25, 0x0[r20]
+    reti
+";
+
+/// Reads a marker from bank 0, switches to bank 1, and reads the marker again
+const SWITCH_BANK: &str = "
+    ldsr    r0, psw
+    movhi   0x0500, r0, r20
+    movhi   0x0400, r0, r21
+    movhi   0x0702, r0, r22
+
+    ld.h    0x0[r22], r6
+    st.h    r6, 0x0[r20]
+    mov     0x1, r6
+    st.h    r6, 0x0[r21]
+    ld.h    0x0[r22], r6
+    st.h    r6, 0x2[r20]
+    in.h    0x0[r21], r6
+    st.h    r6, 0x4[r20]
+
+idle:
+    halt
+    br      idle
+";
+
+/// Calls `routine` from bank 0, then from bank 1, then again from bank 0 after programming it, storing each result
+const REWRITE_ROUTINE: &str = "
+    ldsr    r0, psw
+    movhi   0x0500, r0, r20
+    movhi   0x0400, r0, r21
+    movhi   hi(routine), r0, r22
+    movea   lo(routine), r22, r22
+
+    jal     routine
+    st.w    r10, 0x0[r20]
+
+    mov     0x1, r6
+    st.h    r6, 0x0[r21]
+    jal     routine
+    st.w    r10, 0x4[r20]
+
+    // Back to bank 0, then program the routine to mov 0, r10
+    st.h    r0, 0x0[r21]
+    mov     0x1, r6
+    st.h    r6, 0x2[r21]
+    movea   0x4140, r0, r6
+    st.h    r6, 0x0[r22]
+    jal     routine
+    st.w    r10, 0x8[r20]
+
+idle:
+    halt
+    br      idle
+
+routine:
+    mov     0x1, r10
+    jmp     [r31]
+";
+
+/// Mirrors the assembled ROM to fill `size` bytes
+fn flash_image(source: &str, size: usize) -> Vec<u8> {
+    let rom = assemble_rom(source).unwrap();
+
+    rom.iter().copied().cycle().take(size).collect()
+}
+
+fn new_virtualfriend(flash: Vec<u8>) -> VirtualFriend {
+    VirtualFriend::new_with_mapper(
+        Box::new(FlashMapper::new(flash).unwrap()),
+        EmulatorConfig::default(),
+    )
+}
+
+#[test]
+fn programs_flash_and_raises_game_pak_interrupt() {
+    // Four 64KB sectors, with the interrupt vectors in the last
+    let flash = flash_image(PROGRAM_FLASH, 0x4_0000);
+
+    let mut virtualfriend = new_virtualfriend(flash.clone());
+
+    for _ in 0..3 {
+        let frame = virtualfriend.run_video_frame(GamepadInputs::default());
+        assert!(frame.crash.is_none());
+    }
+
+    // One interrupt each for the erase and the program
+    assert_eq!(
+        read_u16s(&virtualfriend, 0x0500_0000, 4),
+        [2, 0, 0x1234, 0xFFFF]
+    );
+
+    // Programmed flash is restored by savestates
+    let savestate = virtualfriend.create_savestate();
+
+    let mut restored = new_virtualfriend(flash);
+    assert_ne!(read_u16s(&restored, 0x0701_0000, 1)[0], 0x1234);
+
+    restored.load_savestate(&savestate);
+    assert_eq!(read_u16s(&restored, 0x0701_0000, 2), [0x1234, 0xFFFF]);
+}
+
+#[test]
+fn switches_flash_banks() {
+    let bank_size = 0x100_0000;
+
+    let mut flash = flash_image(SWITCH_BANK, bank_size * 2);
+
+    flash[0x2_0000..0x2_0002].copy_from_slice(&0x1111u16.to_le_bytes());
+    flash[bank_size + 0x2_0000..bank_size + 0x2_0002].copy_from_slice(&0x2222u16.to_le_bytes());
+
+    let mut virtualfriend = new_virtualfriend(flash);
+
+    for _ in 0..2 {
+        virtualfriend.run_video_frame(GamepadInputs::default());
+    }
+
+    assert_eq!(
+        read_u16s(&virtualfriend, 0x0500_0000, 3),
+        [0x1111, 0x2222, 0x1]
+    );
+}
+
+#[test]
+fn mirrors_banks_beyond_flash_size() {
+    let bank_size = 0x100_0000;
+
+    // Selects bank 0xFFFF, which mirrors bank 1
+    let source = SWITCH_BANK.replace("mov     0x1, r6", "mov     -1, r6");
+    let mut flash = flash_image(&source, bank_size * 2);
+
+    flash[0x2_0000..0x2_0002].copy_from_slice(&0x1111u16.to_le_bytes());
+    flash[bank_size + 0x2_0000..bank_size + 0x2_0002].copy_from_slice(&0x2222u16.to_le_bytes());
+
+    let mut virtualfriend = new_virtualfriend(flash);
+
+    for _ in 0..2 {
+        virtualfriend.run_video_frame(GamepadInputs::default());
+    }
+
+    assert_eq!(
+        read_u16s(&virtualfriend, 0x0500_0000, 3),
+        [0x1111, 0x2222, 0xFFFF]
+    );
+}
+
+#[test]
+fn reruns_routines_after_flash_changes() {
+    let bank_size = 0x100_0000;
+
+    let routine = assemble(ROM_BASE_ADDRESS, REWRITE_ROUTINE)
+        .unwrap()
+        .label("routine")
+        .unwrap();
+    let offset = (routine - ROM_BASE_ADDRESS) as usize;
+
+    // mov 2, r10 in place of the routine's first instruction in bank 1
+    let mut flash = flash_image(REWRITE_ROUTINE, bank_size * 2);
+    flash[bank_size + offset..bank_size + offset + 2].copy_from_slice(&0x4142u16.to_le_bytes());
+
+    let mut virtualfriend = new_virtualfriend(flash);
+
+    for _ in 0..2 {
+        virtualfriend.run_video_frame(GamepadInputs::default());
+    }
+
+    // Each call executes the routine as currently mapped, not as it was first decoded
+    assert_eq!(read_u32s(&virtualfriend, 0x0500_0000, 3), [1, 2, 0]);
+}
@@ -0,0 +1,44 @@
+use common::build_rom;
+use virtualfriend::{gamepad::GamepadInputs, VirtualFriend};
+
+mod common;
+
+const PROGRAM: &[u16] = &[
+    0xBC40, 0x0500, // 0x07000000 movhi 0x0500, r0, r2
+    0xA060, 0x0005, // 0x07000004 movea 5, r0, r3
+    0xDC62, 0x0000, // 0x07000008 st.w r3, 0x0[r2]
+    0xA3C0, 0x0077, // 0x0700000C movea 0x77, r0, r30
+    0xA080, 0x0005, // 0x07000010 movea 5, r0, r4
+    0xE882, 0x0000, // 0x07000014 caxi 0x0[r2], r4 (matches, stores r30)
+    0x74E5, // 0x07000018 stsr psw, r7
+    0xA0A0, 0x0009, // 0x0700001A movea 9, r0, r5
+    0xE8A2, 0x0000, // 0x0700001E caxi 0x0[r2], r5 (does not match)
+    0x74C5, // 0x07000022 stsr psw, r6
+    0x6800, // 0x07000024 halt
+];
+
+#[test]
+fn compares_and_exchanges() {
+    let mut virtualfriend = VirtualFriend::new(build_rom(PROGRAM)).unwrap();
+
+    // The first frame only executes the reset vector
+    virtualfriend.run_video_frame(GamepadInputs::default());
+    virtualfriend.run_video_frame(GamepadInputs::default());
+
+    let registers = virtualfriend.registers();
+
+    // Successful exchange. reg2 receives the original word
+    assert_eq!(registers.general_purpose[4], 5);
+    // Z
+    assert_eq!(registers.general_purpose[7] & 0xF, 0x1);
+
+    // Failed exchange. reg2 receives the word written by the first CAXI
+    assert_eq!(registers.general_purpose[5], 0x77);
+    // 9 - 0x77 sets S and CY
+    assert_eq!(registers.general_purpose[6] & 0xF, 0xA);
+
+    assert_eq!(
+        virtualfriend.read_memory(0x0500_0000, 4),
+        vec![0x77, 0x00, 0x00, 0x00]
+    );
+}
@@ -0,0 +1,65 @@
+// Each test crate uses only some of these helpers
+#![allow(dead_code)]
+
+use virtualfriend::{
+    assembler::assemble_rom,
+    config::{EmulatorConfig, PowerOnState},
+    VirtualFriend,
+};
+
+/// Builds a 1KB ROM with `program` at 0x07000000 and a reset vector that jumps to it
+pub fn build_rom(program: &[u16]) -> Vec<u8> {
+    let mut rom = vec![0; 1024];
+
+    let mut put = |offset: usize, halfwords: &[u16]| {
+        for (i, halfword) in halfwords.iter().enumerate() {
+            rom[offset + i * 2..offset + i * 2 + 2].copy_from_slice(&halfword.to_le_bytes());
+        }
+    };
+
+    put(0x0, program);
+
+    // Reset vector
+    put(
+        0x3F0,
+        &[
+            0xBC20, 0x0700, // movhi 0x0700, r0, r1
+            0x1801, // jmp [r1]
+        ],
+    );
+
+    rom
+}
+
+/// The default config, with WRAM zeroed at power on, as it is otherwise random
+pub fn zeroed_config() -> EmulatorConfig {
+    EmulatorConfig {
+        power_on: PowerOnState::Zeroed,
+        ..Default::default()
+    }
+}
+
+/// Assembles `source` and loads it with [`zeroed_config`]
+pub fn new_virtualfriend(source: &str) -> VirtualFriend {
+    new_virtualfriend_with_config(source, zeroed_config())
+}
+
+pub fn new_virtualfriend_with_config(source: &str, config: EmulatorConfig) -> VirtualFriend {
+    VirtualFriend::new_with_config(assemble_rom(source).unwrap(), config).unwrap()
+}
+
+pub fn read_u16s(virtualfriend: &VirtualFriend, address: u32, count: usize) -> Vec<u16> {
+    virtualfriend
+        .read_memory(address, count * 2)
+        .chunks(2)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+        .collect()
+}
+
+pub fn read_u32s(virtualfriend: &VirtualFriend, address: u32, count: usize) -> Vec<u32> {
+    virtualfriend
+        .read_memory(address, count * 4)
+        .chunks(4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect()
+}
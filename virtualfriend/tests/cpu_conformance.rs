@@ -0,0 +1,1051 @@
+// Self-checking programs for every instruction the CPU implements. Each program is assembled into a ROM, run until it
+// halts, and the resulting registers, flags, and memory are compared against the V810 specification
+
+use common::{new_virtualfriend, read_u32s};
+use virtualfriend::{
+    assembler::{assemble, ROM_BASE_ADDRESS},
+    gamepad::GamepadInputs,
+    VirtualFriend,
+};
+
+mod common;
+
+const Z: u32 = 0x1;
+const S: u32 = 0x2;
+const OV: u32 = 0x4;
+const CY: u32 = 0x8;
+
+const PSW_NP: u32 = 0x8000;
+const PSW_EP: u32 = 0x4000;
+const PSW_AE: u32 = 0x2000;
+const PSW_ID: u32 = 0x1000;
+
+const WRAM: u32 = 0x0500_0000;
+
+/// `source`, prefixed with clearing PSW
+fn program(source: &str) -> String {
+    format!("ldsr r0, psw\n{source}")
+}
+
+/// The address of `label` in the program built from `source`
+fn label(source: &str, label: &str) -> u32 {
+    assemble(ROM_BASE_ADDRESS, &program(source))
+        .unwrap()
+        .label(label)
+        .unwrap()
+}
+
+/// Assembles `source`, prefixed with clearing PSW, and runs it until it halts
+fn run(source: &str) -> VirtualFriend {
+    let mut virtualfriend = new_virtualfriend(&program(source));
+
+    // The first frame only executes the reset vector
+    virtualfriend.run_video_frame(GamepadInputs::default());
+    virtualfriend.run_video_frame(GamepadInputs::default());
+
+    virtualfriend
+}
+
+fn registers(source: &str) -> [u32; 32] {
+    run(source).registers().general_purpose
+}
+
+#[test]
+fn moves() {
+    let r = registers(
+        "
+        mov     -3, r1
+        mov     r1, r2
+        movea   0x8000, r0, r3
+        movhi   0x1234, r0, r4
+        movhi   hi(0x12348765), r0, r5
+        movea   lo(0x12348765), r5, r5
+        mov     r0, r6
+        add     1, r6
+        halt
+        ",
+    );
+
+    assert_eq!(r[1], 0xFFFF_FFFD);
+    assert_eq!(r[2], 0xFFFF_FFFD);
+    assert_eq!(r[3], 0xFFFF_8000);
+    assert_eq!(r[4], 0x1234_0000);
+    assert_eq!(r[5], 0x1234_8765);
+    assert_eq!(r[6], 1);
+}
+
+#[test]
+fn add_sub_cmp() {
+    let r = registers(
+        "
+        movhi   0x8000, r0, r1
+        add     -1, r1
+        stsr    psw, r20
+        add     1, r1
+        stsr    psw, r21
+        mov     -1, r2
+        add     1, r2
+        stsr    psw, r22
+        mov     1, r3
+        mov     0, r4
+        sub     r3, r4
+        stsr    psw, r23
+        mov     5, r5
+        cmp     5, r5
+        stsr    psw, r24
+        cmp     r5, r0
+        stsr    psw, r25
+        addi    0x7FFF, r5, r6
+        stsr    psw, r26
+        mov     r5, r7
+        add     r5, r7
+        halt
+        ",
+    );
+
+    // 0x80000000 + 0xFFFFFFFF
+    assert_eq!(r[1], 0x8000_0000);
+    assert_eq!(r[20] & 0xF, OV | CY);
+    // 0x7FFFFFFF + 1
+    assert_eq!(r[21] & 0xF, S | OV);
+    // 0xFFFFFFFF + 1
+    assert_eq!(r[2], 0);
+    assert_eq!(r[22] & 0xF, Z | CY);
+    // 0 - 1
+    assert_eq!(r[4], 0xFFFF_FFFF);
+    assert_eq!(r[23] & 0xF, S | CY);
+    // 5 - 5
+    assert_eq!(r[5], 5);
+    assert_eq!(r[24] & 0xF, Z);
+    // 0 - 5
+    assert_eq!(r[25] & 0xF, S | CY);
+    assert_eq!(r[6], 0x8004);
+    assert_eq!(r[26] & 0xF, 0);
+    assert_eq!(r[7], 10);
+}
+
+#[test]
+fn logic() {
+    let r = registers(
+        "
+        movhi   0xF0F0, r0, r1
+        ori     0xF0F0, r1, r1
+        movhi   0x0FF0, r0, r2
+        ori     0x0FF0, r2, r2
+        mov     r1, r3
+        or      r2, r3
+        stsr    psw, r20
+        mov     r1, r4
+        and     r2, r4
+        mov     r1, r5
+        xor     r2, r5
+        not     r1, r6
+        andi    0xFFFF, r1, r7
+        xori    0xFFFF, r1, r8
+        ; Logical operations clear OV and leave CY unchanged
+        movhi   0x8000, r0, r9
+        add     -1, r9
+        and     r0, r9
+        stsr    psw, r21
+        halt
+        ",
+    );
+
+    assert_eq!(r[1], 0xF0F0_F0F0);
+    assert_eq!(r[3], 0xFFF0_FFF0);
+    assert_eq!(r[20] & 0xF, S);
+    assert_eq!(r[4], 0x00F0_00F0);
+    assert_eq!(r[5], 0xFF00_FF00);
+    assert_eq!(r[6], 0x0F0F_0F0F);
+    // The immediate is zero extended
+    assert_eq!(r[7], 0x0000_F0F0);
+    assert_eq!(r[8], 0xF0F0_0F0F);
+    assert_eq!(r[9], 0);
+    assert_eq!(r[21] & 0xF, Z | CY);
+}
+
+#[test]
+fn shifts() {
+    let r = registers(
+        "
+        movhi   0x8000, r0, r1
+        ori     1, r1, r1
+        mov     r1, r2
+        shl     1, r2
+        stsr    psw, r20
+        mov     r1, r3
+        shr     1, r3
+        stsr    psw, r21
+        mov     r1, r4
+        sar     1, r4
+        stsr    psw, r22
+        mov     r1, r5
+        mov     4, r6
+        sar     r6, r5
+        stsr    psw, r23
+        mov     r1, r7
+        shl     0, r7
+        stsr    psw, r24
+        ; Only the low 5 bits of the shift amount are used
+        mov     r1, r8
+        movea   33, r0, r9
+        shr     r9, r8
+        stsr    psw, r25
+        halt
+        ",
+    );
+
+    assert_eq!(r[2], 0x0000_0002);
+    assert_eq!(r[20] & 0xF, CY);
+    assert_eq!(r[3], 0x4000_0000);
+    assert_eq!(r[21] & 0xF, CY);
+    assert_eq!(r[4], 0xC000_0000);
+    assert_eq!(r[22] & 0xF, S | CY);
+    assert_eq!(r[5], 0xF800_0000);
+    assert_eq!(r[23] & 0xF, S);
+    assert_eq!(r[7], 0x8000_0001);
+    assert_eq!(r[24] & 0xF, S);
+    assert_eq!(r[8], 0x4000_0000);
+    assert_eq!(r[25] & 0xF, CY);
+}
+
+#[test]
+fn multiply() {
+    let r = registers(
+        "
+        mov     -3, r1
+        mov     7, r2
+        mul     r1, r2
+        mov     r30, r3
+        stsr    psw, r20
+        movhi   1, r0, r4
+        mov     r4, r5
+        mul     r4, r5
+        mov     r30, r6
+        stsr    psw, r21
+        mov     -1, r7
+        mov     -1, r8
+        mulu    r7, r8
+        mov     r30, r9
+        stsr    psw, r22
+        halt
+        ",
+    );
+
+    // -3 * 7
+    assert_eq!(r[2], 0xFFFF_FFEB);
+    assert_eq!(r[3], 0xFFFF_FFFF);
+    assert_eq!(r[20] & 0xF, S);
+    // 0x10000 * 0x10000
+    assert_eq!(r[5], 0);
+    assert_eq!(r[6], 1);
+    assert_eq!(r[21] & 0xF, Z | OV);
+    // 0xFFFFFFFF * 0xFFFFFFFF
+    assert_eq!(r[8], 1);
+    assert_eq!(r[9], 0xFFFF_FFFE);
+    assert_eq!(r[22] & 0xF, OV);
+}
+
+#[test]
+fn divide() {
+    let r = registers(
+        "
+        mov     -7, r1
+        mov     2, r2
+        div     r2, r1
+        mov     r30, r3
+        stsr    psw, r20
+        mov     -7, r4
+        mov     2, r5
+        divu    r5, r4
+        mov     r30, r6
+        stsr    psw, r21
+        movhi   0x8000, r0, r7
+        mov     -1, r8
+        div     r8, r7
+        mov     r30, r9
+        stsr    psw, r22
+        halt
+        ",
+    );
+
+    // -7 / 2 truncates towards zero
+    assert_eq!(r[1], 0xFFFF_FFFD);
+    assert_eq!(r[3], 0xFFFF_FFFF);
+    assert_eq!(r[20] & 0xF, S);
+    // 0xFFFFFFF9 / 2
+    assert_eq!(r[4], 0x7FFF_FFFC);
+    assert_eq!(r[6], 1);
+    assert_eq!(r[21] & 0xF, 0);
+    // 0x80000000 / -1 overflows
+    assert_eq!(r[7], 0x8000_0000);
+    assert_eq!(r[9], 0);
+    assert_eq!(r[22] & 0xF, S | OV);
+}
+
+#[test]
+fn divide_by_zero() {
+    let virtualfriend = run("
+        mov     5, r1
+        div     r0, r1
+        movea   1, r0, r13
+        halt
+
+        zero_divide_exception:
+        stsr    eipc, r10
+        stsr    ecr, r11
+        ; The restore PC is the DIV itself. Skip it
+        mov     r10, r12
+        add     2, r12
+        ldsr    r12, eipc
+        reti
+        ");
+
+    let registers = virtualfriend.registers();
+    let r = registers.general_purpose;
+
+    assert_eq!(r[1], 5);
+    assert_eq!(r[10], 0x0700_0004);
+    assert_eq!(r[11] & 0xFFFF, 0xFF80);
+    assert_eq!(r[13], 1);
+}
+
+#[test]
+fn trap_and_reti() {
+    let r = registers(
+        "
+        cmp     0, r0
+        trap    5
+        stsr    psw, r20
+        trap    0x14
+        halt
+
+        trap_0:
+        stsr    ecr, r10
+        stsr    eipc, r11
+        stsr    eipsw, r12
+        stsr    psw, r13
+        ; Clobber the flags, which RETI restores
+        cmp     1, r0
+        reti
+
+        trap_16:
+        stsr    ecr, r14
+        reti
+        ",
+    );
+
+    assert_eq!(r[10] & 0xFFFF, 0xFFA5);
+    // The restore PC is the instruction after TRAP
+    assert_eq!(r[11], 0x0700_0006);
+    assert_eq!(r[12] & 0xF, Z);
+    assert_eq!(r[13] & (PSW_EP | PSW_ID), PSW_EP | PSW_ID);
+    assert_eq!(r[20] & (PSW_EP | PSW_ID | 0xF), Z);
+    assert_eq!(r[14] & 0xFFFF, 0xFFB4);
+}
+
+/// Points ADTRE at `target`, and enables the address trap
+const ENABLE_ADDRESS_TRAP: &str = "
+        movhi   hi(target), r0, r6
+        movea   lo(target), r6, r6
+        ldsr    r6, adtre
+        movea   0x2000, r0, r6
+        ldsr    r6, psw
+";
+
+/// Clears AE in the PSW saved in `register`, so returning from the handler doesn't trap again
+fn clear_saved_address_trap(register: &str) -> String {
+    format!(
+        "
+        stsr    {register}, r7
+        movea   0x2000, r0, r8
+        not     r8, r8
+        and     r8, r7
+        ldsr    r7, {register}
+"
+    )
+}
+
+#[test]
+fn address_trap() {
+    let source = format!(
+        "
+        {ENABLE_ADDRESS_TRAP}
+        jr      target
+        halt
+
+        target:
+        mov     1, r10
+        stsr    psw, r15
+        halt
+
+        address_trap:
+        stsr    ecr, r11
+        stsr    eipc, r12
+        ; Whether the instruction at ADTRE ran before the trap
+        mov     r10, r13
+        add     1, r14
+        {}
+        reti
+        ",
+        clear_saved_address_trap("eipsw")
+    );
+
+    let target = label(&source, "target");
+
+    let r = registers(&source);
+
+    assert_eq!(r[11] & 0xFFFF, 0xFFC0);
+    // The restore PC is the instruction at ADTRE, which has not executed
+    assert_eq!(r[12], target);
+    assert_eq!(r[13], 0);
+    // With AE cleared, returning executes it without trapping again
+    assert_eq!(r[14], 1);
+    assert_eq!(r[10], 1);
+    assert_eq!(r[15] & (PSW_EP | PSW_AE), 0);
+}
+
+#[test]
+fn duplexed_address_trap() {
+    // The address trap is hit inside a TRAP handler, which enables it again
+    let source = format!(
+        "
+        {ENABLE_ADDRESS_TRAP}
+        trap    0
+        halt
+
+        ; The vector jumps to the mirror of the handler at the top of memory, which doesn't match ADTRE
+        trap_0:
+        movhi   hi(handler), r0, r6
+        movea   lo(handler), r6, r6
+        jmp     [r6]
+
+        handler:
+        movea   0x7000, r0, r6
+        ldsr    r6, psw
+        target:
+        mov     1, r10
+        stsr    psw, r15
+        reti
+
+        duplexed_exception:
+        stsr    ecr, r11
+        stsr    fepc, r12
+        stsr    psw, r16
+        mov     r10, r13
+        add     1, r14
+        {}
+        reti
+        ",
+        clear_saved_address_trap("fepsw")
+    );
+
+    let target = label(&source, "target");
+
+    let r = registers(&source);
+
+    // The address trap code is stored above that of the TRAP
+    assert_eq!(r[11] >> 16, 0xFFC0);
+    assert_eq!(r[11] & 0xFFFF, 0xFFA0);
+    assert_eq!(r[12], target);
+    assert_eq!(r[16] & (PSW_NP | PSW_EP), PSW_NP | PSW_EP);
+    assert_eq!(r[13], 0);
+    // Returning from the duplexed exception resumes the TRAP handler, which returns in turn
+    assert_eq!(r[14], 1);
+    assert_eq!(r[10], 1);
+    assert_eq!(r[15] & (PSW_NP | PSW_EP | PSW_AE), PSW_EP);
+}
+
+#[test]
+fn setf_conditions() {
+    let mut source = String::from("movhi 0x0500, r0, r20\n");
+
+    for flags in 0..16 {
+        source += &format!("mov {flags}, r2\nldsr r2, psw\n");
+
+        for condition in 0..16 {
+            source += &format!("setf {}, r1\n", CONDITIONS[condition]);
+            source += &format!("st.b r1, {}[r20]\n", flags * 16 + condition);
+        }
+    }
+
+    source += "halt\n";
+
+    let virtualfriend = run(&source);
+    let results = virtualfriend.read_memory(WRAM, 256);
+
+    for flags in 0..16 {
+        for condition in 0..16 {
+            assert_eq!(
+                results[flags * 16 + condition] != 0,
+                evaluate_condition(condition as u32, flags as u32),
+                "setf {} with flags {flags:X}",
+                CONDITIONS[condition]
+            );
+        }
+    }
+}
+
+const CONDITIONS: [&str; 16] = [
+    "v", "c", "z", "nh", "n", "t", "lt", "le", "nv", "nc", "nz", "h", "p", "f", "ge", "gt",
+];
+
+fn evaluate_condition(condition: u32, flags: u32) -> bool {
+    let z = flags & Z != 0;
+    let s = flags & S != 0;
+    let ov = flags & OV != 0;
+    let cy = flags & CY != 0;
+
+    let result = match condition & 0x7 {
+        0 => ov,
+        1 => cy,
+        2 => z,
+        3 => cy || z,
+        4 => s,
+        5 => true,
+        6 => s != ov,
+        7 => (s != ov) || z,
+        _ => unreachable!(),
+    };
+
+    // The upper 8 conditions are the negation of the lower 8
+    if condition < 8 {
+        result
+    } else {
+        !result
+    }
+}
+
+#[test]
+fn branches_and_jumps() {
+    let r = registers(
+        "
+        mov     5, r1
+        mov     0, r2
+        loop:
+        add     1, r2
+        add     -1, r1
+        bnz     loop
+        cmp     3, r2
+        bgt     greater
+        movea   0x99, r0, r3
+        greater:
+        blt     wrong
+        movea   1, r0, r4
+        jal     function
+        movea   2, r0, r7
+        jr      end
+        movea   0x99, r0, r5
+        end:
+        nop
+        halt
+
+        wrong:
+        movea   0x99, r0, r6
+        halt
+
+        function:
+        mov     r31, r8
+        movea   3, r0, r9
+        jmp     [r31]
+        ",
+    );
+
+    assert_eq!(r[1], 0);
+    assert_eq!(r[2], 5);
+    assert_eq!(r[3], 0);
+    assert_eq!(r[4], 1);
+    assert_eq!(r[5], 0);
+    assert_eq!(r[6], 0);
+    assert_eq!(r[7], 2);
+    // JAL stores the address of the following instruction
+    assert_eq!(r[8], 0x0700_001E);
+    assert_eq!(r[9], 3);
+}
+
+#[test]
+fn loads_and_stores() {
+    let virtualfriend = run("
+        movhi   0x0500, r0, r1
+        movhi   0x8081, r0, r2
+        ori     0x82F3, r2, r2
+        st.w    r2, 0[r1]
+        ld.b    0[r1], r3
+        ld.b    1[r1], r4
+        ld.h    2[r1], r5
+        ld.w    0[r1], r6
+        in.b    0[r1], r7
+        in.h    2[r1], r8
+        in.w    0[r1], r9
+        st.w    r0, 4[r1]
+        st.w    r0, 12[r1]
+        movea   0x11, r0, r10
+        st.b    r10, 4[r1]
+        st.h    r10, 6[r1]
+        out.w   r2, 8[r1]
+        out.h   r10, 12[r1]
+        out.b   r10, 14[r1]
+        addi    8, r1, r11
+        ld.w    -8[r11], r12
+        ; Addresses are aligned down to the access size
+        ld.w    2[r1], r13
+        ld.h    1[r1], r14
+        halt
+        ");
+
+    let r = virtualfriend.registers().general_purpose;
+
+    // Loads sign extend
+    assert_eq!(r[3], 0xFFFF_FFF3);
+    assert_eq!(r[4], 0xFFFF_FF82);
+    assert_eq!(r[5], 0xFFFF_8081);
+    assert_eq!(r[6], 0x8081_82F3);
+    // Inputs zero extend
+    assert_eq!(r[7], 0x0000_00F3);
+    assert_eq!(r[8], 0x0000_8081);
+    assert_eq!(r[9], 0x8081_82F3);
+    assert_eq!(r[12], 0x8081_82F3);
+    assert_eq!(r[13], 0x8081_82F3);
+    assert_eq!(r[14], 0xFFFF_82F3);
+
+    assert_eq!(
+        virtualfriend.read_memory(WRAM + 4, 12),
+        vec![0x11, 0x00, 0x11, 0x00, 0xF3, 0x82, 0x81, 0x80, 0x11, 0x00, 0x11, 0x00]
+    );
+}
+
+#[test]
+fn nintendo_extensions() {
+    let r = registers(
+        "
+        movhi   0x1234, r0, r1
+        ori     0x5678, r1, r1
+        rev     r1, r2
+        mov     1, r3
+        rev     r3, r4
+        mov     r1, r5
+        xb      r5
+        mov     r1, r6
+        xh      r6
+        mov     -2, r7
+        mov     3, r8
+        cmp     0, r0
+        mpyhw   r7, r8
+        stsr    psw, r20
+        halt
+        ",
+    );
+
+    assert_eq!(r[2], 0x1E6A_2C48);
+    assert_eq!(r[4], 0x8000_0000);
+    assert_eq!(r[5], 0x1234_7856);
+    assert_eq!(r[6], 0x5678_1234);
+    assert_eq!(r[8], 0xFFFF_FFFA);
+    // MPYHW does not modify flags
+    assert_eq!(r[20] & 0xF, Z);
+}
+
+#[test]
+fn floating_point() {
+    let r = registers(
+        "
+        mov     3, r1
+        cvt.ws  r1, r2
+        movhi   0x3FC0, r0, r3
+        mov     r2, r4
+        addf.s  r3, r4
+        mov     r2, r5
+        subf.s  r3, r5
+        mov     r2, r6
+        mulf.s  r3, r6
+        mov     r2, r7
+        divf.s  r3, r7
+        cmpf.s  r2, r3
+        stsr    psw, r20
+        movhi   0xC030, r0, r9
+        cvt.sw  r9, r10
+        trnc.sw r9, r11
+        halt
+        ",
+    );
+
+    assert_eq!(f32::from_bits(r[2]), 3.0);
+    assert_eq!(f32::from_bits(r[4]), 4.5);
+    assert_eq!(f32::from_bits(r[5]), 1.5);
+    assert_eq!(f32::from_bits(r[6]), 4.5);
+    assert_eq!(f32::from_bits(r[7]), 2.0);
+    // 1.5 - 3.0
+    assert_eq!(r[20] & 0xF, S | CY);
+    // -2.75 rounds to nearest, or truncates
+    assert_eq!(r[10] as i32, -3);
+    assert_eq!(r[11] as i32, -2);
+}
+
+const PSW_FPR: u32 = 0x10;
+const PSW_FUD: u32 = 0x20;
+const PSW_FOV: u32 = 0x40;
+const PSW_FZD: u32 = 0x80;
+const PSW_FIV: u32 = 0x100;
+const PSW_FRO: u32 = 0x200;
+
+/// Loads the bits of `value` into `register`
+fn load_float(value: f32, register: &str) -> String {
+    let bits = value.to_bits();
+
+    format!(
+        "
+        movhi   hi({bits}), r0, {register}
+        movea   lo({bits}), {register}, {register}
+"
+    )
+}
+
+/// Sets TKCW, then runs `op` on r2 and r1, holding `reg2` and `reg1`, storing the PSW in r20. The float exception
+/// handler stores ECR and EIPC in r10 and r11, counts exceptions in r14, and skips the faulting instruction
+fn float_operation(tkcw: u32, reg2: f32, reg1: f32, op: &str) -> [u32; 32] {
+    registers(&format!(
+        "
+        movea   {tkcw}, r0, r6
+        ldsr    r6, tkcw
+        {}
+        {}
+        {op}
+        stsr    psw, r20
+        halt
+
+        float_exception:
+        stsr    ecr, r10
+        stsr    eipc, r11
+        add     1, r14
+        addi    4, r11, r6
+        ldsr    r6, eipc
+        reti
+        ",
+        load_float(reg2, "r2"),
+        load_float(reg1, "r1"),
+    ))
+}
+
+#[test]
+fn float_reserved_operand() {
+    // Infinity, NaN, and a denormal
+    for reserved in [0x7F80_0000, 0x7FC0_0000, 0x0000_0001] {
+        let source = format!(
+            "
+            movhi   hi({reserved}), r0, r1
+            movea   lo({reserved}), r1, r1
+            movhi   0x3F80, r0, r2
+            faulting:
+            addf.s  r1, r2
+            halt
+
+            float_exception:
+            stsr    ecr, r10
+            stsr    eipc, r11
+            stsr    psw, r12
+            addi    4, r11, r6
+            ldsr    r6, eipc
+            reti
+            "
+        );
+
+        let r = registers(&source);
+
+        assert_eq!(r[10] & 0xFFFF, 0xFF60, "{reserved:#X}");
+        // The restore PC is the faulting instruction
+        assert_eq!(r[11], label(&source, "faulting"), "{reserved:#X}");
+        assert_eq!(r[12] & PSW_FRO, PSW_FRO, "{reserved:#X}");
+        // The destination is unchanged
+        assert_eq!(f32::from_bits(r[2]), 1.0, "{reserved:#X}");
+    }
+}
+
+#[test]
+fn float_exception_traps() {
+    // Overflow, zero division, and invalid operations trap if enabled in TKCW, as they are at power on
+    let cases = [
+        (3e38, 3e38, "mulf.s r1, r2", 0xFF64, PSW_FOV),
+        (1.0, 0.0, "divf.s r1, r2", 0xFF68, PSW_FZD),
+        (0.0, 0.0, "divf.s r1, r2", 0xFF70, PSW_FIV),
+        (0.0, 3e9, "cvt.sw r1, r2", 0xFF70, PSW_FIV),
+    ];
+
+    for (reg2, reg1, op, code, flag) in cases {
+        let r = float_operation(0xE0, reg2, reg1, op);
+
+        assert_eq!(r[14], 1, "{op} {reg2} {reg1}");
+        assert_eq!(r[10] & 0xFFFF, code, "{op} {reg2} {reg1}");
+        assert_eq!(r[20] & flag, flag, "{op} {reg2} {reg1}");
+        // The destination is unchanged
+        assert_eq!(f32::from_bits(r[2]), reg2, "{op} {reg2} {reg1}");
+
+        // Otherwise only the flag is set
+        let r = float_operation(0, reg2, reg1, op);
+
+        assert_eq!(r[14], 0, "{op} {reg2} {reg1}");
+        assert_eq!(r[20] & flag, flag, "{op} {reg2} {reg1}");
+    }
+
+    // Underflow and precision loss never trap, even if enabled
+    let r = float_operation(0xF8, 1e-30, 1e-30, "mulf.s r1, r2");
+
+    assert_eq!(r[14], 0);
+    assert_eq!(r[20] & (PSW_FUD | PSW_FPR), PSW_FUD | PSW_FPR);
+    assert_eq!(f32::from_bits(r[2]), 0.0);
+
+    let r = float_operation(0xF8, 1.0, 3.0, "divf.s r1, r2");
+
+    assert_eq!(r[14], 0);
+    assert_eq!(r[20] & (PSW_FUD | PSW_FPR), PSW_FPR);
+}
+
+#[test]
+fn float_flags_are_sticky() {
+    let r = registers(&format!(
+        "
+        ldsr    r0, tkcw
+        {}
+        {}
+        {}
+        mov     r1, r4
+        divf.s  r3, r4
+        stsr    psw, r10
+        ; Exact, so raises no exceptions
+        addf.s  r1, r1
+        stsr    psw, r11
+        mov     r1, r4
+        divf.s  r0, r4
+        mov     r0, r4
+        divf.s  r0, r4
+        mulf.s  r2, r2
+        stsr    psw, r12
+        mov     r1, r4
+        mulf.s  r4, r4
+        stsr    psw, r13
+        ldsr    r0, psw
+        stsr    psw, r14
+        halt
+        ",
+        load_float(1.0, "r1"),
+        load_float(1e-30, "r2"),
+        load_float(3.0, "r3"),
+    ));
+
+    let flags = PSW_FPR | PSW_FUD | PSW_FOV | PSW_FZD | PSW_FIV;
+
+    assert_eq!(r[10] & flags, PSW_FPR);
+    assert_eq!(r[11] & flags, PSW_FPR);
+    assert_eq!(r[12] & flags, PSW_FPR | PSW_FUD | PSW_FZD | PSW_FIV);
+    assert_eq!(r[13] & flags, PSW_FPR | PSW_FUD | PSW_FZD | PSW_FIV);
+    // Only cleared by writing PSW
+    assert_eq!(r[14] & flags, 0);
+
+    let r = float_operation(0, 3e38, 3e38, "mulf.s r1, r2\naddf.s r0, r1");
+    assert_eq!(r[20] & PSW_FOV, PSW_FOV);
+}
+
+#[test]
+fn float_rounding_modes() {
+    // The rounding direction is in TKCW bits 0-1
+    let third = |tkcw: u32, dividend: f32| float_operation(tkcw, dividend, 3.0, "divf.s r1, r2")[2];
+
+    assert_eq!(third(0, 1.0), 0x3EAA_AAAB);
+    assert_eq!(third(1, 1.0), 0x3EAA_AAAA);
+    assert_eq!(third(2, 1.0), 0x3EAA_AAAB);
+    assert_eq!(third(3, 1.0), 0x3EAA_AAAA);
+
+    assert_eq!(third(0, -1.0), 0xBEAA_AAAB);
+    assert_eq!(third(1, -1.0), 0xBEAA_AAAA);
+    assert_eq!(third(2, -1.0), 0xBEAA_AAAA);
+    assert_eq!(third(3, -1.0), 0xBEAA_AAAB);
+
+    let convert =
+        |tkcw: u32, value: f32| float_operation(tkcw, 0.0, value, "cvt.sw r1, r2")[2] as i32;
+
+    // Ties round to even
+    assert_eq!(convert(0, 2.5), 2);
+    assert_eq!(convert(0, 3.5), 4);
+    assert_eq!(convert(2, 2.5), 3);
+    assert_eq!(convert(3, -2.5), -3);
+    // RDI truncates regardless of the rounding direction
+    assert_eq!(convert(0x4 | 2, 2.5), 2);
+    assert_eq!(convert(0x4 | 3, -2.5), -2);
+}
+
+#[test]
+fn float_exception_restore_point() {
+    // With EP already set, the zero division is duplexed, and FEPC points to the faulting instruction
+    let source = format!(
+        "
+        {}
+        mov     r0, r1
+        movea   0x4000, r0, r6
+        ldsr    r6, psw
+        faulting:
+        divf.s  r1, r2
+        stsr    psw, r13
+        halt
+
+        duplexed_exception:
+        stsr    ecr, r10
+        stsr    fepc, r11
+        stsr    eipc, r12
+        addi    4, r11, r6
+        ldsr    r6, fepc
+        reti
+        ",
+        load_float(1.0, "r2"),
+    );
+
+    let r = registers(&source);
+
+    assert_eq!(r[10] >> 16, 0xFF68);
+    assert_eq!(r[11], label(&source, "faulting"));
+    // EIPC is left for the outer handler
+    assert_eq!(r[12], 0);
+    // Returning restores FEPSW, still within the outer exception
+    assert_eq!(r[13] & (PSW_NP | PSW_EP | PSW_FZD), PSW_EP | PSW_FZD);
+    assert_eq!(f32::from_bits(r[2]), 1.0);
+}
+
+#[test]
+fn bit_string_search() {
+    let search = |op: &str, words: [u32; 2], offset: u32, length: u32| {
+        let virtualfriend = run(&format!(
+            "
+            movhi   0x0500, r0, r30
+            movhi   hi({}), r0, r1
+            movea   lo({}), r1, r1
+            st.w    r1, 0[r30]
+            movhi   hi({}), r0, r1
+            movea   lo({}), r1, r1
+            st.w    r1, 4[r30]
+            movea   {offset}, r0, r27
+            movea   {length}, r0, r28
+            mov     0, r29
+            {op}
+            stsr    psw, r20
+            halt
+            ",
+            words[0], words[0], words[1], words[1]
+        ));
+
+        virtualfriend.registers().general_purpose
+    };
+
+    // Found after skipping 8 bits
+    let r = search("sch1bsu", [0x0000_0100, 0], 0, 32);
+    assert_eq!(r[29], 8);
+    assert_eq!(r[20] & Z, 0);
+
+    // Starts at the bit offset in r27
+    let r = search("sch1bsu", [0x0000_0300, 0], 9, 32);
+    assert_eq!(r[29], 0);
+    assert_eq!(r[20] & Z, 0);
+
+    // Searching downward from bit 31
+    let r = search("sch1bsd", [0x0000_0100, 0], 31, 32);
+    assert_eq!(r[29], 23);
+    assert_eq!(r[20] & Z, 0);
+
+    // Crosses into the next word
+    let r = search("sch0bsu", [0xFFFF_FFFF, 0xFFFF_FFFE], 0, 40);
+    assert_eq!(r[29], 32);
+    assert_eq!(r[20] & Z, 0);
+
+    // Not found
+    let r = search("sch0bsu", [0xFFFF_FFFF, 0xFFFF_FFFF], 0, 40);
+    assert_eq!(r[29], 40);
+    assert_eq!(r[28], 0);
+    assert_eq!(r[20] & Z, Z);
+
+    let r = search("sch1bsd", [0, 0], 31, 16);
+    assert_eq!(r[29], 16);
+    assert_eq!(r[28], 0);
+    assert_eq!(r[20] & Z, Z);
+}
+
+#[test]
+fn bit_string_transfer() {
+    const SOURCE: [u32; 3] = [0x1234_5678, 0x9ABC_DEF0, 0x0F1E_2D3C];
+    const DEST: [u32; 3] = [0xFFFF_0000, 0x0000_FFFF, 0xA5A5_A5A5];
+
+    let ops: [(&str, fn(bool, bool) -> bool); 8] = [
+        ("orbsu", |dest, source| dest | source),
+        ("andbsu", |dest, source| dest & source),
+        ("xorbsu", |dest, source| dest ^ source),
+        ("movbsu", |_, source| source),
+        ("ornbsu", |dest, source| dest | !source),
+        ("andnbsu", |dest, source| dest & !source),
+        ("xornbsu", |dest, source| dest ^ !source),
+        ("notbsu", |_, source| !source),
+    ];
+
+    let (source_offset, dest_offset, length) = (4, 8, 48);
+
+    for (op, operation) in ops {
+        let mut source = String::from("movhi 0x0500, r0, r30\nmovea 0x100, r30, r29\n");
+
+        for (i, (source_word, dest_word)) in SOURCE.iter().zip(DEST.iter()).enumerate() {
+            source += &format!(
+                "
+                movhi   hi({source_word}), r0, r1
+                movea   lo({source_word}), r1, r1
+                st.w    r1, {}[r30]
+                movhi   hi({dest_word}), r0, r1
+                movea   lo({dest_word}), r1, r1
+                st.w    r1, {}[r29]
+                ",
+                i * 4,
+                i * 4
+            );
+        }
+
+        source += &format!(
+            "
+            movea   {source_offset}, r0, r27
+            movea   {dest_offset}, r0, r26
+            movea   {length}, r0, r28
+            {op}
+            halt
+            "
+        );
+
+        let virtualfriend = run(&source);
+
+        let mut expected = DEST;
+
+        for i in 0..length {
+            let source_bit =
+                SOURCE[(source_offset + i) / 32] >> ((source_offset + i) % 32) & 1 != 0;
+
+            let dest_index = (dest_offset + i) / 32;
+            let dest_bit = 1 << ((dest_offset + i) % 32);
+
+            if operation(expected[dest_index] & dest_bit != 0, source_bit) {
+                expected[dest_index] |= dest_bit;
+            } else {
+                expected[dest_index] &= !dest_bit;
+            }
+        }
+
+        assert_eq!(read_u32s(&virtualfriend, WRAM + 0x100, 3), expected, "{op}");
+
+        let r = virtualfriend.registers().general_purpose;
+
+        // The registers point to the end of the strings
+        assert_eq!(r[28], 0, "{op}");
+        assert_eq!(r[27], ((source_offset + length) % 32) as u32, "{op}");
+        assert_eq!(r[26], ((dest_offset + length) % 32) as u32, "{op}");
+        assert_eq!(
+            r[30],
+            WRAM + ((source_offset + length) / 32 * 4) as u32,
+            "{op}"
+        );
+        assert_eq!(
+            r[29],
+            WRAM + 0x100 + ((dest_offset + length) / 32 * 4) as u32,
+            "{op}"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+// Pins the cycle counts of instructions whose execution time depends on their operands. Each timed instruction is
+// run once from ROM, with its operands loaded beforehand, and the cycles the profiler attributes to it compared
+
+use common::new_virtualfriend;
+use virtualfriend::{
+    assembler::{assemble, ROM_BASE_ADDRESS},
+    gamepad::GamepadInputs,
+};
+
+mod common;
+
+/// Runs `setup`, then `instruction`, returning the cycles spent executing `instruction`. Fetches from ROM with the
+/// power on wait states add no cycles of their own
+fn cycles(setup: &str, instruction: &str) -> u64 {
+    let source = format!(
+        "
+    ldsr    r0, psw
+{setup}
+timed:
+    {instruction}
+
+idle:
+    halt
+    br      idle
+"
+    );
+
+    let program = assemble(ROM_BASE_ADDRESS, &source).unwrap();
+    let timed = program.label("timed").unwrap();
+
+    let mut virtualfriend = new_virtualfriend(&source);
+
+    virtualfriend.start_profiling();
+
+    for _ in 0..2 {
+        let frame = virtualfriend.run_video_frame(GamepadInputs::default());
+        assert!(frame.crash.is_none());
+    }
+
+    let profile = virtualfriend.stop_profiling().unwrap();
+
+    profile.pc_cycles[&timed]
+}
+
+/// Loads the floats `reg2` and `reg1` into r2 and r1
+fn float_operands(reg2: f32, reg1: f32) -> String {
+    format!(
+        "
+    movhi   hi({reg2}), r0, r2
+    movea   lo({reg2}), r2, r2
+    movhi   hi({reg1}), r0, r1
+    movea   lo({reg1}), r1, r1
+",
+        reg2 = reg2.to_bits(),
+        reg1 = reg1.to_bits(),
+    )
+}
+
+#[test]
+fn addf_depends_on_shifts() {
+    // Nothing to align or normalize
+    assert_eq!(cycles(&float_operands(1.5, 0.0), "addf.s r1, r2"), 9);
+    // One normalizing shift
+    assert_eq!(cycles(&float_operands(1.0, 1.0), "addf.s r1, r2"), 9);
+    // 23 aligning shifts, out of 48 possible shifts over the range of 9-28 cycles
+    assert_eq!(
+        cycles(&float_operands(1.0, f32::EPSILON), "addf.s r1, r2"),
+        9 + 19 * 23 / 48
+    );
+}
+
+#[test]
+fn subf_and_cmpf_depend_on_cancellation() {
+    // Cancelling to zero requires a full normalization of 24 shifts
+    assert_eq!(
+        cycles(&float_operands(3.0, 3.0), "subf.s r1, r2"),
+        12 + 16 * 24 / 48
+    );
+    assert_eq!(
+        cycles(&float_operands(3.0, 3.0), "cmpf.s r1, r2"),
+        7 + 3 * 24 / 48
+    );
+
+    assert_eq!(cycles(&float_operands(3.0, 0.0), "subf.s r1, r2"), 12);
+    assert_eq!(cycles(&float_operands(3.0, 0.0), "cmpf.s r1, r2"), 7);
+}
+
+#[test]
+fn mulf_depends_on_multiplier_bits() {
+    assert_eq!(cycles(&float_operands(1.5, 0.0), "mulf.s r1, r2"), 8);
+    // A single significant bit
+    assert_eq!(cycles(&float_operands(1.5, 1.0), "mulf.s r1, r2"), 8);
+    // Two significant bits, and the product of 2.25 needs one normalizing shift
+    assert_eq!(
+        cycles(&float_operands(1.5, 1.5), "mulf.s r1, r2"),
+        8 + 22 * 2 / 24
+    );
+    // All 24 bits are significant
+    assert_eq!(
+        cycles(&float_operands(1.0, 2.0 - f32::EPSILON), "mulf.s r1, r2"),
+        8 + 22 * 23 / 24
+    );
+}
+
+#[test]
+fn conversions_depend_on_magnitude() {
+    let int_operand = |value: i32| {
+        format!(
+            "
+    movhi   hi({value}), r0, r1
+    movea   lo({value}), r1, r1
+",
+            value = value as u32,
+        )
+    };
+
+    assert_eq!(cycles(&int_operand(0), "cvt.ws r1, r2"), 5);
+    // 31 normalizing shifts, out of 32 over the range of 5-16 cycles
+    assert_eq!(cycles(&int_operand(1), "cvt.ws r1, r2"), 5 + 11 * 31 / 32);
+    // One normalizing shift, and rounding to fit the mantissa
+    assert_eq!(
+        cycles(&int_operand(i32::MAX), "cvt.ws r1, r2"),
+        5 + 11 * 2 / 32
+    );
+
+    assert_eq!(cycles(&float_operands(0.0, 0.0), "cvt.sw r2, r3"), 9);
+    // The binary point is already below the mantissa
+    assert_eq!(
+        cycles(&float_operands(8_388_608.0, 0.0), "cvt.sw r2, r3"),
+        9
+    );
+    // 23 shifts, out of 32 over the range of 9-14 cycles
+    assert_eq!(
+        cycles(&float_operands(1.0, 0.0), "trnc.sw r2, r3"),
+        9 + 5 * 23 / 32
+    );
+}
+
+/// Stores `words` to WRAM and points the bit string registers at them, with the destination at 0x05000100
+fn bit_string_operands(
+    words: [u32; 2],
+    source_offset: u32,
+    dest_offset: u32,
+    length: u32,
+) -> String {
+    format!(
+        "
+    movhi   0x0500, r0, r30
+    movea   0x100, r30, r29
+    movhi   hi({first}), r0, r1
+    movea   lo({first}), r1, r1
+    st.w    r1, 0[r30]
+    movhi   hi({second}), r0, r1
+    movea   lo({second}), r1, r1
+    st.w    r1, 4[r30]
+    movea   {source_offset}, r0, r27
+    movea   {dest_offset}, r0, r26
+    movea   {length}, r0, r28
+",
+        first = words[0],
+        second = words[1],
+    )
+}
+
+#[test]
+fn bit_string_search_is_charged_per_word() {
+    // Starting, then reading and scanning one word
+    assert_eq!(
+        cycles(
+            &(bit_string_operands([0x0000_0100, 0], 0, 0, 32) + "mov r0, r29"),
+            "sch1bsu"
+        ),
+        13 + 3 + 2
+    );
+    // A second word is read and scanned without starting again
+    assert_eq!(
+        cycles(
+            &(bit_string_operands([0xFFFF_FFFF, 0xFFFF_FFFE], 0, 0, 40) + "mov r0, r29"),
+            "sch0bsu"
+        ),
+        13 + 2 * (3 + 2)
+    );
+}
+
+#[test]
+fn bit_string_transfer_is_charged_per_word() {
+    // Starting, reading the source word, then reading and writing the destination word
+    assert_eq!(
+        cycles(&bit_string_operands([0, 0], 0, 0, 32), "movbsu"),
+        13 + 3 + 3 + 1
+    );
+    assert_eq!(
+        cycles(&bit_string_operands([0, 0], 0, 0, 64), "movbsu"),
+        13 + 2 * (3 + 3 + 1)
+    );
+    // Misaligned offsets read two source words for the destination word, and shift them into place
+    assert_eq!(
+        cycles(&bit_string_operands([0, 0], 4, 0, 32), "movbsu"),
+        13 + 2 * 3 + 3 + 1 + 2
+    );
+}
@@ -0,0 +1,58 @@
+use common::build_rom;
+use virtualfriend::{
+    config::{EmulatorConfig, IllegalOpcodeBehavior},
+    crash::CrashCause,
+    gamepad::GamepadInputs,
+    VirtualFriend,
+};
+
+mod common;
+
+const PROGRAM: &[u16] = &[
+    0x4541, // 0x07000000 add 1, r10
+    0xD800, // 0x07000002 Reserved opcode
+    0x4541, // 0x07000004 add 1, r10
+];
+
+#[test]
+fn reports_invalid_instruction() {
+    let mut virtualfriend = VirtualFriend::new_with_config(
+        build_rom(PROGRAM),
+        EmulatorConfig {
+            illegal_opcode: IllegalOpcodeBehavior::HaltAndReport,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    // The first frame only executes the reset vector
+    virtualfriend.run_video_frame(GamepadInputs::default());
+
+    let frame = virtualfriend.run_video_frame(GamepadInputs::default());
+
+    assert!(frame.video.is_none());
+
+    let crash = frame.crash.expect("Expected crash");
+
+    assert_eq!(
+        crash.cause,
+        CrashCause::InvalidInstruction {
+            instruction: 0xD800
+        }
+    );
+    assert_eq!(crash.pc, 0x0700_0002);
+    // Execution stopped at the invalid instruction
+    assert_eq!(crash.registers.general_purpose[10], 1);
+    assert_eq!(
+        crash.recent_pcs[crash.recent_pcs.len() - 2..],
+        [0x0700_0000, 0x0700_0002]
+    );
+
+    let offset = crash.pc.wrapping_sub(crash.memory_address) as usize;
+    assert_eq!(crash.memory[offset..offset + 4], [0x00, 0xD8, 0x41, 0x45]);
+
+    // The system remains crashed
+    let frame = virtualfriend.run_video_frame(GamepadInputs::default());
+
+    assert_eq!(frame.crash, Some(crash));
+}
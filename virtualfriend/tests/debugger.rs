@@ -0,0 +1,255 @@
+use common::new_virtualfriend;
+use virtualfriend::{
+    assembler::{assemble, ROM_BASE_ADDRESS},
+    debugger::{StopReason, WatchAccess, WatchKind, Watchpoint},
+    gamepad::GamepadInputs,
+    VirtualFriend,
+};
+
+mod common;
+
+/// Calls `count` with r10 = 3. `count` recurses until r10 reaches 0, saving r31 on the stack at r3, and increments
+/// r11 as each call returns
+const RECURSIVE_PROGRAM: &str = "
+    ldsr    r0, psw
+    movhi   0x0501, r0, sp
+    mov     3, r10
+call:
+    jal     count
+after:
+    halt
+    br      after
+
+count:
+    add     -4, sp
+    st.w    r31, 0[sp]
+    cmp     0, r10
+    bz      done
+    add     -1, r10
+recurse:
+    jal     count
+done:
+    ld.w    0[sp], r31
+    add     4, sp
+    add     1, r11
+    jmp     [r31]
+";
+
+/// Calls `outer`, which calls `inner` twice, which itself calls `innermost`. Each call increments r12
+const NESTED_PROGRAM: &str = "
+    ldsr    r0, psw
+call:
+    jal     outer
+after:
+    halt
+    br      after
+
+outer:
+    add     1, r12
+    mov     r31, r20
+    jal     inner
+    jal     inner
+    mov     r20, r31
+    jmp     [r31]
+
+inner:
+    add     1, r12
+    mov     r31, r21
+    jal     innermost
+    mov     r21, r31
+    jmp     [r31]
+
+innermost:
+    add     1, r12
+    jmp     [r31]
+";
+
+fn label(source: &str, name: &str) -> u32 {
+    assemble(ROM_BASE_ADDRESS, source)
+        .unwrap()
+        .label(name)
+        .unwrap()
+}
+
+/// Runs frames until the debugger stops execution
+fn run_until_stop(virtualfriend: &mut VirtualFriend) -> StopReason {
+    for _ in 0..10 {
+        let frame = virtualfriend.run_video_frame(GamepadInputs::default());
+        assert!(frame.crash.is_none());
+
+        if let Some(stop_reason) = frame.stop_reason {
+            return stop_reason;
+        }
+    }
+
+    panic!("Execution never stopped");
+}
+
+#[test]
+fn step_over_skips_recursive_calls() {
+    let call = label(RECURSIVE_PROGRAM, "call");
+    let after = label(RECURSIVE_PROGRAM, "after");
+
+    let mut virtualfriend = new_virtualfriend(RECURSIVE_PROGRAM);
+    virtualfriend.add_breakpoint(call);
+
+    assert_eq!(
+        run_until_stop(&mut virtualfriend),
+        StopReason::Breakpoint { pc: call }
+    );
+
+    virtualfriend.step_over();
+
+    // Every level of the recursion returns before the step completes
+    assert_eq!(
+        run_until_stop(&mut virtualfriend),
+        StopReason::Step { pc: after }
+    );
+    assert_eq!(virtualfriend.registers().general_purpose[11], 4);
+}
+
+#[test]
+fn step_over_within_recursion_stops_at_same_depth() {
+    let recurse = label(RECURSIVE_PROGRAM, "recurse");
+    let done = label(RECURSIVE_PROGRAM, "done");
+
+    let mut virtualfriend = new_virtualfriend(RECURSIVE_PROGRAM);
+    virtualfriend.add_breakpoint(recurse);
+
+    assert_eq!(
+        run_until_stop(&mut virtualfriend),
+        StopReason::Breakpoint { pc: recurse }
+    );
+
+    let stack_pointer = virtualfriend.registers().general_purpose[3];
+    virtualfriend.remove_breakpoint(recurse);
+    virtualfriend.step_over();
+
+    // The deeper calls return to `done` first, but with the stack pointer below that of the stepped call
+    assert_eq!(
+        run_until_stop(&mut virtualfriend),
+        StopReason::Step { pc: done }
+    );
+
+    let registers = virtualfriend.registers();
+    assert_eq!(registers.general_purpose[3], stack_pointer);
+    assert_eq!(registers.general_purpose[11], 3);
+}
+
+#[test]
+fn run_until_return_skips_nested_returns() {
+    let outer = label(NESTED_PROGRAM, "outer");
+    let after = label(NESTED_PROGRAM, "after");
+
+    let mut virtualfriend = new_virtualfriend(NESTED_PROGRAM);
+    virtualfriend.add_breakpoint(outer);
+
+    assert_eq!(
+        run_until_stop(&mut virtualfriend),
+        StopReason::Breakpoint { pc: outer }
+    );
+
+    virtualfriend.run_until_return();
+
+    // `outer`, both calls to `inner`, and both calls to `innermost` have run
+    assert_eq!(
+        run_until_stop(&mut virtualfriend),
+        StopReason::Step { pc: after }
+    );
+    assert_eq!(virtualfriend.registers().general_purpose[12], 5);
+}
+
+#[test]
+fn read_watchpoints_ignore_instruction_fetches() {
+    // Copies `routine` to WRAM, calls it there, then reads back the first halfword of it and of itself in ROM
+    let source = "
+    ldsr    r0, psw
+    movhi   hi(routine), r0, r6
+    movea   lo(routine), r6, r6
+    movhi   0x0500, r0, r7
+    ld.w    0[r6], r8
+    st.w    r8, 0[r7]
+    jal     call_wram
+wram_read:
+    ld.h    0[r7], r9
+rom_read:
+    ld.h    0[r6], r9
+    halt
+
+call_wram:
+    jmp     [r7]
+
+    .align  4
+routine:
+    add     1, r10
+    jmp     [r31]
+";
+
+    let routine = label(source, "routine");
+    let wram_read = label(source, "wram_read");
+    let rom_read = label(source, "rom_read");
+
+    let mut virtualfriend = new_virtualfriend(source);
+
+    // The routine executes from both WRAM and ROM, but only the loads stop
+    let wram = virtualfriend.add_watchpoint(Watchpoint {
+        range: 0x0500_0000..=0x0500_0003,
+        kind: WatchKind::Read,
+        value: None,
+    });
+    let rom = virtualfriend.add_watchpoint(Watchpoint {
+        range: ROM_BASE_ADDRESS..=routine + 3,
+        kind: WatchKind::Read,
+        value: None,
+    });
+
+    // The copy reads the routine in ROM
+    let StopReason::Watchpoint { hit, .. } = run_until_stop(&mut virtualfriend) else {
+        panic!("Expected a watchpoint");
+    };
+    assert_eq!((hit.id, hit.address), (rom, routine));
+
+    let StopReason::Watchpoint { pc, hit } = run_until_stop(&mut virtualfriend) else {
+        panic!("Expected a watchpoint");
+    };
+    assert_eq!(pc, wram_read);
+    assert_eq!(hit.id, wram);
+    assert_eq!(hit.access, WatchAccess::Read);
+    assert_eq!(virtualfriend.registers().general_purpose[10], 1);
+
+    let StopReason::Watchpoint { pc, hit } = run_until_stop(&mut virtualfriend) else {
+        panic!("Expected a watchpoint");
+    };
+    assert_eq!(pc, rom_read);
+    assert_eq!((hit.id, hit.address), (rom, routine));
+}
+
+#[test]
+fn watchpoints_survive_loading_savestates() {
+    let source = "
+    ldsr    r0, psw
+    movhi   0x0500, r0, r6
+loop:
+    add     1, r10
+    st.w    r10, 0[r6]
+    br      loop
+";
+
+    let mut virtualfriend = new_virtualfriend(source);
+    let savestate = virtualfriend.create_savestate();
+
+    let id = virtualfriend.add_watchpoint(Watchpoint {
+        range: 0x0500_0000..=0x0500_0001,
+        kind: WatchKind::Write,
+        value: Some(3),
+    });
+
+    virtualfriend.load_savestate(&savestate);
+
+    let StopReason::Watchpoint { hit, .. } = run_until_stop(&mut virtualfriend) else {
+        panic!("Expected a watchpoint");
+    };
+    assert_eq!(hit.id, id);
+    assert_eq!(hit.access, WatchAccess::Write);
+    assert_eq!(virtualfriend.registers().general_purpose[10], 3);
+}
@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use common::new_virtualfriend_with_config;
+use virtualfriend::{
+    config::{EmulatorConfig, FixedClock, PowerOnState},
+    gamepad::GamepadInputs,
+    VirtualFriend,
+};
+
+mod common;
+
+/// Displays the power on contents of WRAM, and plays them as a waveform
+const PROGRAM: &str = "
+    ldsr    r0, psw
+
+    // Copy WRAM over both framebuffers of both eyes. WRAM is mirrored past 64KB
+    movhi   0x0500, r0, r6
+    mov     r0, r7
+    movhi   0x2, r0, r8
+copy:
+    ld.w    0x0[r6], r9
+    st.w    r9, 0x0[r7]
+    add     0x4, r6
+    add     0x4, r7
+    cmp     r8, r7
+    bnz     copy
+
+    // Waveform 0 from the first 32 bytes of WRAM
+    movhi   0x0500, r0, r6
+    movhi   0x0100, r0, r7
+    movea   0x80, r7, r8
+wave:
+    ld.b    0x0[r6], r9
+    andi    0x3F, r9, r9
+    st.b    r9, 0x0[r7]
+    add     0x1, r6
+    add     0x4, r7
+    cmp     r8, r7
+    bnz     wave
+
+    movhi   0x0100, r0, r7
+    movea   0xFF, r0, r6
+    st.b    r6, 0x404[r7]
+    movea   0x80, r0, r6
+    st.b    r6, 0x408[r7]
+    mov     0x6, r6
+    st.b    r6, 0x40C[r7]
+    movea   0xF0, r0, r6
+    st.b    r6, 0x410[r7]
+    st.b    r0, 0x418[r7]
+    movea   0x80, r0, r6
+    st.b    r6, 0x400[r7]
+
+    // Brightness, then display with sync
+    movhi   hi(0x0005F800), r0, r21
+    movea   lo(0x0005F800), r21, r21
+    movea   0x20, r0, r6
+    st.h    r6, 0x24[r21]
+    movea   0x40, r0, r6
+    st.h    r6, 0x26[r21]
+    st.h    r6, 0x28[r21]
+    movea   0x202, r0, r6
+    st.h    r6, 0x22[r21]
+
+idle:
+    halt
+    br      idle
+";
+
+fn new_virtualfriend(power_on: PowerOnState) -> VirtualFriend {
+    new_virtualfriend_with_config(
+        PROGRAM,
+        EmulatorConfig {
+            power_on,
+            clock: Arc::new(FixedClock(1_700_000_000)),
+            ..Default::default()
+        },
+    )
+}
+
+fn inputs(frame: usize) -> GamepadInputs {
+    GamepadInputs {
+        start: frame % 4 == 0,
+        ..Default::default()
+    }
+}
+
+/// Runs `frame_count` frames, returning every frame's video, followed by all of the audio
+fn run(virtualfriend: &mut VirtualFriend, frame_count: usize) -> (Vec<Vec<u8>>, Vec<(i16, i16)>) {
+    let mut video = Vec::new();
+    let mut audio = Vec::new();
+
+    for frame in 0..frame_count {
+        let frame = virtualfriend.run_video_frame(inputs(frame));
+
+        let frame_video = frame.video.unwrap();
+
+        video.push(frame_video.left);
+        video.push(frame_video.right);
+        audio.extend(frame.audio_buffer);
+    }
+
+    (video, audio)
+}
+
+#[test]
+fn identical_seeds_produce_identical_output() {
+    let mut first = new_virtualfriend(PowerOnState::RandomSeeded(0x1234));
+    let mut second = new_virtualfriend(PowerOnState::RandomSeeded(0x1234));
+
+    let (first_video, first_audio) = run(&mut first, 8);
+    let (second_video, second_audio) = run(&mut second, 8);
+
+    assert_eq!(first_video, second_video);
+    assert_eq!(first_audio, second_audio);
+
+    // WRAM is displayed
+    assert!(first_video.last().unwrap().iter().any(|pixel| *pixel != 0));
+    assert!(first_audio.iter().any(|(left, _)| *left != 0));
+
+    assert_eq!(
+        first.create_savestate().data(),
+        second.create_savestate().data()
+    );
+    assert_eq!(first.create_savestate().timestamp_s, 1_700_000_000);
+}
+
+#[test]
+fn different_seeds_produce_different_output() {
+    let mut first = new_virtualfriend(PowerOnState::RandomSeeded(1));
+    let mut second = new_virtualfriend(PowerOnState::RandomSeeded(2));
+
+    let (first_video, _) = run(&mut first, 4);
+    let (second_video, _) = run(&mut second, 4);
+
+    assert_ne!(first_video, second_video);
+}
+
+#[test]
+fn zeroed_power_on_clears_wram() {
+    let virtualfriend = new_virtualfriend(PowerOnState::Zeroed);
+
+    assert!(virtualfriend
+        .read_memory(0x0500_0000, 0x1_0000)
+        .iter()
+        .all(|byte| *byte == 0));
+}
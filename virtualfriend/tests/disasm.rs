@@ -0,0 +1,138 @@
+use virtualfriend::{
+    assembler::{assemble, ROM_BASE_ADDRESS},
+    disasm::{decode, disassemble, Instruction, Operation},
+    instruction::Opcode,
+};
+
+/// System register IDs the assembler has names for
+const SYSTEM_REGISTERS: [u16; 10] = [0, 1, 2, 3, 4, 5, 6, 7, 24, 25];
+
+#[test]
+fn decodes_every_opcode_as_the_cpu_does() {
+    // The CPU dispatches on `Opcode::from_halfword`, and fetches a second halfword for the formats that carry one
+    for halfword in 0..=u16::MAX {
+        let instruction = decode(ROM_BASE_ADDRESS, halfword, 0);
+
+        match Opcode::from_halfword(halfword) {
+            Some(opcode) => {
+                let size = if opcode.instruction_format().has_second_halfword() {
+                    4
+                } else {
+                    2
+                };
+
+                assert_eq!(instruction.size, size, "{halfword:#06X}");
+
+                if let Operation::Opcode(decoded) = instruction.operation {
+                    assert_eq!(decoded, opcode, "{halfword:#06X}");
+                }
+            }
+            None => {
+                assert_eq!(instruction.operation, Operation::Invalid, "{halfword:#06X}");
+                assert_eq!(instruction.size, 2, "{halfword:#06X}");
+            }
+        }
+    }
+}
+
+/// Encodings of every opcode, with a variety of operands. Fields that select a sub-operation are enumerated
+fn encodings() -> Vec<(u16, u16)> {
+    let mut encodings = Vec::new();
+
+    for opcode in 0..64 {
+        // Register 18 sets bit 9, which selects the odd Bcond conditions
+        for reg2 in [3, 18] {
+            let halfword = (opcode << 10) | (reg2 << 5);
+
+            let (reg1s, second_halfwords): (Vec<u16>, Vec<u16>) =
+                match Opcode::from_halfword(halfword) {
+                    Some(Opcode::BitString) => ((0..32).collect(), vec![0]),
+                    Some(Opcode::Setf) => ((0..16).collect(), vec![0]),
+                    Some(Opcode::Ldsr | Opcode::Stsr) => (SYSTEM_REGISTERS.to_vec(), vec![0]),
+                    Some(Opcode::Extended) => (vec![4], (0..64).map(|subop| subop << 10).collect()),
+                    // Even, as branch displacements are
+                    _ => (vec![4, 26], vec![0x0004, 0x8004]),
+                };
+
+            for &reg1 in &reg1s {
+                for &second_halfword in &second_halfwords {
+                    encodings.push((halfword | reg1, second_halfword));
+                }
+            }
+        }
+    }
+
+    encodings
+}
+
+#[test]
+fn every_instruction_round_trips_through_assembler() {
+    let address = ROM_BASE_ADDRESS + 0x1000;
+
+    for (halfword, second_halfword) in encodings() {
+        let instruction = decode(address, halfword, second_halfword);
+
+        if instruction.operation == Operation::Invalid {
+            continue;
+        }
+
+        let text = instruction.to_string();
+
+        let program = assemble(address, &text).unwrap_or_else(|error| panic!("{text}: {error}"));
+
+        assert_eq!(program.bytes.len() as u32, instruction.size, "{text}");
+
+        let reassembled = disassemble(address, &program.bytes);
+
+        // Fields the instruction ignores may differ, but the instruction must be the same
+        let same = |instruction: &Instruction| {
+            (
+                instruction.size,
+                instruction.operation,
+                instruction.operands,
+            )
+        };
+
+        assert_eq!(reassembled.len(), 1, "{text}");
+        assert_eq!(same(&reassembled[0]), same(&instruction), "{text}");
+        assert_eq!(reassembled[0].to_string(), text);
+    }
+}
+
+#[test]
+fn disassembles_assembled_program() {
+    let program = assemble(
+        ROM_BASE_ADDRESS,
+        "
+        loop:
+            movhi   0x0500, r0, r6
+            ld.w    -0x4[r6], r7
+            st.h    r7, 0x2[r6]
+            addf.s  r7, r8
+            bnz     loop
+            jal     loop
+            halt
+        ",
+    )
+    .unwrap();
+
+    let instructions = disassemble(program.base_address, &program.bytes);
+
+    let sizes = instructions
+        .iter()
+        .map(|instruction| instruction.size)
+        .collect::<Vec<_>>();
+
+    assert_eq!(sizes, [4, 4, 4, 4, 2, 4, 2]);
+    assert_eq!(instructions[1].next_address(), instructions[2].address);
+
+    let disassembly = instructions
+        .iter()
+        .map(|instruction| instruction.to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(disassembly[1], "ld.w    -0x4[r6], r7");
+    assert_eq!(disassembly[2], "st.h    r7, 0x2[r6]");
+    assert_eq!(disassembly[4], "bnz     0x07000000");
+    assert_eq!(disassembly[5], "jal     0x07000000");
+}
@@ -0,0 +1,111 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    thread,
+};
+
+use common::build_rom;
+use virtualfriend::{gamepad::GamepadInputs, gdb::GdbServer, VirtualFriend};
+
+mod common;
+
+/// Loops forever, storing an incrementing counter to WRAM and calling an empty function
+const PROGRAM: &[u16] = &[
+    0xBC40, 0x0500, // 0x07000000 movhi 0x0500, r0, r2
+    0x4541, // 0x07000004 add 1, r10
+    0xD542, 0x0000, // 0x07000006 st.h r10, 0x0[r2]
+    0xAC00, 0x0006, // 0x0700000A jal 0x07000010
+    0x8BF6, // 0x0700000E br 0x07000004
+    0x181F, // 0x07000010 jmp [r31]
+];
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn request(&mut self, data: &str) -> String {
+        let checksum = data.bytes().fold(0_u8, |sum, byte| sum.wrapping_add(byte));
+
+        write!(self.stream, "${data}#{checksum:02x}").unwrap();
+
+        let mut response = Vec::new();
+        let mut byte = [0; 1];
+
+        // Ack
+        self.stream.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], b'+');
+
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            response.push(byte[0]);
+
+            if response.len() >= 3 && response[response.len() - 3] == b'#' {
+                break;
+            }
+        }
+
+        self.stream.write_all(b"+").unwrap();
+
+        let response = String::from_utf8(response).unwrap();
+
+        response[1..response.len() - 3].to_string()
+    }
+}
+
+#[test]
+fn gdb_breakpoint_step_registers_and_memory() {
+    let server = GdbServer::bind(0).unwrap();
+    let address = server.local_addr().unwrap();
+
+    let emulator = thread::spawn(move || {
+        let mut virtualfriend = VirtualFriend::new(build_rom(PROGRAM)).unwrap();
+
+        server
+            .serve(&mut virtualfriend, GamepadInputs::default())
+            .unwrap();
+    });
+
+    let mut client = Client {
+        stream: TcpStream::connect(address).unwrap(),
+    };
+
+    assert_eq!(client.request("?"), "S05");
+
+    // Break on the JAL
+    assert_eq!(client.request("Z0,700000a,2"), "OK");
+    assert_eq!(client.request("c"), "T05swbreak:;");
+
+    // PC (register 64) in target byte order
+    assert_eq!(client.request("p40"), "0a000007");
+    // r10 has been incremented once
+    assert_eq!(client.request("pa"), "01000000");
+    // The counter was stored to WRAM
+    assert_eq!(client.request("m5000000,2"), "0100");
+
+    let registers = client.request("g");
+    assert_eq!(registers.len(), 65 * 8);
+    assert_eq!(&registers[64 * 8..], "0a000007");
+
+    // Stepping the JAL enters the function
+    assert_eq!(client.request("z0,700000a,2"), "OK");
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p40"), "10000007");
+    // Link pointer holds the return address
+    assert_eq!(client.request("p1f"), "0e000007");
+
+    // Register and memory writes
+    assert_eq!(client.request("Pa=2a000000"), "OK");
+    assert_eq!(client.request("pa"), "2a000000");
+    assert_eq!(client.request("M5000010,4:78563412"), "OK");
+    assert_eq!(client.request("m5000010,4"), "78563412");
+
+    // Write watchpoint on the counter
+    assert_eq!(client.request("Z2,5000000,2"), "OK");
+    assert_eq!(client.request("c"), "T05watch:05000000;");
+    assert_eq!(client.request("m5000000,2"), "2b00");
+
+    assert_eq!(client.request("D"), "OK");
+
+    emulator.join().unwrap();
+}
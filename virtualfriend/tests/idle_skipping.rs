@@ -0,0 +1,189 @@
+use common::{new_virtualfriend_with_config, read_u16s, read_u32s, zeroed_config};
+use virtualfriend::{config::EmulatorConfig, gamepad::GamepadInputs, Frame, VirtualFriend};
+
+mod common;
+
+/// Plays a tone, and runs a main loop that waits on the gamepad, a WRAM flag set by the VIP interrupt, XPSTTS, and
+/// HALT, while VIP and timer interrupts count into WRAM
+const PROGRAM: &str = "
+    ldsr    r0, psw
+    movhi   0x0500, r0, r20
+    movhi   0x0200, r0, r22
+    movhi   0x0100, r0, r23
+    movhi   hi(0x0005F800), r0, r21
+    movea   lo(0x0005F800), r21, r21
+
+    st.w    r0, 0x0[r20]
+    st.w    r0, 0x4[r20]
+    st.w    r0, 0x8[r20]
+
+    // Sawtooth in waveform 0
+    mov     r23, r7
+    mov     r0, r6
+    movea   0x20, r0, r8
+wave:
+    st.b    r6, 0x0[r7]
+    add     0x4, r7
+    add     0x1, r6
+    cmp     r8, r6
+    bnz     wave
+
+    // Channel 1 with a looping envelope
+    movea   0xFF, r0, r6
+    st.b    r6, 0x404[r23]
+    movea   0x80, r0, r6
+    st.b    r6, 0x408[r23]
+    mov     0x6, r6
+    st.b    r6, 0x40C[r23]
+    movea   0xF0, r0, r6
+    st.b    r6, 0x410[r23]
+    mov     0x3, r6
+    st.b    r6, 0x414[r23]
+    st.b    r0, 0x418[r23]
+    movea   0x80, r0, r6
+    st.b    r6, 0x400[r23]
+
+    // Display and drawing enabled, with FRAMESTART and XPEND interrupts
+    movea   0x202, r0, r6
+    st.h    r6, 0x22[r21]
+    mov     0x2, r6
+    st.h    r6, 0x42[r21]
+    movea   0x4010, r0, r6
+    st.h    r6, 0x2[r21]
+
+    // Timer interrupt every 600us
+    movea   0x1E, r0, r6
+    st.b    r6, 0x18[r22]
+    st.b    r0, 0x1C[r22]
+    movea   0x19, r0, r6
+    st.b    r6, 0x20[r22]
+
+main:
+    mov     0x4, r6
+    st.b    r6, 0x28[r22]
+poll_gamepad:
+    ld.b    0x28[r22], r6
+    andi    0x2, r6, r6
+    bnz     poll_gamepad
+    in.b    0x10[r22], r7
+    in.b    0x14[r22], r8
+    shl     0x8, r8
+    or      r8, r7
+    st.h    r7, 0x8[r20]
+
+    ld.w    0x0[r20], r9
+wait_vip:
+    ld.w    0x0[r20], r10
+    cmp     r9, r10
+    bz      wait_vip
+
+poll_drawing:
+    in.h    0x40[r21], r6
+    andi    0xC, r6, r6
+    bnz     poll_drawing
+
+    halt
+    br      main
+
+vip_interrupt:
+    in.h    0x0[r21], r25
+    st.h    r25, 0x4[r21]
+    ld.w    0x0[r20], r26
+    add     0x1, r26
+    st.w    r26, 0x0[r20]
+    reti
+
+timer_interrupt:
+    movea   0x1D, r0, r25
+    st.b    r25, 0x20[r22]
+    ld.w    0x4[r20], r26
+    add     0x1, r26
+    st.w    r26, 0x4[r20]
+    reti
+";
+
+fn new_virtualfriend(disable_idle_skipping: bool) -> VirtualFriend {
+    new_virtualfriend_with_config(
+        PROGRAM,
+        EmulatorConfig {
+            disable_idle_skipping,
+            ..zeroed_config()
+        },
+    )
+}
+
+fn inputs(frame: usize) -> GamepadInputs {
+    GamepadInputs {
+        a_button: frame % 3 == 0,
+        left_dpad_up: frame % 5 == 0,
+        ..Default::default()
+    }
+}
+
+fn assert_frames_match(skipped: &Frame, stepped: &Frame) {
+    assert!(skipped.crash.is_none());
+    assert!(stepped.crash.is_none());
+
+    match (&skipped.video, &stepped.video) {
+        (Some(skipped), Some(stepped)) => {
+            assert_eq!(skipped.left, stepped.left);
+            assert_eq!(skipped.right, stepped.right);
+        }
+        (None, None) => {}
+        _ => panic!("Video frames differ"),
+    }
+
+    assert_eq!(skipped.audio_buffer, stepped.audio_buffer);
+}
+
+fn assert_state_matches(skipped: &VirtualFriend, stepped: &VirtualFriend) {
+    assert_eq!(skipped.registers(), stepped.registers());
+    assert_eq!(
+        skipped.read_memory(0x0500_0000, 0x10),
+        stepped.read_memory(0x0500_0000, 0x10)
+    );
+}
+
+#[test]
+fn video_frames_match_without_skipping() {
+    let mut skipped = new_virtualfriend(false);
+    let mut stepped = new_virtualfriend(true);
+
+    for frame in 0..12 {
+        let skipped_frame = skipped.run_video_frame(inputs(frame));
+        let stepped_frame = stepped.run_video_frame(inputs(frame));
+
+        assert_frames_match(&skipped_frame, &stepped_frame);
+        assert_state_matches(&skipped, &stepped);
+    }
+
+    let counts = read_u32s(&skipped, 0x0500_0000, 2);
+
+    // VIP and timer interrupts were serviced, and the gamepad was read
+    assert!(counts[0] > 0);
+    assert!(counts[1] > 0);
+    assert_ne!(read_u16s(&skipped, 0x0500_0008, 1)[0], 0);
+}
+
+#[test]
+fn audio_frames_match_without_skipping() {
+    let mut skipped = new_virtualfriend(false);
+    let mut stepped = new_virtualfriend(true);
+
+    for frame in 0..16 {
+        let skipped_frame = skipped.run_audio_frame(inputs(frame), 700);
+        let stepped_frame = stepped.run_audio_frame(inputs(frame), 700);
+
+        assert_eq!(skipped_frame.audio_buffer.len(), 700);
+        assert_frames_match(&skipped_frame, &stepped_frame);
+        assert_state_matches(&skipped, &stepped);
+    }
+
+    let skipped_frame = skipped.run_audio_frame(GamepadInputs::default(), 700);
+
+    // The tone is audible
+    assert!(skipped_frame
+        .audio_buffer
+        .iter()
+        .any(|(left, right)| *left != 0 && *right != 0));
+}
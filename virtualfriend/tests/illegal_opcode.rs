@@ -0,0 +1,69 @@
+use common::build_rom;
+use virtualfriend::{
+    config::{EmulatorConfig, IllegalOpcodeBehavior},
+    gamepad::GamepadInputs,
+    VirtualFriend,
+};
+
+mod common;
+
+const PROGRAM: &[u16] = &[
+    0x7005, // 0x07000000 ldsr r0, psw (clear NP)
+    0x4541, // 0x07000002 add 1, r10
+    0xD800, // 0x07000004 Reserved opcode
+    0x4541, // 0x07000006 add 1, r10
+    0x6800, // 0x07000008 halt
+];
+
+/// Illegal opcode exception handler at 0xFFFFFF90
+const HANDLER: &[u16] = &[
+    0x4562, // add 2, r11
+    0x6800, // halt
+];
+
+fn run(illegal_opcode: IllegalOpcodeBehavior) -> VirtualFriend {
+    let mut rom = build_rom(PROGRAM);
+
+    for (i, halfword) in HANDLER.iter().enumerate() {
+        rom[0x390 + i * 2..0x390 + i * 2 + 2].copy_from_slice(&halfword.to_le_bytes());
+    }
+
+    let mut virtualfriend = VirtualFriend::new_with_config(
+        rom,
+        EmulatorConfig {
+            illegal_opcode,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    // The first frame only executes the reset vector
+    virtualfriend.run_video_frame(GamepadInputs::default());
+    virtualfriend.run_video_frame(GamepadInputs::default());
+
+    virtualfriend
+}
+
+#[test]
+fn raises_illegal_opcode_exception() {
+    let virtualfriend = run(IllegalOpcodeBehavior::Exception);
+    let registers = virtualfriend.registers();
+
+    assert_eq!(registers.general_purpose[10], 1);
+    assert_eq!(registers.general_purpose[11], 2);
+    assert_eq!(registers.ecr & 0xFFFF, 0xFF90);
+    // Restore PC is the illegal instruction
+    assert_eq!(registers.eipc, 0x0700_0004);
+    assert_eq!(registers.pc, 0xFFFF_FF94);
+}
+
+#[test]
+fn continues_past_illegal_opcode() {
+    let virtualfriend = run(IllegalOpcodeBehavior::LogAndContinue);
+    let registers = virtualfriend.registers();
+
+    assert_eq!(registers.general_purpose[10], 2);
+    assert_eq!(registers.general_purpose[11], 0);
+    assert_eq!(registers.pc, 0x0700_000A);
+    assert!(virtualfriend.crash_report().is_none());
+}
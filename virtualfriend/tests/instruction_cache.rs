@@ -0,0 +1,188 @@
+use common::{new_virtualfriend, read_u32s};
+use virtualfriend::{
+    assembler::{assemble, ROM_BASE_ADDRESS},
+    gamepad::GamepadInputs,
+    VirtualFriend,
+};
+
+mod common;
+
+/// CHCW ICE
+const ENABLE: u32 = 0x2;
+/// CHCW ICC, clearing all 128 entries
+const CLEAR_ALL: u32 = 0x8001;
+const DUMP: u32 = 0x10;
+const RESTORE: u32 = 0x20;
+
+/// Where cache contents are dumped to and restored from
+const CACHE_IMAGE: u32 = 0x0500_1000;
+
+/// Writes `chcw`, then runs a loop of two 16 bit instructions, which share a cache subblock, 100 times. The cache is
+/// then dumped to `CACHE_IMAGE`, and optionally cleared and dumped again to `CACHE_IMAGE + 0x800`, with the cache
+/// disabled so the fetches between don't fill it again
+fn loop_program(chcw: u32, clear: bool) -> String {
+    let clear = if clear {
+        format!(
+            "
+    ori     {clear}, r0, r6
+    ldsr    r6, chcw
+    movhi   hi({dump}), r0, r6
+    movea   lo({dump}), r6, r6
+    ldsr    r6, chcw
+",
+            clear = CLEAR_ALL,
+            dump = CACHE_IMAGE + 0x800 + DUMP,
+        )
+    } else {
+        String::new()
+    };
+
+    format!(
+        "
+    ldsr    r0, psw
+    movea   {chcw}, r0, r6
+    ldsr    r6, chcw
+    movea   100, r0, r10
+    br      loop
+
+    .align  8
+loop:
+    add     -1, r10
+    bnz     loop
+
+    movhi   hi({dump}), r0, r6
+    movea   lo({dump}), r6, r6
+    ldsr    r6, chcw
+{clear}
+idle:
+    halt
+    br      idle
+",
+        dump = CACHE_IMAGE + (DUMP | ENABLE),
+    )
+}
+
+/// Calls `patched`, a 4 byte routine sharing a single cache subblock, storing its result at 0x05000000. The cache is
+/// first restored from `CACHE_IMAGE`, and after the call, cleared, and the routine called again, storing its result at
+/// 0x05000004
+fn restore_program() -> String {
+    format!(
+        "
+    ldsr    r0, psw
+    movhi   0x0500, r0, r20
+    movhi   hi({restore}), r0, r6
+    movea   lo({restore}), r6, r6
+    ldsr    r6, chcw
+
+    jal     patched
+    st.w    r10, 0x0[r20]
+
+    ori     {clear}, r0, r6
+    ldsr    r6, chcw
+
+    jal     patched
+    st.w    r10, 0x4[r20]
+
+idle:
+    halt
+    br      idle
+
+    .align  8
+patched:
+    mov     1, r10
+    jmp     [r31]
+",
+        restore = CACHE_IMAGE + (RESTORE | ENABLE),
+        clear = CLEAR_ALL | ENABLE,
+    )
+}
+
+fn run(virtualfriend: &mut VirtualFriend) {
+    for _ in 0..3 {
+        let frame = virtualfriend.run_video_frame(GamepadInputs::default());
+        assert!(frame.crash.is_none());
+    }
+}
+
+fn label(source: &str, name: &str) -> u32 {
+    assemble(ROM_BASE_ADDRESS, source)
+        .unwrap()
+        .label(name)
+        .unwrap()
+}
+
+/// The cycles spent executing each instruction of the loop
+fn loop_cycles(chcw: u32) -> (u64, u64) {
+    let source = loop_program(chcw, false);
+    let add = label(&source, "loop");
+
+    let mut virtualfriend = new_virtualfriend(&source);
+
+    virtualfriend.start_profiling();
+    run(&mut virtualfriend);
+    let profile = virtualfriend.stop_profiling().unwrap();
+
+    (profile.pc_cycles[&add], profile.pc_cycles[&(add + 2)])
+}
+
+/// The dumped data and tag words of the cache entry holding `address`
+fn dumped_entry(virtualfriend: &VirtualFriend, image: u32, address: u32) -> (Vec<u32>, u32) {
+    let index = (address >> 3) & 0x7F;
+
+    (
+        read_u32s(virtualfriend, image + index * 8, 2),
+        read_u32s(virtualfriend, image + 0x400 + index * 4, 1)[0],
+    )
+}
+
+#[test]
+fn misses_add_the_fill_penalty() {
+    let (uncached_add, uncached_bnz) = loop_cycles(0);
+    let (cached_add, cached_bnz) = loop_cycles(ENABLE);
+
+    // Instruction timings include fetching from ROM at power on, so uncached fetches and hits add no cycles. The ADD
+    // misses once, filling its subblock with the miss penalty of 3 cycles
+    assert_eq!(cached_add - uncached_add, 3);
+    assert_eq!(cached_bnz, uncached_bnz);
+}
+
+#[test]
+fn dumps_and_clears_entries() {
+    let source = loop_program(ENABLE, true);
+    let add = label(&source, "loop");
+
+    let mut virtualfriend = new_virtualfriend(&source);
+    run(&mut virtualfriend);
+
+    // The loop fills the first subblock of its entry, and the instruction following it the second. The entry is
+    // tagged with address bits 31-10
+    let (data, tag) = dumped_entry(&virtualfriend, CACHE_IMAGE, add);
+    assert_eq!(data, read_u32s(&virtualfriend, add, 2));
+    assert_eq!(tag, (add >> 10) | (3 << 22));
+
+    // Every entry is invalid after ICC
+    let tags = read_u32s(&virtualfriend, CACHE_IMAGE + 0x800 + 0x400, 128);
+    assert!(tags.iter().all(|tag| tag & (3 << 22) == 0));
+}
+
+#[test]
+fn executes_restored_entries_until_cleared() {
+    let source = restore_program();
+    let patched = label(&source, "patched");
+
+    // mov 2, r10 and jmp [r31] in place of the mov 1, r10 in ROM
+    let restored = assemble(patched, "mov 2, r10\njmp [r31]").unwrap().bytes;
+    let index = (patched >> 3) & 0x7F;
+
+    let mut virtualfriend = new_virtualfriend(&source);
+    virtualfriend.write_memory(CACHE_IMAGE + index * 8, &restored);
+    virtualfriend.write_memory(
+        CACHE_IMAGE + 0x400 + index * 4,
+        &((patched >> 10) | (1 << 22)).to_le_bytes(),
+    );
+
+    run(&mut virtualfriend);
+
+    // The restored instructions run instead of those in ROM, until ICC clears them
+    assert_eq!(read_u32s(&virtualfriend, 0x0500_0000, 2), [2, 1]);
+}
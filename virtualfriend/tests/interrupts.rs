@@ -0,0 +1,126 @@
+use common::{new_virtualfriend, read_u32s};
+use virtualfriend::gamepad::GamepadInputs;
+
+mod common;
+
+/// Waits with interrupts disabled until both FRAMESTART and the timer are pending, then enables them. Each handler
+/// acknowledges its source, and appends its ID to a log in WRAM, followed by the main program
+const SIMULTANEOUS_PROGRAM: &str = "
+    ldsr    r0, psw
+    movhi   0x0500, r0, r24
+    movhi   0x0200, r0, r22
+    movhi   hi(0x0005F800), r0, r21
+    movea   lo(0x0005F800), r21, r21
+
+    sei
+
+    // FRAMESTART interrupt
+    movea   0x10, r0, r6
+    st.h    r6, 0x2[r21]
+
+    // Timer zeroes after one 20us tick
+    mov     0x1, r6
+    st.b    r6, 0x18[r22]
+    st.b    r0, 0x1C[r22]
+    movea   0x19, r0, r6
+    st.b    r6, 0x20[r22]
+
+wait_timer:
+    ld.b    0x20[r22], r6
+    andi    0x2, r6, r6
+    bz      wait_timer
+
+wait_vip:
+    in.h    0x0[r21], r6
+    andi    0x10, r6, r6
+    bz      wait_vip
+
+    cli
+    nop
+    mov     0x3, r6
+    st.w    r6, 0x0[r24]
+    add     0x4, r24
+
+idle:
+    halt
+    br      idle
+
+vip_interrupt:
+    movea   0x10, r0, r25
+    st.h    r25, 0x4[r21]
+    st.h    r0, 0x2[r21]
+    mov     0x1, r25
+    st.w    r25, 0x0[r24]
+    add     0x4, r24
+    reti
+
+timer_interrupt:
+    // Clear zero status, and stop the timer
+    mov     0x4, r25
+    st.b    r25, 0x20[r22]
+    mov     0x2, r25
+    st.w    r25, 0x0[r24]
+    add     0x4, r24
+    reti
+";
+
+/// Starts a hardware read of the gamepad with the key input interrupt enabled, and halts. The handler counts into WRAM
+const GAMEPAD_PROGRAM: &str = "
+    ldsr    r0, psw
+    movhi   0x0500, r0, r20
+    movhi   0x0200, r0, r22
+    st.w    r0, 0x0[r20]
+
+    mov     0x4, r6
+    st.b    r6, 0x28[r22]
+
+idle:
+    halt
+    br      idle
+
+gamepad_interrupt:
+    // Setting K-Int-Inh acknowledges the interrupt
+    movea   0x80, r0, r25
+    st.b    r25, 0x28[r22]
+    ld.w    0x0[r20], r25
+    add     0x1, r25
+    st.w    r25, 0x0[r20]
+    reti
+";
+
+#[test]
+fn simultaneous_interrupts_are_both_serviced() {
+    let mut virtualfriend = new_virtualfriend(SIMULTANEOUS_PROGRAM);
+
+    for _ in 0..3 {
+        let frame = virtualfriend.run_video_frame(GamepadInputs::default());
+        assert!(frame.crash.is_none());
+    }
+
+    // VIP is serviced first, and the timer remains pending until VIP returns, before the main program continues
+    assert_eq!(read_u32s(&virtualfriend, 0x0500_0000, 4), [1, 2, 3, 0]);
+}
+
+#[test]
+fn gamepad_interrupt_is_raised_by_held_buttons() {
+    let mut virtualfriend = new_virtualfriend(GAMEPAD_PROGRAM);
+
+    for _ in 0..3 {
+        virtualfriend.run_video_frame(GamepadInputs::default());
+    }
+
+    // No buttons were held during the read
+    assert_eq!(read_u32s(&virtualfriend, 0x0500_0000, 1), [0]);
+
+    let mut virtualfriend = new_virtualfriend(GAMEPAD_PROGRAM);
+
+    for _ in 0..3 {
+        virtualfriend.run_video_frame(GamepadInputs {
+            a_button: true,
+            ..Default::default()
+        });
+    }
+
+    // Serviced once, and not again after being acknowledged
+    assert_eq!(read_u32s(&virtualfriend, 0x0500_0000, 1), [1]);
+}
@@ -0,0 +1,151 @@
+use std::{cell::RefCell, io::Cursor, rc::Rc};
+
+use common::{new_virtualfriend_with_config, read_u16s};
+use virtualfriend::{
+    config::{EmulatorConfig, PowerOnState},
+    gamepad::GamepadInputs,
+    lockstep::{run_lockstep, LockstepResult, RegisterDifference},
+    trace::{MednafenTraceReader, MednafenTraceWriter, TraceFilter, TraceRecord, TraceSink},
+    VirtualFriend,
+};
+
+mod common;
+
+const PROGRAM: &str = "
+    ldsr    r0, psw
+    movhi   0x0500, r0, r2
+    mov     10, r11
+loop:
+    add     1, r10
+    st.w    r10, 0[r2]
+    add     -1, r11
+    bnz     loop
+    halt
+";
+
+struct RecordSink {
+    records: Rc<RefCell<Vec<TraceRecord>>>,
+}
+
+impl TraceSink for RecordSink {
+    fn record(&mut self, record: &TraceRecord) {
+        self.records.borrow_mut().push(*record);
+    }
+}
+
+fn new_virtualfriend() -> VirtualFriend {
+    new_virtualfriend_with_config(
+        PROGRAM,
+        EmulatorConfig {
+            power_on: PowerOnState::Mednafen,
+            ..Default::default()
+        },
+    )
+}
+
+/// Runs the program and round trips its trace through the Mednafen text format
+fn reference_trace() -> Vec<TraceRecord> {
+    let records = Rc::new(RefCell::new(Vec::new()));
+
+    let mut virtualfriend = new_virtualfriend();
+    virtualfriend.attach_trace_sink(
+        Box::new(RecordSink {
+            records: records.clone(),
+        }),
+        TraceFilter::default(),
+    );
+
+    // The first frame only executes the reset vector
+    virtualfriend.run_video_frame(GamepadInputs::default());
+    virtualfriend.run_video_frame(GamepadInputs::default());
+
+    let mut writer = MednafenTraceWriter::new(Vec::new(), true);
+
+    for record in records.borrow().iter() {
+        writer.record(record);
+    }
+
+    let text = writer.into_inner().unwrap();
+
+    MednafenTraceReader::new(Cursor::new(text))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+}
+
+#[test]
+fn matches_identical_trace() {
+    let trace = reference_trace();
+
+    // Reset vector, setup, 10 loop iterations, and HALT
+    assert_eq!(trace.len(), 3 + 3 + 40 + 1);
+    assert_eq!(trace[0].registers.pc, 0xFFFF_FFF0);
+    // Mednafen's power on state
+    assert_eq!(trace[0].registers.general_purpose[10], 0xDEAD_BEEF);
+
+    let result = run_lockstep(
+        &mut new_virtualfriend(),
+        trace.iter().copied().map(Ok),
+        GamepadInputs::default(),
+        8,
+    )
+    .unwrap();
+
+    assert_eq!(result, LockstepResult::Matched { count: trace.len() });
+}
+
+#[test]
+fn reports_first_divergence() {
+    let mut trace = reference_trace();
+
+    let index = 20;
+    trace[index].registers.general_purpose[10] ^= 0x100;
+    // Later divergences are not reported
+    trace[index + 1].registers.general_purpose[11] ^= 0x100;
+
+    let result = run_lockstep(
+        &mut new_virtualfriend(),
+        trace.iter().copied().map(Ok),
+        GamepadInputs::default(),
+        8,
+    )
+    .unwrap();
+
+    let LockstepResult::Diverged(divergence) = result else {
+        panic!("Expected divergence, found {result:?}");
+    };
+
+    assert_eq!(divergence.index, index);
+    assert_eq!(
+        divergence.differences,
+        vec![RegisterDifference {
+            name: "R10",
+            expected: trace[index].registers.general_purpose[10],
+            actual: trace[index].registers.general_purpose[10] ^ 0x100,
+        }]
+    );
+
+    assert_eq!(divergence.history.len(), 8);
+    assert_eq!(divergence.history.last().unwrap().record, trace[index - 1]);
+    assert_eq!(
+        divergence.history.last().unwrap().instruction.address,
+        trace[index - 1].registers.pc
+    );
+}
+
+#[test]
+fn fills_game_pak_ram_as_mednafen_does() {
+    let virtualfriend = new_virtualfriend();
+
+    // Three blocks of 0x24 halfwords, each 0x28 halfwords apart
+    let ram = read_u16s(&virtualfriend, 0x0600_0000, 0x80);
+
+    for (address, &value) in ram.iter().enumerate() {
+        let expected = if address < 0x78 && address % 0x28 < 0x24 {
+            0xFFFF
+        } else {
+            0
+        };
+
+        assert_eq!(value, expected, "{address:#X}");
+    }
+}
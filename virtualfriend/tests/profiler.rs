@@ -0,0 +1,90 @@
+use common::new_virtualfriend;
+use virtualfriend::{
+    assembler::{assemble, ROM_BASE_ADDRESS},
+    gamepad::GamepadInputs,
+};
+
+mod common;
+
+const PROGRAM: &str = "
+    ldsr    r0, psw
+    jal     outer
+    halt
+
+outer:
+    mov     r31, r20
+    movea   100, r0, r10
+outer_loop:
+    add     -1, r10
+    bnz     outer_loop
+    jal     inner
+    mov     r20, r31
+    jmp     [r31]
+
+inner:
+    movea   50, r0, r11
+inner_loop:
+    add     -1, r11
+    bnz     inner_loop
+    jmp     [r31]
+";
+
+#[test]
+fn attributes_cycles_to_call_stacks() {
+    let program = assemble(ROM_BASE_ADDRESS, PROGRAM).unwrap();
+    let outer = program.label("outer").unwrap();
+    let inner = program.label("inner").unwrap();
+    let outer_loop = program.label("outer_loop").unwrap();
+
+    let mut virtualfriend = new_virtualfriend(PROGRAM);
+
+    virtualfriend.start_profiling();
+
+    for _ in 0..3 {
+        virtualfriend.run_video_frame(GamepadInputs::default());
+    }
+
+    let profile = virtualfriend.stop_profiling().unwrap();
+
+    let outer_cycles = profile.stack_cycles[&vec![outer]];
+    let inner_cycles = profile.stack_cycles[&vec![outer, inner]];
+
+    // The outer loop runs twice as many iterations
+    assert!(outer_cycles > inner_cycles);
+    assert!(inner_cycles > 0);
+    assert!(profile.pc_cycles[&outer_loop] > 0);
+
+    // The program halts at the top level once the calls return
+    assert_eq!(profile.halted_stack_cycles.len(), 1);
+    assert!(profile.halted_stack_cycles[&vec![]] > 0);
+
+    let stack_total: u64 = profile.stack_cycles.values().sum();
+    assert_eq!(stack_total + profile.halted_cycles, profile.total_cycles);
+
+    let mut folded = Vec::new();
+    profile.write_folded(&mut folded).unwrap();
+    let folded = String::from_utf8(folded).unwrap();
+
+    assert!(folded
+        .lines()
+        .any(|line| line == format!("[root];{outer:08X} {outer_cycles}")));
+    assert!(folded
+        .lines()
+        .any(|line| line == format!("[root];{outer:08X};{inner:08X} {inner_cycles}")));
+    assert!(folded
+        .lines()
+        .any(|line| line.starts_with("[root];[halted] ")));
+
+    assert_eq!(profile.frames.len(), 3);
+
+    let last_frame = &profile.frames[2];
+    assert!(last_frame.cycles > 0);
+    assert_eq!(last_frame.halted_cycles, last_frame.cycles);
+
+    let mut summary = Vec::new();
+    profile.write_frame_summary(&mut summary).unwrap();
+    let summary = String::from_utf8(summary).unwrap();
+
+    assert_eq!(summary.lines().count(), 3);
+    assert!(summary.lines().nth(2).unwrap().contains("100.0% halted"));
+}
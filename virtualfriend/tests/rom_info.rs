@@ -0,0 +1,67 @@
+use virtualfriend::{assembler::assemble_rom, cartridge::RomInfo, VirtualFriend};
+
+const PROGRAM: &str = "
+    ldsr    r0, psw
+
+idle:
+    halt
+    br      idle
+";
+
+/// Writes a header for "テスト GAME", maker "01", game "VTSE", revision 1, just below the vectors
+fn with_header(mut rom: Vec<u8>) -> Vec<u8> {
+    let start = rom.len() - 0x220;
+    let header = &mut rom[start..start + 0x20];
+
+    header.fill(0);
+    header[..20].fill(b' ');
+    header[..11].copy_from_slice(&[
+        0x83, 0x65, 0x83, 0x58, 0x83, 0x67, b' ', b'G', b'A', b'M', b'E',
+    ]);
+    header[0x19..0x1B].copy_from_slice(b"01");
+    header[0x1B..0x1F].copy_from_slice(b"VTSE");
+    header[0x1F] = 1;
+
+    rom
+}
+
+#[test]
+fn parses_header() {
+    let info = RomInfo::new(&with_header(vec![0; 0x400]));
+
+    assert_eq!(info.title, "テスト GAME");
+    assert_eq!(info.maker, "01");
+    assert_eq!(info.game_id, "VTSE");
+    assert_eq!(info.revision, 1);
+    assert_eq!(info.size, 0x400);
+}
+
+#[test]
+fn hashes_image() {
+    let info = RomInfo::new(b"abc");
+
+    assert_eq!(info.crc32, 0x3524_41C2);
+    assert_eq!(info.md5_hex(), "900150983cd24fb0d6963f7d28e17f72");
+    assert_eq!(info.sha1_hex(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+
+    // Too small to hold a header
+    assert_eq!(info.title, "");
+    assert_eq!(info.game_id, "");
+    assert_eq!(info.revision, 0);
+}
+
+#[test]
+fn describes_loaded_rom_before_mirroring() {
+    let rom = assemble_rom(PROGRAM).unwrap();
+
+    // A 1.5KB image, which is mirrored to 2KB when loaded
+    let mut trimmed = rom.clone();
+    trimmed.extend_from_slice(&rom[0x200..]);
+    let trimmed = with_header(trimmed);
+
+    let virtualfriend = VirtualFriend::new(trimmed.clone()).unwrap();
+
+    assert_eq!(virtualfriend.rom_info(), &RomInfo::new(&trimmed));
+    assert_eq!(virtualfriend.rom_info().size, 0x600);
+    assert_eq!(virtualfriend.rom_info().game_id, "VTSE");
+}
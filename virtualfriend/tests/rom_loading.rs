@@ -0,0 +1,77 @@
+use virtualfriend::{
+    assembler::assemble_rom,
+    cartridge::{FlashMapper, RomError},
+    gamepad::GamepadInputs,
+    VirtualFriend,
+};
+
+const PROGRAM: &str = "
+    ldsr    r0, psw
+    movhi   0x0500, r0, r20
+    movea   0x1234, r0, r6
+    st.h    r6, 0x0[r20]
+
+idle:
+    halt
+    br      idle
+";
+
+/// Trims the assembled 1KB ROM to 1.5KB, as a 1KB chip followed by a 512B chip holding the vectors
+fn trimmed_rom() -> Vec<u8> {
+    let rom = assemble_rom(PROGRAM).unwrap();
+
+    let mut trimmed = rom.clone();
+    trimmed.extend_from_slice(&rom[0x200..]);
+
+    trimmed
+}
+
+#[test]
+fn mirrors_non_power_of_two_rom() {
+    let rom = trimmed_rom();
+    assert_eq!(rom.len(), 0x600);
+
+    let mut virtualfriend = VirtualFriend::new(rom.clone()).unwrap();
+
+    // The 512B chip repeats to fill the upper half of the 2KB ROM
+    assert_eq!(virtualfriend.read_memory(0x0700_0000, 0x400), rom[..0x400]);
+    assert_eq!(virtualfriend.read_memory(0x0700_0400, 0x200), rom[0x400..]);
+    assert_eq!(virtualfriend.read_memory(0x0700_0600, 0x200), rom[0x400..]);
+    // And the 2KB ROM repeats across the ROM region
+    assert_eq!(virtualfriend.read_memory(0x07FF_FE00, 0x200), rom[0x400..]);
+
+    // The reset vector is reached at the top of the ROM region
+    for _ in 0..2 {
+        let frame = virtualfriend.run_video_frame(GamepadInputs::default());
+        assert!(frame.crash.is_none());
+    }
+
+    assert_eq!(virtualfriend.read_memory(0x0500_0000, 2), vec![0x34, 0x12]);
+}
+
+#[test]
+fn mirrors_odd_sized_rom() {
+    let mut rom = assemble_rom(PROGRAM).unwrap();
+    // A trailing byte, as left by some dumps
+    rom.push(0xAB);
+
+    let virtualfriend = VirtualFriend::new(rom.clone()).unwrap();
+
+    assert_eq!(virtualfriend.read_memory(0x0700_0000, 0x400), rom[..0x400]);
+    assert_eq!(virtualfriend.read_memory(0x0700_0400, 4), vec![0xAB; 4]);
+}
+
+#[test]
+fn rejects_invalid_roms() {
+    assert_eq!(VirtualFriend::new(vec![]).err(), Some(RomError::Empty));
+    assert_eq!(
+        VirtualFriend::new(vec![0; 0x100_0002]).err(),
+        Some(RomError::TooLarge { size: 0x100_0002 })
+    );
+
+    assert_eq!(FlashMapper::new(vec![]).err(), Some(RomError::Empty));
+    assert_eq!(
+        FlashMapper::new(trimmed_rom()).err(),
+        Some(RomError::FlashSizeNotPowerOfTwo { size: 0x600 })
+    );
+}
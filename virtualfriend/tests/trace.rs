@@ -0,0 +1,166 @@
+use std::{
+    cell::RefCell,
+    io::{self, BufWriter, Cursor, Write},
+    rc::Rc,
+};
+
+use common::new_virtualfriend;
+use virtualfriend::{
+    assembler::{assemble, ROM_BASE_ADDRESS},
+    gamepad::GamepadInputs,
+    trace::{
+        BinaryTraceReader, BinaryTraceWriter, MednafenTraceReader, MednafenTraceWriter,
+        TraceFilter, TraceRecord, TraceSink,
+    },
+};
+
+mod common;
+
+const PROGRAM: &str = "
+    ldsr    r0, psw
+    movhi   0x0500, r0, r2
+    mov     10, r11
+loop:
+    add     1, r10
+    st.w    r10, 0[r2]
+    add     -1, r11
+    bnz     loop
+    halt
+";
+
+struct RecordSink {
+    records: Rc<RefCell<Vec<TraceRecord>>>,
+}
+
+impl TraceSink for RecordSink {
+    fn record(&mut self, record: &TraceRecord) {
+        self.records.borrow_mut().push(*record);
+    }
+}
+
+/// A writer whose contents can be read after it is handed to a trace writer
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs the program with `sink` attached, then detaches it
+fn trace(sink: Box<dyn TraceSink>, filter: TraceFilter) {
+    let mut virtualfriend = new_virtualfriend(PROGRAM);
+    virtualfriend.attach_trace_sink(sink, filter);
+
+    // The first frame only executes the reset vector
+    virtualfriend.run_video_frame(GamepadInputs::default());
+    virtualfriend.run_video_frame(GamepadInputs::default());
+
+    assert!(virtualfriend.detach_trace_sink().unwrap().is_some());
+}
+
+fn records(filter: TraceFilter) -> Vec<TraceRecord> {
+    let records = Rc::new(RefCell::new(Vec::new()));
+
+    trace(
+        Box::new(RecordSink {
+            records: records.clone(),
+        }),
+        filter,
+    );
+
+    records.take()
+}
+
+#[test]
+fn round_trips_through_both_formats() {
+    let expected = records(TraceFilter::default());
+
+    // Reset vector, setup, 10 loop iterations, and HALT
+    assert_eq!(expected.len(), 3 + 3 + 40 + 1);
+
+    // Buffered, so nothing reaches the buffer unless detaching flushes the writer
+    let mednafen = SharedBuffer::default();
+    trace(
+        Box::new(MednafenTraceWriter::new(
+            BufWriter::new(mednafen.clone()),
+            true,
+        )),
+        TraceFilter::default(),
+    );
+
+    let mednafen_records = MednafenTraceReader::new(Cursor::new(mednafen.0.take()))
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap();
+
+    assert_eq!(mednafen_records, expected);
+
+    let binary = SharedBuffer::default();
+    trace(
+        Box::new(BinaryTraceWriter::new(BufWriter::new(binary.clone()))),
+        TraceFilter::default(),
+    );
+
+    let binary_records = BinaryTraceReader::new(Cursor::new(binary.0.take()))
+        .unwrap()
+        .collect::<io::Result<Vec<_>>>()
+        .unwrap();
+
+    assert_eq!(binary_records, expected);
+}
+
+#[test]
+fn filters_by_pc_and_cycles() {
+    let all = records(TraceFilter::default());
+
+    let program = assemble(ROM_BASE_ADDRESS, PROGRAM).unwrap();
+    let loop_start = program.label("loop").unwrap();
+
+    // Only the ADD and ST.W of each iteration. The range is inclusive
+    let in_loop = records(TraceFilter {
+        pc_range: Some(loop_start..=loop_start + 2),
+        ..Default::default()
+    });
+
+    assert_eq!(in_loop.len(), 2 * 10);
+    assert_eq!(
+        in_loop,
+        all.iter()
+            .filter(|record| (loop_start..=loop_start + 2).contains(&record.registers.pc))
+            .copied()
+            .collect::<Vec<_>>()
+    );
+
+    // The end of the range is exclusive
+    let cycle_range = all[10].cycle_count..all[20].cycle_count;
+
+    let in_cycles = records(TraceFilter {
+        cycle_range: Some(cycle_range.clone()),
+        ..Default::default()
+    });
+
+    assert_eq!(in_cycles, all[10..20]);
+
+    // Both must match
+    let both = records(TraceFilter {
+        pc_range: Some(loop_start..=loop_start + 2),
+        cycle_range: Some(cycle_range),
+    });
+
+    assert_eq!(
+        both,
+        all[10..20]
+            .iter()
+            .filter(|record| (loop_start..=loop_start + 2).contains(&record.registers.pc))
+            .copied()
+            .collect::<Vec<_>>()
+    );
+    assert!(!both.is_empty());
+}
@@ -0,0 +1,203 @@
+use common::new_virtualfriend;
+use virtualfriend::{
+    assembler::{assemble, ROM_BASE_ADDRESS},
+    gamepad::GamepadInputs,
+    profiler::Profile,
+    VirtualFriend,
+};
+
+mod common;
+
+/// Sets WCR, reads it back into WRAM, then performs 100 word loads from `base` and halts. If `drawing`, the loads
+/// wait until the VIP is drawing
+fn load_program(wcr: u8, base: u32, drawing: bool) -> String {
+    let wait_drawing = if drawing {
+        "
+    movhi   hi(0x0005F800), r0, r21
+    movea   lo(0x0005F800), r21, r21
+    mov     0x2, r6
+    st.h    r6, 0x42[r21]
+wait_drawing:
+    in.h    0x40[r21], r6
+    andi    0xC, r6, r6
+    bz      wait_drawing
+"
+    } else {
+        ""
+    };
+
+    format!(
+        "
+    ldsr    r0, psw
+    movhi   0x0200, r0, r22
+    movhi   0x0500, r0, r20
+    mov     {wcr}, r6
+    st.b    r6, 0x24[r22]
+    in.b    0x24[r22], r6
+    st.b    r6, 0x0[r20]
+{wait_drawing}
+    movhi   hi({base}), r0, r7
+    movea   lo({base}), r7, r7
+    movea   100, r0, r10
+load:
+    ld.w    0x0[r7], r8
+    add     -1, r10
+    bnz     load
+
+idle:
+    halt
+    br      idle
+"
+    )
+}
+
+/// A loop without memory accesses, which returns to r31
+const ROUTINE: &str = "
+    movea   100, r0, r10
+loop:
+    add     -1, r10
+    bnz     loop
+    jmp     [r31]
+";
+
+/// Address of the copy of `ROUTINE` in WRAM
+const WRAM_ROUTINE_ADDRESS: u32 = 0x0500_0100;
+
+/// Sets WCR, then calls `target`, which is either the copy of `ROUTINE` in ROM, or the one in WRAM
+fn call_program(wcr: u8, target: &str) -> String {
+    format!(
+        "
+    ldsr    r0, psw
+    movhi   0x0200, r0, r22
+    mov     {wcr}, r6
+    st.b    r6, 0x24[r22]
+    movhi   hi({target}), r0, r6
+    movea   lo({target}), r6, r6
+    jal     trampoline
+
+idle:
+    halt
+    br      idle
+
+trampoline:
+    jmp     [r6]
+
+routine:
+{ROUTINE}
+"
+    )
+}
+
+/// Runs until long after the program halts
+fn profile(virtualfriend: &mut VirtualFriend) -> Profile {
+    virtualfriend.start_profiling();
+
+    for _ in 0..4 {
+        virtualfriend.run_video_frame(GamepadInputs::default());
+    }
+
+    virtualfriend.stop_profiling().unwrap()
+}
+
+/// Runs the load program, returning the cycles spent executing the loads, and the WCR value that was read back
+fn load_cycles(wcr: u8, base: u32, drawing: bool) -> (u64, u8) {
+    let source = load_program(wcr, base, drawing);
+    let load = assemble(ROM_BASE_ADDRESS, &source)
+        .unwrap()
+        .label("load")
+        .unwrap();
+
+    let mut virtualfriend = new_virtualfriend(&source);
+    let profile = profile(&mut virtualfriend);
+
+    (
+        profile.pc_cycles[&load],
+        virtualfriend.read_memory(0x0500_0000, 1)[0],
+    )
+}
+
+/// Runs the routine from ROM, or from WRAM, returning the cycles spent executing it
+fn routine_cycles(wcr: u8, from_wram: bool) -> u64 {
+    let mut virtualfriend;
+
+    let routine_address = if from_wram {
+        let routine = assemble(WRAM_ROUTINE_ADDRESS, ROUTINE).unwrap();
+
+        virtualfriend = new_virtualfriend(&call_program(wcr, &WRAM_ROUTINE_ADDRESS.to_string()));
+        virtualfriend.write_memory(WRAM_ROUTINE_ADDRESS, &routine.bytes);
+
+        WRAM_ROUTINE_ADDRESS
+    } else {
+        let source = call_program(wcr, "routine");
+
+        virtualfriend = new_virtualfriend(&source);
+
+        assemble(ROM_BASE_ADDRESS, &source)
+            .unwrap()
+            .label("routine")
+            .unwrap()
+    };
+
+    let routine_length = assemble(routine_address, ROUTINE).unwrap().bytes.len() as u32;
+
+    profile(&mut virtualfriend)
+        .pc_cycles
+        .iter()
+        .filter(|(pc, _)| (routine_address..routine_address + routine_length).contains(pc))
+        .map(|(_, cycles)| cycles)
+        .sum()
+}
+
+#[test]
+fn wcr_selects_rom_wait_states() {
+    let (two_wait_cycles, two_wait_wcr) = load_cycles(0, 0x0700_0000, false);
+    let (one_wait_cycles, one_wait_wcr) = load_cycles(1, 0x0700_0000, false);
+
+    // Unused bits read as set
+    assert_eq!(two_wait_wcr, 0xFC);
+    assert_eq!(one_wait_wcr, 0xFD);
+
+    // Each load is fetched from ROM in two 16 bit transfers, and reads ROM in two more, each one wait shorter
+    assert_eq!(two_wait_cycles - one_wait_cycles, 100 * 4);
+}
+
+#[test]
+fn wram_has_no_wait_states() {
+    let (two_wait_cycles, _) = load_cycles(0, 0x0500_0100, false);
+    let (one_wait_cycles, _) = load_cycles(1, 0x0500_0100, false);
+
+    // Only the fetches from ROM are shortened
+    assert_eq!(two_wait_cycles - one_wait_cycles, 100 * 2);
+
+    let (rom_cycles, _) = load_cycles(1, 0x0700_0000, false);
+
+    assert_eq!(rom_cycles - one_wait_cycles, 100 * 2);
+}
+
+#[test]
+fn instruction_fetches_follow_rom_wait_states() {
+    let wram_cycles = routine_cycles(0, true);
+
+    assert_eq!(wram_cycles, routine_cycles(1, true));
+
+    // Instruction timings include fetching from ROM with the power on wait states
+    assert_eq!(routine_cycles(0, false), wram_cycles);
+
+    // With 1 wait, each 16 bit transfer is a cycle shorter, but no instruction takes less than 1 cycle. Only the 99
+    // taken branches and the return take longer than that
+    assert_eq!(wram_cycles - routine_cycles(1, false), 99 + 1);
+}
+
+#[test]
+fn vram_waits_while_drawing() {
+    let (wram_cycles, _) = load_cycles(0, 0x0500_0100, true);
+    let (vram_cycles, _) = load_cycles(0, 0x0000_0100, true);
+
+    // Each word load is two transfers, each waiting 2 cycles for the VIP
+    assert_eq!(vram_cycles - wram_cycles, 100 * 2 * 2);
+
+    let (idle_wram_cycles, _) = load_cycles(0, 0x0500_0100, false);
+    let (idle_vram_cycles, _) = load_cycles(0, 0x0000_0100, false);
+
+    assert_eq!(idle_vram_cycles, idle_wram_cycles);
+}
@@ -0,0 +1,94 @@
+use common::{build_rom, new_virtualfriend, read_u32s};
+use virtualfriend::{gamepad::GamepadInputs, VirtualFriend};
+
+mod common;
+
+/// Copies a routine into WRAM and calls it, then patches the routine and calls it again
+const PROGRAM: &[u16] = &[
+    0xBC40, 0x0500, // 0x07000000 movhi 0x0500, r0, r2
+    0xA060, 0x4541, // 0x07000004 movea 0x4541, r0, r3 (add 1, r10)
+    0xD462, 0x0000, // 0x07000008 st.h r3, 0x0[r2]
+    0xA060, 0x4562, // 0x0700000C movea 0x4562, r0, r3 (add 2, r11)
+    0xD462, 0x0002, // 0x07000010 st.h r3, 0x2[r2]
+    0xA060, 0x181F, // 0x07000014 movea 0x181F, r0, r3 (jmp [r31])
+    0xD462, 0x0004, // 0x07000018 st.h r3, 0x4[r2]
+    0xBFE0, 0x0700, // 0x0700001C movhi 0x0700, r0, r31
+    0xA3FF, 0x0026, // 0x07000020 movea 0x0026, r31, r31
+    0x1802, // 0x07000024 jmp [r2]
+    0xA060, 0x4543, // 0x07000026 movea 0x4543, r0, r3 (add 3, r10)
+    0xD462, 0x0000, // 0x0700002A st.h r3, 0x0[r2]
+    0xBFE0, 0x0700, // 0x0700002E movhi 0x0700, r0, r31
+    0xA3FF, 0x0038, // 0x07000032 movea 0x0038, r31, r31
+    0x1802, // 0x07000036 jmp [r2]
+    0x6800, // 0x07000038 halt
+];
+
+#[test]
+fn executes_routine_copied_to_wram() {
+    let mut virtualfriend = VirtualFriend::new(build_rom(PROGRAM)).unwrap();
+
+    // The first frame only executes the reset vector
+    virtualfriend.run_video_frame(GamepadInputs::default());
+    virtualfriend.run_video_frame(GamepadInputs::default());
+
+    let registers = virtualfriend.registers();
+
+    // add 1, r10 followed by the patched add 3, r10
+    assert_eq!(registers.general_purpose[10], 4);
+    // add 2, r11 twice
+    assert_eq!(registers.general_purpose[11], 4);
+    // Halted after returning from the second call
+    assert_eq!(registers.pc, 0x0700_003A);
+
+    assert_eq!(
+        virtualfriend.read_memory(0x0500_0000, 6),
+        vec![0x43, 0x45, 0x62, 0x45, 0x1F, 0x18]
+    );
+}
+
+#[test]
+fn reruns_routine_after_patching_it_in_wram() {
+    // Copies `routine` to WRAM, then calls it four times, patching its immediate before each call to the loop count
+    let source = "
+    ldsr    r0, psw
+    movhi   hi(routine), r0, r6
+    movea   lo(routine), r6, r6
+    movhi   0x0500, r0, r7
+    ld.w    0[r6], r8
+    st.w    r8, 0[r7]
+    movea   0x100, r7, r20
+
+    mov     0x0, r9
+    movea   0x4140, r0, r8
+repeat:
+    mov     r8, r11
+    or      r9, r11
+    st.h    r11, 0[r7]
+    jal     call_wram
+    st.w    r10, 0[r20]
+    add     0x4, r20
+    add     0x1, r9
+    cmp     0x4, r9
+    bnz     repeat
+
+idle:
+    halt
+    br      idle
+
+call_wram:
+    jmp     [r7]
+
+    .align  4
+routine:
+    mov     0x0, r10
+    jmp     [r31]
+";
+
+    let mut virtualfriend = new_virtualfriend(source);
+
+    for _ in 0..2 {
+        virtualfriend.run_video_frame(GamepadInputs::default());
+    }
+
+    assert_eq!(read_u32s(&virtualfriend, 0x0500_0100, 4), [0, 1, 2, 3]);
+}
@@ -23,6 +23,7 @@ class Emulator {
     private var prevFrameTime: TimeInterval?
 
     private var halt = true
+    private var reportedCrash = false
 
     var stereoImageChannel = AsyncImageChannel()
 
@@ -65,6 +66,11 @@ class Emulator {
             return VirtualFriend(pointer)
         }
 
+        guard let virtualFriend else {
+            fileUrl.stopAccessingSecurityScopedResource()
+            throw EmulatorError.invalidRom
+        }
+
         do {
             let saveData = try Data(contentsOf: saveUrl(for: self.fileName))
 
@@ -199,6 +205,12 @@ class Emulator {
 
         let frame = self.virtualFriend.run_audio_frame(inputs, AUDIO_FRAMES_PER_LOOP)
 
+        if let crash = frame.crash, !self.reportedCrash {
+            // The core has halted. Every subsequent frame will contain the same report
+            self.reportedCrash = true
+            print("Emulator crashed\n\(crash.description.toString())")
+        }
+
         if let frame = frame.video {
             Task {
                 let leftImage = frame.left.ciImage(color: self.color)
@@ -280,4 +292,5 @@ class Emulator {
 enum EmulatorError: Error {
     case audioFormatInit
     case audioBufferInit
+    case invalidRom
 }
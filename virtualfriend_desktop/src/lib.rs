@@ -79,6 +79,17 @@ pub fn build_client<F: Fn(&ThreadFrame) -> bool>(
     // Window
     let mut event_loop =
         event_loop.map_or_else(|| EventLoop::new().unwrap(), |event_loop| event_loop);
+
+    let rom = fs::read(&rom_path).expect("Could not load ROM");
+    let mut virtualfriend = match VirtualFriend::new(rom) {
+        Ok(virtualfriend) => virtualfriend,
+        Err(error) => {
+            // Skip the ROM without opening a window
+            println!("Could not load {}: {error}", rom_path.display());
+            return event_loop;
+        }
+    };
+
     let window = WindowBuilder::new()
         .with_inner_size(PhysicalSize::new(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32))
         .with_title("Virtualfriend")
@@ -150,9 +161,6 @@ pub fn build_client<F: Fn(&ThreadFrame) -> bool>(
 
     let (mut rewind_receiver, rewind_transmitter) = channel_starting_with::<bool>(false);
 
-    let rom = fs::read(&rom_path).expect("Could not load ROM");
-    let mut virtualfriend = VirtualFriend::new(rom);
-
     if let Some(save_path) = save_path {
         if let Ok(ram) = fs::read(save_path) {
             // We have save RAM. Upload it
@@ -162,6 +170,7 @@ pub fn build_client<F: Fn(&ThreadFrame) -> bool>(
     }
 
     let mut frame_id = 0;
+    let mut crashed = false;
     let virtualfriend = Arc::new(Mutex::new(virtualfriend));
 
     let virtualfriend_audio = virtualfriend.clone();
@@ -175,6 +184,8 @@ pub fn build_client<F: Fn(&ThreadFrame) -> bool>(
             Frame {
                 video,
                 audio_buffer: vec![],
+                stop_reason: None,
+                crash: None,
             }
         } else {
             // Normal frame
@@ -184,6 +195,16 @@ pub fn build_client<F: Fn(&ThreadFrame) -> bool>(
                 .run_audio_frame(inputs_receiver.latest().clone(), sample_count)
         };
 
+        if let Some(crash) = frame.crash {
+            if !crashed {
+                // The core is halted. Output silence and leave the last frame on screen
+                println!("{crash}");
+                crashed = true;
+            }
+
+            return VecDeque::from(vec![(0, 0); sample_count]);
+        }
+
         if let Some(video) = frame.video {
             // Send updated video frame
             frame_id += 1;
@@ -1,9 +1,11 @@
 use std::sync::Mutex;
 
 use ffi::{
-    FFIFrame, FFIGamepadInputs, FFIManifest, FFIMetadata, FFIUnparsedSavestate, FFIVideoFrame,
+    FFICrashReport, FFIFrame, FFIGamepadInputs, FFIManifest, FFIMetadata, FFIRomInfo,
+    FFIUnparsedSavestate, FFIVideoFrame,
 };
 use virtualfriend::{
+    cartridge::RomInfo,
     gamepad::GamepadInputs,
     manifest::{Manifest, Metadata},
     savestates::savestate::UnparsedSavestate,
@@ -18,11 +20,18 @@ mod ffi {
         right: Vec<u8>,
     }
 
+    #[swift_bridge(swift_repr = "struct")]
+    struct FFICrashReport {
+        pc: u32,
+        description: String,
+    }
+
     #[swift_bridge(swift_repr = "struct")]
     struct FFIFrame {
         video: Option<FFIVideoFrame>,
         audio_left: Vec<i16>,
         audio_right: Vec<i16>,
+        crash: Option<FFICrashReport>,
     }
 
     #[swift_bridge(swift_repr = "struct")]
@@ -76,11 +85,24 @@ mod ffi {
         contents: Vec<u8>,
     }
 
+    #[swift_bridge(swift_repr = "struct")]
+    struct FFIRomInfo {
+        title: String,
+        maker: String,
+        game_id: String,
+        revision: u8,
+
+        size: usize,
+        crc32: u32,
+        md5: Vec<u8>,
+        sha1: Vec<u8>,
+    }
+
     extern "Rust" {
         type VirtualFriend;
 
         #[swift_bridge(init)]
-        fn new(rom_data: &[u8]) -> VirtualFriend;
+        fn new(rom_data: &[u8]) -> Option<VirtualFriend>;
 
         fn load_ram(&mut self, ram: &[u8]);
         fn save_ram(&self) -> Vec<u8>;
@@ -89,6 +111,8 @@ mod ffi {
         fn create_savestate(&self) -> FFIUnparsedSavestate;
 
         fn run_audio_frame(&mut self, inputs: FFIGamepadInputs, buffer_size: usize) -> FFIFrame;
+
+        fn rom_info(&self) -> FFIRomInfo;
     }
 
     extern "Rust" {
@@ -105,9 +129,16 @@ pub struct VirtualFriend {
 }
 
 impl VirtualFriend {
-    fn new(rom_data: &[u8]) -> Self {
-        VirtualFriend {
-            core: Mutex::new(virtualfriend::VirtualFriend::new(rom_data.to_vec())),
+    fn new(rom_data: &[u8]) -> Option<Self> {
+        match virtualfriend::VirtualFriend::new(rom_data.to_vec()) {
+            Ok(core) => Some(VirtualFriend {
+                core: Mutex::new(core),
+            }),
+            Err(error) => {
+                println!("Invalid ROM: {error}");
+
+                None
+            }
         }
     }
 
@@ -130,6 +161,10 @@ impl VirtualFriend {
     fn run_audio_frame(&mut self, inputs: FFIGamepadInputs, buffer_size: usize) -> FFIFrame {
         self.core.try_lock().expect("Could not acquire mutex lock for run_audio_frame. Emulator host is misconfigured; is it running on multiple threads?").run_audio_frame(inputs.into(), buffer_size).into()
     }
+
+    fn rom_info(&self) -> FFIRomInfo {
+        self.core.try_lock().expect("Could not acquire mutex lock for rom_info. Emulator host is misconfigured; is it running on multiple threads?").rom_info().into()
+    }
 }
 
 fn load_manifest(manifest_path: String) -> Option<FFIManifest> {
@@ -159,6 +194,10 @@ impl From<Frame> for FFIFrame {
             }),
             audio_left,
             audio_right,
+            crash: value.crash.map(|crash| FFICrashReport {
+                pc: crash.pc,
+                description: crash.to_string(),
+            }),
         }
     }
 }
@@ -274,6 +313,22 @@ impl From<FFIUnparsedSavestate> for UnparsedSavestate {
     }
 }
 
+impl From<&RomInfo> for FFIRomInfo {
+    fn from(value: &RomInfo) -> Self {
+        FFIRomInfo {
+            title: value.title.clone(),
+            maker: value.maker.clone(),
+            game_id: value.game_id.clone(),
+            revision: value.revision,
+
+            size: value.size,
+            crc32: value.crc32,
+            md5: value.md5.to_vec(),
+            sha1: value.sha1.to_vec(),
+        }
+    }
+}
+
 // TODO: I don't know how to make this a struct member, so for the sake of time it's just a standalone function
 fn unparsed_savestate_data(savestate: FFIUnparsedSavestate) -> Vec<u8> {
     let savestate: UnparsedSavestate = savestate.into();